@@ -0,0 +1,301 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use pdfium_render::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+
+use crate::error::AppError;
+use crate::export::flatten_page_strokes;
+use crate::pdf::{find_pdf_entry, load_strokes_from_file, string_key_to_u32, Stroke};
+use crate::persistence::{write_json, FileKind};
+use crate::render::{pixels_at_dpi, validate_scale};
+use crate::state::AppState;
+use crate::stroke_queue::flush_pending_strokes;
+use crate::timestamp::file_timestamp;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnotatedThumbnail {
+    path: String,
+    // sha256 of the page's strokes when rendered, a page is stale once they differ
+    strokes_hash: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AnnotatedThumbnails {
+    divisor: f32,
+    #[serde(deserialize_with = "string_key_to_u32")]
+    pages: HashMap<u32, AnnotatedThumbnail>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageImageFormat {
+    Png,
+    Jpeg,
+}
+
+impl PageImageFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            PageImageFormat::Png => "png",
+            PageImageFormat::Jpeg => "jpg",
+        }
+    }
+}
+
+fn strokes_hash(strokes: &[Stroke]) -> Result<String, String> {
+    let serialized = serde_json::to_vec(strokes).map_err(|e| e.to_string())?;
+    Ok(hex::encode(Sha256::digest(serialized)))
+}
+
+fn load_annotated_thumbnails(path: &Path) -> Result<AnnotatedThumbnails, String> {
+    if !path.exists() {
+        return Ok(AnnotatedThumbnails::default());
+    }
+
+    let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str::<AnnotatedThumbnails>(&data).map_err(|e| e.to_string())
+}
+
+// Renders thumbnails with the strokes drawn in, only for pages whose strokes changed since
+// the last run (or every page when the divisor changed). Strokes are flattened into the
+// in-memory document before rendering, so they scale with the page like in an export.
+fn render_annotated_thumbnails(
+    app_handle: &AppHandle,
+    pdfium: &Pdfium,
+    pdf_id: u64,
+    divisor: f32,
+) -> Result<AnnotatedThumbnails, String> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let pdf_entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+    let folder_path = app_data_dir.join(format!("pdf_{pdf_id}"));
+    let thumbs_dir = folder_path.join("annotated_thumbnails");
+    let index_path = folder_path.join("annotated_thumbs.json");
+
+    flush_pending_strokes(app_handle, pdf_id)?;
+    let strokes = {
+        let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+        let _guard = lock.lock().unwrap();
+        load_strokes_from_file(app_handle, &folder_path.join("strokes.json"))?
+    };
+
+    let mut thumbnails = load_annotated_thumbnails(&index_path)?;
+    if thumbnails.divisor != divisor {
+        thumbnails.divisor = divisor;
+        thumbnails.pages.clear();
+        // a different size makes every existing render stale
+        if thumbs_dir.exists() {
+            fs::remove_dir_all(&thumbs_dir).map_err(|e| e.to_string())?;
+        }
+    }
+
+    fs::create_dir_all(&thumbs_dir).map_err(|e| e.to_string())?;
+
+    let document = pdfium
+        .load_pdf_from_file(&pdf_entry.clone_path, None)
+        .map_err(|e| e.to_string())?;
+    let page_count = document.pages().len() as u32;
+
+    // pages that no longer exist don't keep stale entries around
+    thumbnails.pages.retain(|page, thumb| {
+        let exists = *page <= page_count;
+        if !exists {
+            let _ = fs::remove_file(&thumb.path);
+        }
+        exists
+    });
+
+    let timestamp = file_timestamp();
+    for index in 0..document.pages().len() {
+        let page_no = index as u32 + 1;
+        let page_strokes = strokes
+            .inner
+            .get(&page_no)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let hash = strokes_hash(page_strokes)?;
+
+        let up_to_date = thumbnails
+            .pages
+            .get(&page_no)
+            .is_some_and(|thumb| thumb.strokes_hash == hash && Path::new(&thumb.path).exists());
+        if up_to_date {
+            continue;
+        }
+
+        let mut page = document.pages().get(index).map_err(|e| e.to_string())?;
+        if !page_strokes.is_empty() {
+            flatten_page_strokes(&document, &mut page, page_strokes)?;
+        }
+
+        let width = (page.width().value / divisor) as i32;
+        let height = (page.height().value / divisor) as i32;
+        let bitmap = page
+            .render(width, height, None)
+            .map_err(|e| e.to_string())?;
+
+        let thumb_path = thumbs_dir.join(format!("page_{page_no}_{timestamp}.jpg"));
+        bitmap
+            .as_image()
+            .save(&thumb_path)
+            .map_err(|e| e.to_string())?;
+
+        let previous = thumbnails.pages.insert(
+            page_no,
+            AnnotatedThumbnail {
+                path: thumb_path.to_str().unwrap().to_string(),
+                strokes_hash: hash,
+            },
+        );
+        if let Some(previous) = previous {
+            if Path::new(&previous.path) != thumb_path {
+                let _ = fs::remove_file(&previous.path);
+            }
+        }
+    }
+
+    write_json(app_handle, &index_path, &thumbnails, FileKind::Thumbnails)?;
+
+    Ok(thumbnails)
+}
+
+#[tauri::command]
+pub async fn generate_annotated_thumbnails(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    divisor: Option<f32>,
+) -> Result<AnnotatedThumbnails, String> {
+    log::info!("Generating annotated thumbnails of pdf {pdf_id}");
+
+    let divisor =
+        divisor.unwrap_or_else(|| app_handle.state::<AppState>().settings().thumbnail_divisor);
+    if !divisor.is_finite() || divisor < 1.0 {
+        return Err(format!("Invalid thumbnail divisor: {divisor}"));
+    }
+
+    let pdfium = app_handle.state::<AppState>().pdfium()?;
+
+    // cpu heavy
+    tauri::async_runtime::spawn_blocking(move || {
+        render_annotated_thumbnails(&app_handle, &pdfium, pdf_id, divisor)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+fn write_annotated_page_images(
+    app_handle: &AppHandle,
+    pdfium: &Pdfium,
+    pdf_id: u64,
+    pages: &[u32],
+    scale: f32,
+    format: PageImageFormat,
+    output_dir: &Path,
+) -> Result<Vec<String>, AppError> {
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    let pdf_entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+
+    flush_pending_strokes(app_handle, pdf_id)?;
+    let strokes = {
+        let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+        let _guard = lock.lock().unwrap();
+        load_strokes_from_file(
+            app_handle,
+            &app_data_dir.join(format!("pdf_{pdf_id}/strokes.json")),
+        )?
+    };
+
+    let document = pdfium.load_pdf_from_file(&pdf_entry.clone_path, None)?;
+    let page_count = document.pages().len() as u32;
+    if let Some(page) = pages.iter().find(|&&p| p == 0 || p > page_count) {
+        return Err(AppError::InvalidInput(format!(
+            "Page {page} is out of range (1-{page_count})"
+        )));
+    }
+
+    fs::create_dir_all(output_dir)?;
+
+    // named after the export name so images of different documents don't collide, numbered
+    // in the requested order so a folder listing plays back as a slideshow
+    let export_name = pdf_entry.export_file_name();
+    let stem = export_name.strip_suffix(".pdf").unwrap_or(&export_name);
+    let digits = pages.len().to_string().len();
+
+    // strokes are flattened into the in-memory page, a page listed twice only gets them once
+    let mut flattened = HashSet::new();
+    let mut paths = Vec::with_capacity(pages.len());
+    for (i, page_no) in pages.iter().enumerate() {
+        let mut page = document.pages().get((page_no - 1) as PdfPageIndex)?;
+        if flattened.insert(*page_no) {
+            if let Some(page_strokes) = strokes.inner.get(page_no) {
+                flatten_page_strokes(&document, &mut page, page_strokes)?;
+            }
+        }
+
+        let size = page.page_size();
+        let (width, height) = pixels_at_dpi(size.width().value, size.height().value, scale * 72.0);
+        let image = page.render(width, height, None)?.as_image();
+
+        let path = output_dir.join(format!(
+            "{stem}_{:0digits$}_page{page_no}.{}",
+            i + 1,
+            format.extension()
+        ));
+        let saved = match format {
+            PageImageFormat::Png => image.save_with_format(&path, image::ImageFormat::Png),
+            // jpeg has no alpha channel
+            PageImageFormat::Jpeg => image
+                .to_rgb8()
+                .save_with_format(&path, image::ImageFormat::Jpeg),
+        };
+        saved.map_err(|e| AppError::Io(e.to_string()))?;
+        paths.push(path.to_string_lossy().into_owned());
+    }
+
+    Ok(paths)
+}
+
+// Renders `pages` with their strokes drawn in and writes them to `output_dir` as numbered
+// images, e.g. for a slideshow. `scale` is pixels per page point. Pages without strokes are
+// written as plain renders. Returns the paths in the order of `pages`.
+#[tauri::command]
+pub async fn export_annotated_page_images(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    pages: Vec<u32>,
+    scale: f32,
+    format: PageImageFormat,
+    output_dir: String,
+) -> Result<Vec<String>, AppError> {
+    log::info!("Exporting pages {pages:?} of pdf {pdf_id} as annotated images to {output_dir}");
+
+    if pages.is_empty() {
+        return Err(AppError::InvalidInput(
+            "Select at least one page to export".to_string(),
+        ));
+    }
+    validate_scale(scale).map_err(AppError::InvalidInput)?;
+
+    let pdfium = app_handle.state::<AppState>().pdfium()?;
+
+    // cpu heavy
+    tauri::async_runtime::spawn_blocking(move || {
+        write_annotated_page_images(
+            &app_handle,
+            &pdfium,
+            pdf_id,
+            &pages,
+            scale,
+            format,
+            Path::new(&output_dir),
+        )
+    })
+    .await?
+}
@@ -0,0 +1,178 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tauri::{AppHandle, Manager};
+
+use crate::error::AppError;
+use crate::pdf::{find_pdf_entry, load_bookmarks_from_file, load_strokes_from_file, PdfEntry};
+use crate::profiles::stored_profile_files;
+use crate::state::AppState;
+
+// Sidecars worth keeping when a pdf is removed. encryption.json goes along so encrypted
+// annotations stay readable with the same passphrase, profiles.json so the stored
+// annotations of the other profiles keep their owners.
+const ARCHIVED_FILES: [&str; 6] = [
+    "strokes.json",
+    "bookmarks.json",
+    "starred.json",
+    "note.json",
+    "encryption.json",
+    "profiles.json",
+];
+// Kept only alongside actual annotations
+const ARCHIVED_METADATA: [&str; 2] = ["encryption.json", "profiles.json"];
+
+// The sidecars of `folder_path` to archive, including the strokes_{name}.json and
+// bookmarks_{name}.json of profiles that aren't active
+fn archived_files(folder_path: &Path) -> Result<Vec<String>, AppError> {
+    let mut files: Vec<String> = ARCHIVED_FILES
+        .into_iter()
+        .filter(|name| folder_path.join(name).exists())
+        .map(String::from)
+        .collect();
+    for (path, _) in stored_profile_files(folder_path)? {
+        if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+            files.push(name.to_string());
+        }
+    }
+    Ok(files)
+}
+
+// Archived annotations live outside the pdf folders, keyed by the content hash of the document
+fn archive_dir(app_data_dir: &Path, content_hash: &str) -> PathBuf {
+    app_data_dir.join("annotation_archive").join(content_hash)
+}
+
+pub(crate) fn has_archived_annotations(app_data_dir: &Path, content_hash: &str) -> bool {
+    archive_dir(app_data_dir, content_hash).is_dir()
+}
+
+// Copies the annotations of a pdf about to be removed into the archive, replacing an older
+// archive of the same content. Returns false when there was nothing to keep or the entry
+// predates content hashing.
+pub(crate) fn archive_annotations(
+    app_data_dir: &Path,
+    pdf_entry: &PdfEntry,
+    folder_path: &Path,
+) -> Result<bool, AppError> {
+    let Some(content_hash) = &pdf_entry.content_hash else {
+        log::warn!(
+            "Not archiving annotations of pdf {}, it has no content hash",
+            pdf_entry.id
+        );
+        return Ok(false);
+    };
+
+    let files = archived_files(folder_path)?;
+    if files
+        .iter()
+        .all(|name| ARCHIVED_METADATA.contains(&name.as_str()))
+    {
+        return Ok(false);
+    }
+
+    let dir = archive_dir(app_data_dir, content_hash);
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+
+    for name in &files {
+        if let Err(e) = fs::copy(folder_path.join(name), dir.join(name)) {
+            let _ = fs::remove_dir_all(&dir);
+            return Err(e.into());
+        }
+    }
+
+    log::info!(
+        "Archived annotations of pdf {} under {content_hash}",
+        pdf_entry.id
+    );
+    Ok(true)
+}
+
+// Brings back the annotations archived when an earlier copy of the same document was removed,
+// then drops the archive. Refuses when the pdf already has annotations of its own. Returns
+// false when nothing was archived for it.
+#[tauri::command]
+pub fn restore_archived_annotations(app_handle: AppHandle, pdf_id: u64) -> Result<bool, AppError> {
+    log::info!("Restoring archived annotations of pdf {pdf_id}");
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    let pdf_entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+    let Some(content_hash) = &pdf_entry.content_hash else {
+        return Ok(false);
+    };
+
+    let dir = archive_dir(&app_data_dir, content_hash);
+    if !dir.is_dir() {
+        return Ok(false);
+    }
+
+    let folder_path = app_data_dir.join(format!("pdf_{pdf_id}"));
+
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    let strokes = load_strokes_from_file(&app_handle, &folder_path.join("strokes.json"))?;
+    let bookmarks = load_bookmarks_from_file(&app_handle, &folder_path.join("bookmarks.json"))?;
+    if !strokes.inner.is_empty() || !bookmarks.is_empty() {
+        return Err(AppError::InvalidInput(format!(
+            "PDF {pdf_id} already has annotations"
+        )));
+    }
+
+    // everything archive_annotations kept, the stored profile files included
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            fs::copy(entry.path(), folder_path.join(entry.file_name()))?;
+        }
+    }
+    // a restored encryption.json takes effect, the pdf has to be unlocked again
+    app_handle
+        .state::<AppState>()
+        .unlocked_keys
+        .lock()
+        .unwrap()
+        .remove(&pdf_id);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archives_the_stored_profile_files() {
+        let dir = std::env::temp_dir().join(format!("akda_archive_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("strokes.json"), "{}").unwrap();
+        fs::write(
+            dir.join("profiles.json"),
+            r#"{"active":"default","profiles":[
+                {"name":"default","separate_bookmarks":false,"created_at":null},
+                {"name":"exam","separate_bookmarks":true,"created_at":null}]}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("strokes_exam.json"), "{}").unwrap();
+        fs::write(dir.join("bookmarks_exam.json"), "[]").unwrap();
+
+        let mut files = archived_files(&dir).unwrap();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                "bookmarks_exam.json",
+                "profiles.json",
+                "strokes.json",
+                "strokes_exam.json"
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,9 +1,31 @@
-use std::{collections::HashMap, fs, path::PathBuf};
-
-use chrono::Utc;
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    fs,
+    path::PathBuf,
+};
+
+use chrono::DateTime;
 use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Manager};
 
+use crate::color::normalize_color;
+use crate::pdf::{load_editor_settings, read_pdf_entries, PdfEntry};
+use crate::persistence::{write_json, FileKind};
+use crate::timestamp::{id_timestamp, unique_id};
+
+// Order of a collection's members, unset keeps library (registration) order
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum SortBy {
+    // file name, case-insensitive A-Z
+    Name,
+    // newest import first
+    Added,
+    // furthest read first, by last page visited over page count
+    Progress,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Collection {
@@ -11,6 +33,24 @@ pub struct Collection {
     pub name: String,
     pub color: String,
     pub pdf_ids: HashMap<String, bool>,
+    #[serde(default)]
+    pub description: Option<String>,
+    // same format as generate_id, None for collections created before tracking
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+    #[serde(default)]
+    pub sort_preference: Option<SortBy>,
+    // pinned collections are listed before the others
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+impl Collection {
+    fn touch(&mut self) {
+        self.updated_at = Some(timestamp());
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -18,12 +58,12 @@ pub struct CollectionsFile {
     pub collections: Vec<Collection>,
 }
 
-fn collections_file_path(app: &AppHandle) -> Result<PathBuf, String> {
+pub(crate) fn collections_file_path(app: &AppHandle) -> Result<PathBuf, String> {
     let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
     Ok(dir.join("collections.json"))
 }
 
-fn read_collections(path: &PathBuf) -> Result<CollectionsFile, String> {
+pub(crate) fn read_collections(path: &PathBuf) -> Result<CollectionsFile, String> {
     if !path.exists() {
         return Ok(CollectionsFile::default());
     }
@@ -36,32 +76,91 @@ fn read_collections(path: &PathBuf) -> Result<CollectionsFile, String> {
     serde_json::from_str(&data).map_err(|e| e.to_string())
 }
 
-fn write_collections(path: &PathBuf, data: &CollectionsFile) -> Result<(), String> {
-    fs::create_dir_all(path.parent().ok_or("Invalid path")?).map_err(|e| e.to_string())?;
-    let json = serde_json::to_string_pretty(data).map_err(|e| e.to_string())?;
-    fs::write(path, json).map_err(|e| e.to_string())
+pub(crate) fn write_collections(
+    app: &AppHandle,
+    path: &PathBuf,
+    data: &CollectionsFile,
+) -> Result<(), String> {
+    write_json(app, path, data, FileKind::Collections)
 }
 
+fn timestamp() -> String {
+    id_timestamp()
+}
+
+// Unique even for collections created within the same millisecond
 fn generate_id() -> String {
-    Utc::now().format("%Y%m%d%H%M%S%3f").to_string()
+    unique_id()
 }
 
+// Pinned collections first, each group in insertion order
 #[tauri::command]
 pub fn get_collections(app: AppHandle) -> Result<Vec<Collection>, String> {
     let path = collections_file_path(&app)?;
-    let data = read_collections(&path)?;
+    let mut data = read_collections(&path)?;
+    data.collections.sort_by_key(|c| !c.pinned);
     Ok(data.collections)
 }
 
+// A collection without its membership map, for views that only need counts
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionSummary {
+    pub id: String,
+    pub name: String,
+    pub color: String,
+    pub pinned: bool,
+    pub pdf_count: usize,
+}
+
+impl From<&Collection> for CollectionSummary {
+    fn from(col: &Collection) -> Self {
+        Self {
+            id: col.id.clone(),
+            name: col.name.clone(),
+            color: col.color.clone(),
+            pinned: col.pinned,
+            pdf_count: col.pdf_ids.len(),
+        }
+    }
+}
+
+// Collections ordered by member count, ties by name (case-insensitive A-Z)
+#[tauri::command]
+pub fn get_collections_by_size(
+    app: AppHandle,
+    descending: bool,
+) -> Result<Vec<CollectionSummary>, String> {
+    let path = collections_file_path(&app)?;
+    let data = read_collections(&path)?;
+
+    let mut summaries: Vec<CollectionSummary> = data
+        .collections
+        .iter()
+        .map(CollectionSummary::from)
+        .collect();
+    summaries.sort_by_cached_key(|summary| summary.name.to_lowercase());
+    // stable, so equal counts keep the name order
+    if descending {
+        summaries.sort_by_key(|summary| Reverse(summary.pdf_count));
+    } else {
+        summaries.sort_by_key(|summary| summary.pdf_count);
+    }
+
+    Ok(summaries)
+}
+
 #[tauri::command]
 pub fn create_collection(
     app: AppHandle,
     name: String,
     color: String,
+    description: Option<String>,
 ) -> Result<Collection, String> {
     if name.trim().is_empty() {
         return Err("Collection name cannot be empty".into());
     }
+    let color = normalize_color(&color)?;
 
     let path = collections_file_path(&app)?;
     let mut data = read_collections(&path)?;
@@ -70,15 +169,21 @@ pub fn create_collection(
         return Err(format!("Collection with name '{}' already exists", name));
     }
 
+    let now = timestamp();
     let new_col = Collection {
         id: generate_id(),
         name,
         color,
         pdf_ids: HashMap::new(),
+        description: description.filter(|d| !d.trim().is_empty()),
+        created_at: Some(now.clone()),
+        updated_at: Some(now),
+        sort_preference: None,
+        pinned: false,
     };
 
     data.collections.push(new_col.clone());
-    write_collections(&path, &data)?;
+    write_collections(&app, &path, &data)?;
     Ok(new_col)
 }
 
@@ -109,7 +214,8 @@ pub fn rename_collection(app: AppHandle, id: String, new_name: String) -> Result
         .ok_or("Collection not found")?;
 
     col.name = new_name;
-    write_collections(&path, &data)?;
+    col.touch();
+    write_collections(&app, &path, &data)?;
     Ok(true)
 }
 
@@ -126,7 +232,7 @@ pub fn delete_collection(app: AppHandle, id: String) -> Result<bool, String> {
         return Err("Collection not found".into());
     }
 
-    write_collections(&path, &data)?;
+    write_collections(&app, &path, &data)?;
     Ok(true)
 }
 
@@ -136,6 +242,8 @@ pub fn change_collection_color(
     id: String,
     new_color: String,
 ) -> Result<bool, String> {
+    let new_color = normalize_color(&new_color)?;
+
     let path = collections_file_path(&app)?;
     let mut data = read_collections(&path)?;
 
@@ -146,7 +254,29 @@ pub fn change_collection_color(
         .ok_or("Collection not found")?;
 
     col.color = new_color;
-    write_collections(&path, &data)?;
+    col.touch();
+    write_collections(&app, &path, &data)?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn set_collection_description(
+    app: AppHandle,
+    id: String,
+    description: Option<String>,
+) -> Result<bool, String> {
+    let path = collections_file_path(&app)?;
+    let mut data = read_collections(&path)?;
+
+    let col = data
+        .collections
+        .iter_mut()
+        .find(|c| c.id == id)
+        .ok_or("Collection not found")?;
+
+    col.description = description.filter(|d| !d.trim().is_empty());
+    col.touch();
+    write_collections(&app, &path, &data)?;
     Ok(true)
 }
 
@@ -166,7 +296,8 @@ pub fn add_pdf_to_collection(
         .ok_or("Collection not found")?;
 
     col.pdf_ids.insert(pdf_id, true);
-    write_collections(&path, &data)?;
+    col.touch();
+    write_collections(&app, &path, &data)?;
     Ok(true)
 }
 
@@ -186,7 +317,8 @@ pub fn remove_pdf_from_collection(
         .ok_or("Collection not found")?;
 
     col.pdf_ids.remove(&pdf_id);
-    write_collections(&path, &data)?;
+    col.touch();
+    write_collections(&app, &path, &data)?;
     Ok(true)
 }
 
@@ -212,8 +344,9 @@ pub fn toggle_pdf_in_collection(
         col.pdf_ids.insert(pdf_id, true);
         true
     };
+    col.touch();
 
-    write_collections(&path, &data)?;
+    write_collections(&app, &path, &data)?;
     Ok(is_added)
 }
 
@@ -227,13 +360,182 @@ pub fn remove_pdf_from_all_collections(app: AppHandle, pdf_id: String) -> Result
         let before = col.pdf_ids.len();
         col.pdf_ids.remove(&pdf_id);
         if col.pdf_ids.len() < before {
+            col.touch();
             removed_count += 1;
         }
     }
 
     if removed_count > 0 {
-        write_collections(&path, &data)?;
+        write_collections(&app, &path, &data)?;
     }
 
     Ok(removed_count)
 }
+
+// Library entries that belong to no collection, in registry order
+#[tauri::command]
+pub fn list_uncategorized_pdfs(app: AppHandle) -> Result<Vec<PdfEntry>, String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let pdfs = read_pdf_entries(&app_data_dir.join("pdfs.json"))?;
+
+    let path = collections_file_path(&app)?;
+    let data = read_collections(&path)?;
+
+    // collections key pdfs by their id as a string
+    let categorized: HashSet<&str> = data
+        .collections
+        .iter()
+        .flat_map(|c| c.pdf_ids.keys().map(String::as_str))
+        .collect();
+
+    Ok(pdfs
+        .into_iter()
+        .filter(|pdf| !categorized.contains(pdf.id.to_string().as_str()))
+        .collect())
+}
+
+#[tauri::command]
+pub fn set_collection_sort(
+    app: AppHandle,
+    id: String,
+    sort: Option<SortBy>,
+) -> Result<bool, String> {
+    log::info!("Setting sort of collection {id} to {sort:?}");
+
+    let path = collections_file_path(&app)?;
+    let mut data = read_collections(&path)?;
+
+    let col = data
+        .collections
+        .iter_mut()
+        .find(|c| c.id == id)
+        .ok_or("Collection not found")?;
+
+    col.sort_preference = sort;
+    col.touch();
+    write_collections(&app, &path, &data)?;
+    Ok(true)
+}
+
+// Returns whether the collection is pinned after the toggle
+#[tauri::command]
+pub fn toggle_collection_pin(app: AppHandle, id: String) -> Result<bool, String> {
+    let path = collections_file_path(&app)?;
+    let mut data = read_collections(&path)?;
+
+    let col = data
+        .collections
+        .iter_mut()
+        .find(|c| c.id == id)
+        .ok_or("Collection not found")?;
+
+    col.pinned = !col.pinned;
+    col.touch();
+    let pinned = col.pinned;
+    write_collections(&app, &path, &data)?;
+    Ok(pinned)
+}
+
+impl SortBy {
+    // Whether the order described on each variant runs from low to high
+    pub(crate) fn is_ascending(&self) -> bool {
+        matches!(self, SortBy::Name)
+    }
+}
+
+// Sorts in the order described on each SortBy variant
+pub(crate) fn sort_pdfs(app: &AppHandle, pdfs: &mut [PdfEntry], sort: SortBy) {
+    match sort {
+        SortBy::Name => {
+            pdfs.sort_by_cached_key(|pdf| pdf.file_name.to_lowercase());
+        }
+        SortBy::Added => {
+            // entries registered before import times were tracked go last
+            pdfs.sort_by_cached_key(|pdf| {
+                Reverse(
+                    pdf.created_at
+                        .as_deref()
+                        .and_then(|at| DateTime::parse_from_rfc3339(at).ok()),
+                )
+            });
+        }
+        SortBy::Progress => {
+            let progress: HashMap<u64, f64> = pdfs
+                .iter()
+                .map(|pdf| (pdf.id, reading_progress(app, pdf)))
+                .collect();
+            pdfs.sort_by(|a, b| progress[&b.id].total_cmp(&progress[&a.id]));
+        }
+    }
+}
+
+// Share of the document read, 0 when it was never opened or its page count isn't known yet
+fn reading_progress(app: &AppHandle, pdf: &PdfEntry) -> f64 {
+    // the stored count, opening every member to count pages would stall the listing
+    let page_count = pdf.page_count.unwrap_or(0);
+    if page_count == 0 {
+        return 0.0;
+    }
+
+    let current_page = load_editor_settings(app.clone(), pdf.id)
+        .map(|settings| settings.current_page)
+        .unwrap_or(0);
+    current_page as f64 / page_count as f64
+}
+
+// Members of a collection joined with the registry, sorted by `sort` or else the
+// collection's saved preference. Ids no longer in the library are skipped.
+#[tauri::command]
+pub fn get_collection_pdfs(
+    app: AppHandle,
+    id: String,
+    sort: Option<SortBy>,
+) -> Result<Vec<PdfEntry>, String> {
+    log::info!("Listing pdfs of collection {id}");
+
+    let path = collections_file_path(&app)?;
+    let data = read_collections(&path)?;
+
+    let col = data
+        .collections
+        .iter()
+        .find(|c| c.id == id)
+        .ok_or("Collection not found")?;
+
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let mut pdfs: Vec<PdfEntry> = read_pdf_entries(&app_data_dir.join("pdfs.json"))?
+        .into_iter()
+        .filter(|pdf| col.pdf_ids.contains_key(&pdf.id.to_string()))
+        .collect();
+
+    if let Some(sort) = sort.or(col.sort_preference) {
+        sort_pdfs(&app, &mut pdfs, sort);
+    }
+
+    Ok(pdfs)
+}
+
+// Drops memberships of pdfs not in `known`, returns how many were dropped
+pub(crate) fn retain_known_members(
+    app: &AppHandle,
+    known: &HashSet<String>,
+) -> Result<usize, String> {
+    let path = collections_file_path(app)?;
+    let mut data = read_collections(&path)?;
+    let mut dropped = 0;
+
+    for col in &mut data.collections {
+        let before = col.pdf_ids.len();
+        col.pdf_ids.retain(|pdf_id, _| known.contains(pdf_id));
+        if col.pdf_ids.len() < before {
+            dropped += before - col.pdf_ids.len();
+            col.touch();
+        }
+    }
+
+    if dropped > 0 {
+        write_collections(app, &path, &data)?;
+    }
+
+    Ok(dropped)
+}
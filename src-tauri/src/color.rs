@@ -0,0 +1,205 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+// Normalized color, parsed from the free-form strings the frontend stores
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Rgba {
+    pub fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    // Alpha as a 0.0..=1.0 fraction
+    pub fn alpha(&self) -> f64 {
+        f64::from(self.a) / 255.0
+    }
+
+    // Scales the alpha channel, used to fold a stroke's opacity into its color
+    pub fn with_opacity(self, opacity: f64) -> Self {
+        let a = (self.alpha() * opacity.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Self { a, ..self }
+    }
+
+    // WCAG relative luminance of the color channels, alpha is ignored
+    pub fn relative_luminance(&self) -> f64 {
+        let linear = |c: u8| {
+            let c = f64::from(c) / 255.0;
+            if c <= 0.03928 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+
+        0.2126 * linear(self.r) + 0.7152 * linear(self.g) + 0.0722 * linear(self.b)
+    }
+}
+
+impl fmt::Display for Rgba {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{:02x}{:02x}{:02x}{:02x}",
+            self.r, self.g, self.b, self.a
+        )
+    }
+}
+
+impl FromStr for Rgba {
+    type Err = String;
+
+    // Accepts #rgb, #rrggbb, #rrggbbaa, rgb(r, g, b) and rgba(r, g, b, a) with a in 0..=1
+    fn from_str(color: &str) -> Result<Self, Self::Err> {
+        let trimmed = color.trim();
+        let invalid = || format!("Unsupported color: '{color}'");
+
+        if let Some(hex) = trimmed.strip_prefix('#') {
+            return parse_hex(hex).ok_or_else(invalid);
+        }
+
+        let lower = trimmed.to_ascii_lowercase();
+        let (args, has_alpha) = if let Some(rest) = lower.strip_prefix("rgba(") {
+            (rest.strip_suffix(')').ok_or_else(invalid)?, true)
+        } else if let Some(rest) = lower.strip_prefix("rgb(") {
+            (rest.strip_suffix(')').ok_or_else(invalid)?, false)
+        } else {
+            return Err(invalid());
+        };
+
+        parse_functional(args, has_alpha).ok_or_else(invalid)
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Rgba> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channel = |s: &str| u8::from_str_radix(s, 16).ok();
+    // #rgb shorthand doubles each digit, so "f" becomes "ff"
+    let short = |i: usize| channel(&hex[i..i + 1].repeat(2));
+
+    match hex.len() {
+        3 => Some(Rgba::new(short(0)?, short(1)?, short(2)?, 255)),
+        6 | 8 => Some(Rgba::new(
+            channel(&hex[0..2])?,
+            channel(&hex[2..4])?,
+            channel(&hex[4..6])?,
+            if hex.len() == 8 {
+                channel(&hex[6..8])?
+            } else {
+                255
+            },
+        )),
+        _ => None,
+    }
+}
+
+fn parse_functional(args: &str, has_alpha: bool) -> Option<Rgba> {
+    let parts: Vec<&str> = args.split(',').map(str::trim).collect();
+    if parts.len() != if has_alpha { 4 } else { 3 } {
+        return None;
+    }
+
+    let channel = |s: &str| s.parse::<u8>().ok();
+    let a = if has_alpha {
+        let alpha = parts[3].parse::<f64>().ok()?;
+        if !(0.0..=1.0).contains(&alpha) {
+            return None;
+        }
+        (alpha * 255.0).round() as u8
+    } else {
+        255
+    };
+
+    Some(Rgba::new(
+        channel(parts[0])?,
+        channel(parts[1])?,
+        channel(parts[2])?,
+        a,
+    ))
+}
+
+// Checks a color string without changing it, for settings the user typed in
+pub fn validate_color(color: &str) -> Result<(), String> {
+    color.parse::<Rgba>().map(|_| ())
+}
+
+// Parses a color and returns the form it is stored in: lowercase #rrggbb, or #rrggbbaa when
+// it isn't opaque, so the same color is always stored the same way
+pub fn normalize_color(color: &str) -> Result<String, String> {
+    let rgba = color.parse::<Rgba>()?;
+    if rgba.a == 255 {
+        Ok(format!("#{:02x}{:02x}{:02x}", rgba.r, rgba.g, rgba.b))
+    } else {
+        Ok(rgba.to_string())
+    }
+}
+
+// Black or white, whichever reads better on top of the given color
+#[tauri::command]
+pub fn contrasting_text_color(hex: String) -> Result<String, String> {
+    let luminance = hex.parse::<Rgba>()?.relative_luminance();
+
+    // the luminance where black and white text have equal contrast ratios
+    let color = if luminance > 0.179 {
+        "#000000"
+    } else {
+        "#ffffff"
+    };
+    Ok(color.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_accepted_forms() {
+        let accepted = [
+            ("#FFF", "#ffffff"),
+            ("#1F2937", "#1f2937"),
+            ("  #1f2937  ", "#1f2937"),
+            ("#1f2937ff", "#1f2937"),
+            ("#1F293780", "#1f293780"),
+            ("rgb(31, 41, 55)", "#1f2937"),
+            ("RGBA(31,41,55,0.5)", "#1f293780"),
+            ("rgba(31, 41, 55, 1)", "#1f2937"),
+            ("rgba(0, 0, 0, 0)", "#00000000"),
+        ];
+        for (input, stored) in accepted {
+            assert_eq!(normalize_color(input).as_deref(), Ok(stored), "{input}");
+        }
+    }
+
+    #[test]
+    fn rejects_unparseable_colors() {
+        let rejected = [
+            "",
+            "#",
+            "#ff",
+            "#fffff",
+            "#ggg",
+            "#1f2937f",
+            "red",
+            "rgb(256, 0, 0)",
+            "rgb(-1, 0, 0)",
+            "rgb(1, 2)",
+            "rgb(1, 2, 3",
+            "rgba(1, 2, 3)",
+            "rgba(1, 2, 3, 1.5)",
+            "hsl(0, 100%, 50%)",
+        ];
+        for input in rejected {
+            assert!(normalize_color(input).is_err(), "{input:?} was accepted");
+        }
+    }
+}
@@ -0,0 +1,432 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tauri::{AppHandle, Manager};
+
+use crate::collections::{collections_file_path, read_collections, write_collections};
+use crate::pdf::{
+    load_thumbnails_from_file, read_pdf_entries, write_pdf_entries, PdfEditorSyncProps, PdfEntry,
+};
+use crate::persistence::{write_json, FileKind};
+use crate::state::AppState;
+use crate::storage::forget_disk_usage;
+use crate::stroke_queue::flush_all_pending_strokes;
+use crate::study_sets::{read_study_sets, study_sets_path, write_study_sets};
+
+// Rounds of locking compact_ids tries while pdfs keep being registered
+const MAX_LOCK_ATTEMPTS: usize = 3;
+
+// Renames done so far, undone in reverse when a later step fails
+#[derive(Default)]
+struct Renames {
+    done: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Renames {
+    fn rename(&mut self, from: &Path, to: &Path) -> Result<(), String> {
+        fs::rename(from, to).map_err(|e| format!("Failed to move {}: {e}", from.display()))?;
+        self.done.push((from.to_path_buf(), to.to_path_buf()));
+        Ok(())
+    }
+
+    fn undo(self) {
+        for (from, to) in self.done.into_iter().rev() {
+            if let Err(e) = fs::rename(&to, &from) {
+                log::error!(
+                    "Failed to move {} back to {}: {e}",
+                    to.display(),
+                    from.display()
+                );
+            }
+        }
+    }
+}
+
+fn moved_path(path: &str, old_folder: &Path, new_folder: &Path) -> String {
+    match Path::new(path).strip_prefix(old_folder) {
+        Ok(relative) => new_folder.join(relative).to_string_lossy().into_owned(),
+        Err(_) => path.to_string(),
+    }
+}
+
+// Points the sidecars of a moved folder that hold paths or the id at the new location.
+// All of them are caches or settings, a failure is logged rather than undoing the move.
+fn update_sidecars(app_handle: &AppHandle, old_folder: &Path, new_folder: &Path, new_id: u64) {
+    let thumbs_path = new_folder.join("thumbs.json");
+    if thumbs_path.exists() {
+        let updated = load_thumbnails_from_file(&thumbs_path).and_then(|mut thumbnails| {
+            for path in thumbnails.inner.values_mut() {
+                *path = moved_path(path, old_folder, new_folder);
+            }
+            write_json(app_handle, &thumbs_path, &thumbnails, FileKind::Thumbnails)
+        });
+        if let Err(e) = updated {
+            log::warn!("Failed to update thumbnails of pdf {new_id}: {e}");
+        }
+    }
+
+    let editor_path = new_folder.join("editor.json");
+    if editor_path.exists() {
+        let updated = fs::read_to_string(&editor_path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| {
+                serde_json::from_str::<PdfEditorSyncProps>(&data).map_err(|e| e.to_string())
+            })
+            .and_then(|mut settings| {
+                settings.id = new_id;
+                write_json(
+                    app_handle,
+                    &editor_path,
+                    &settings,
+                    FileKind::EditorSettings,
+                )
+            });
+        if let Err(e) = updated {
+            log::warn!("Failed to update editor settings of pdf {new_id}: {e}");
+        }
+    }
+
+    // annotated thumbnails are a pure cache, regenerating beats rewriting it
+    let _ = fs::remove_dir_all(new_folder.join("annotated_thumbnails"));
+    let _ = fs::remove_file(new_folder.join("annotated_thumbs.json"));
+}
+
+// Moves every renumbered folder into `staging` under its new name, with the clone renamed
+fn stage_folders(
+    app_data_dir: &Path,
+    staging: &Path,
+    remap: &BTreeMap<u64, u64>,
+    renames: &mut Renames,
+) -> Result<(), String> {
+    fs::create_dir_all(staging).map_err(|e| e.to_string())?;
+
+    for (&old_id, &new_id) in remap {
+        let old_folder = app_data_dir.join(format!("pdf_{old_id}"));
+        let staged_folder = staging.join(format!("pdf_{new_id}"));
+
+        let clone_size = fs::metadata(old_folder.join(format!("{old_id}.pdf")))
+            .map_err(|e| format!("PDF file of pdf {old_id} is missing: {e}"))?
+            .len();
+
+        renames.rename(&old_folder, &staged_folder)?;
+        let staged_clone = staged_folder.join(format!("{new_id}.pdf"));
+        renames.rename(&staged_folder.join(format!("{old_id}.pdf")), &staged_clone)?;
+
+        // verify before anything outside the staging dir changes
+        let staged_size = fs::metadata(&staged_clone)
+            .map_err(|e| e.to_string())?
+            .len();
+        if staged_size != clone_size {
+            return Err(format!("PDF file of pdf {old_id} changed while moving"));
+        }
+    }
+
+    Ok(())
+}
+
+// Renumbers the library to ids 1..=n in registry order, renaming folders and updating the
+// registry, collections, study sets and sidecars. Folders are moved into a staging dir first
+// and checked, then swapped in; any failure up to writing the registry puts everything back.
+// Returns the old id -> new id mapping of the pdfs that changed.
+#[tauri::command]
+pub fn compact_ids(app_handle: AppHandle) -> Result<BTreeMap<u64, u64>, String> {
+    log::info!("Compacting pdf ids");
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let state = app_handle.state::<AppState>();
+
+    // background work holds ids and paths that are about to change
+    let busy = !state.export_jobs.lock().unwrap().is_empty()
+        || !state.ocr_jobs.lock().unwrap().is_empty()
+        || !state.extraction_progress.lock().unwrap().is_empty();
+    if busy {
+        return Err("Wait for running exports, OCR and extractions to finish".to_string());
+    }
+
+    // queued strokes would be written to the folders under their old ids
+    flush_all_pending_strokes(&app_handle);
+
+    let state_path = app_data_dir.join("pdfs.json");
+
+    // The registry is read again once every affected pdf lock and the registry lock are held,
+    // the first read only decides which pdfs to lock. A pdf registered in between needs a lock
+    // that isn't held yet, so the locks are taken again with it included.
+    let mut candidates = compact_remap(&read_pdf_entries(&state_path)?);
+    for _ in 0..MAX_LOCK_ATTEMPTS {
+        if candidates.is_empty() {
+            return Ok(candidates);
+        }
+
+        // every affected pdf lock, then the registry, like single-pdf commands do. An id can
+        // be both an old and a new one, the set keeps it from being locked twice
+        let locked_ids: BTreeSet<u64> = candidates
+            .iter()
+            .flat_map(|(&old_id, &new_id)| [old_id, new_id])
+            .collect();
+        let locks: Vec<_> = locked_ids.iter().map(|&id| state.pdf_lock(id)).collect();
+        let _pdf_guards: Vec<_> = locks.iter().map(|lock| lock.lock().unwrap()).collect();
+        let _registry_guard = state.registry_lock.lock().unwrap();
+
+        let pdfs = read_pdf_entries(&state_path)?;
+        let remap = compact_remap(&pdfs);
+        let all_locked = remap
+            .iter()
+            .all(|(old_id, new_id)| locked_ids.contains(old_id) && locked_ids.contains(new_id));
+        if all_locked {
+            return renumber(&app_handle, &app_data_dir, pdfs, remap);
+        }
+        candidates = remap;
+    }
+
+    Err("The library kept changing, try again".to_string())
+}
+
+// New id of every pdf whose id changes when the library is numbered 1..=n in registry order
+fn compact_remap(pdfs: &[PdfEntry]) -> BTreeMap<u64, u64> {
+    pdfs.iter()
+        .zip(1u64..)
+        .filter(|(pdf, new_id)| pdf.id != *new_id)
+        .map(|(pdf, new_id)| (pdf.id, new_id))
+        .collect()
+}
+
+// The renumbering itself, with the locks of every pdf in `remap` and the registry held
+fn renumber(
+    app_handle: &AppHandle,
+    app_data_dir: &Path,
+    pdfs: Vec<PdfEntry>,
+    remap: BTreeMap<u64, u64>,
+) -> Result<BTreeMap<u64, u64>, String> {
+    let state = app_handle.state::<AppState>();
+    let state_path = app_data_dir.join("pdfs.json");
+
+    // strokes queued since the flush can't follow their pdf to its new id
+    let queued = {
+        let pending = state.pending_strokes.lock().unwrap();
+        remap
+            .keys()
+            .any(|id| pending.get(id).is_some_and(|queue| !queue.is_empty()))
+    };
+    if queued {
+        return Err("Strokes are still being saved, try again".to_string());
+    }
+
+    // a stray folder (e.g. left by a crash) would be overwritten by a renamed one
+    for &new_id in remap.values() {
+        let target = app_data_dir.join(format!("pdf_{new_id}"));
+        if target.exists() && !remap.contains_key(&new_id) {
+            return Err(format!(
+                "pdf_{new_id} exists but isn't in the library, move it away first"
+            ));
+        }
+    }
+
+    let collections_path = collections_file_path(app_handle)?;
+    let mut collections = read_collections(&collections_path)?;
+    let study_sets_path = study_sets_path(app_handle)?;
+    let _study_sets_guard = state.study_sets_lock.lock().unwrap();
+    let study_sets = read_study_sets(&study_sets_path)?;
+
+    let staging = app_data_dir.join("compact_staging");
+    let mut renames = Renames::default();
+
+    let swapped = stage_folders(app_data_dir, &staging, &remap, &mut renames).and_then(|_| {
+        for &new_id in remap.values() {
+            renames.rename(
+                &staging.join(format!("pdf_{new_id}")),
+                &app_data_dir.join(format!("pdf_{new_id}")),
+            )?;
+        }
+        Ok(())
+    });
+
+    let new_pdfs: Vec<PdfEntry> = pdfs
+        .iter()
+        .map(|pdf| {
+            let Some(&new_id) = remap.get(&pdf.id) else {
+                return pdf.clone();
+            };
+            let old_folder = app_data_dir.join(format!("pdf_{}", pdf.id));
+            let new_folder = app_data_dir.join(format!("pdf_{new_id}"));
+            PdfEntry {
+                id: new_id,
+                clone_path: new_folder
+                    .join(format!("{new_id}.pdf"))
+                    .to_string_lossy()
+                    .into_owned(),
+                cover_path: moved_path(&pdf.cover_path, &old_folder, &new_folder),
+                ..pdf.clone()
+            }
+        })
+        .collect();
+
+    let string_remap: HashMap<String, String> = remap
+        .iter()
+        .map(|(old_id, new_id)| (old_id.to_string(), new_id.to_string()))
+        .collect();
+    for collection in &mut collections.collections {
+        collection.pdf_ids = collection
+            .pdf_ids
+            .drain()
+            .map(|(id, value)| (string_remap.get(&id).cloned().unwrap_or(id), value))
+            .collect();
+    }
+
+    let mut new_study_sets = study_sets.clone();
+    for item in new_study_sets
+        .study_sets
+        .iter_mut()
+        .flat_map(|set| set.items.iter_mut())
+    {
+        if let Some(&new_id) = remap.get(&item.pdf_id) {
+            item.pdf_id = new_id;
+        }
+    }
+
+    // the registry is the commit point, study sets and collections follow it or it's reverted
+    let committed = swapped
+        .and_then(|_| write_pdf_entries(app_handle, &state_path, &new_pdfs))
+        .and_then(|_| {
+            write_study_sets(app_handle, &study_sets_path, &new_study_sets).map_err(|e| {
+                if let Err(revert) = write_pdf_entries(app_handle, &state_path, &pdfs) {
+                    log::error!("Failed to restore the registry: {revert}");
+                }
+                e
+            })
+        })
+        .and_then(|_| {
+            write_collections(app_handle, &collections_path, &collections).map_err(|e| {
+                if let Err(revert) = write_pdf_entries(app_handle, &state_path, &pdfs) {
+                    log::error!("Failed to restore the registry: {revert}");
+                }
+                if let Err(revert) = write_study_sets(app_handle, &study_sets_path, &study_sets) {
+                    log::error!("Failed to restore the study sets: {revert}");
+                }
+                e
+            })
+        });
+
+    if let Err(e) = committed {
+        log::error!("Compacting ids failed, restoring the previous layout: {e}");
+        renames.undo();
+        let _ = fs::remove_dir(&staging);
+        return Err(e);
+    }
+
+    let _ = fs::remove_dir(&staging);
+    forget_disk_usage(app_handle, None);
+
+    // unlocked annotation keys follow their pdfs to the new ids
+    {
+        let mut keys = state.unlocked_keys.lock().unwrap();
+        let moved: Vec<_> = remap
+            .iter()
+            .filter_map(|(old_id, &new_id)| keys.remove(old_id).map(|key| (new_id, key)))
+            .collect();
+        keys.extend(moved);
+    }
+
+    for (&old_id, &new_id) in &remap {
+        update_sidecars(
+            app_handle,
+            &app_data_dir.join(format!("pdf_{old_id}")),
+            &app_data_dir.join(format!("pdf_{new_id}")),
+            new_id,
+        );
+    }
+
+    log::info!("Renumbered {} pdf(s)", remap.len());
+    Ok(remap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A scratch app data dir with a folder and clone for each id
+    fn library(name: &str, ids: &[u64]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("akda_compact_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for id in ids {
+            let folder = dir.join(format!("pdf_{id}"));
+            fs::create_dir_all(&folder).unwrap();
+            fs::write(folder.join(format!("{id}.pdf")), format!("pdf {id}")).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn stages_folders_under_their_new_ids() {
+        let dir = library("stage", &[3, 5]);
+        let staging = dir.join("compact_staging");
+        let remap = BTreeMap::from([(3, 1), (5, 2)]);
+
+        let mut renames = Renames::default();
+        stage_folders(&dir, &staging, &remap, &mut renames).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(staging.join("pdf_1/1.pdf")).unwrap(),
+            "pdf 3"
+        );
+        assert_eq!(
+            fs::read_to_string(staging.join("pdf_2/2.pdf")).unwrap(),
+            "pdf 5"
+        );
+        assert!(!dir.join("pdf_3").exists());
+        assert!(!dir.join("pdf_5").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn undo_puts_staged_folders_back_after_a_failure() {
+        let dir = library("rollback", &[3, 5]);
+        // listed but without a clone, staging fails on it after moving pdf_3 and pdf_5
+        fs::create_dir_all(dir.join("pdf_7")).unwrap();
+        let staging = dir.join("compact_staging");
+        let remap = BTreeMap::from([(3, 1), (5, 2), (7, 4)]);
+
+        let mut renames = Renames::default();
+        assert!(stage_folders(&dir, &staging, &remap, &mut renames).is_err());
+        renames.undo();
+
+        assert_eq!(
+            fs::read_to_string(dir.join("pdf_3/3.pdf")).unwrap(),
+            "pdf 3"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("pdf_5/5.pdf")).unwrap(),
+            "pdf 5"
+        );
+        assert!(dir.join("pdf_7").exists());
+        assert!(!staging.join("pdf_1").exists());
+        assert!(!staging.join("pdf_2").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn remaps_only_ids_that_change() {
+        let entry = |id| {
+            PdfEntry::new(
+                id,
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                None,
+                None,
+            )
+        };
+        let pdfs = vec![entry(1), entry(3), entry(4), entry(9)];
+        assert_eq!(
+            compact_remap(&pdfs),
+            BTreeMap::from([(3, 2), (4, 3), (9, 4)])
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::fs;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::error::AppError;
+use crate::pdf::{find_pdf_entry, load_strokes_from_file, PdfStrokes, Stroke};
+use crate::persistence::{read_data_file, to_json_string, write_json_as, FileKind, JsonFormat};
+use crate::state::AppState;
+use crate::stroke_queue::flush_pending_strokes;
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct CompactStrokesReport {
+    // on disk, encrypted files included
+    bytes_before: u64,
+    bytes_after: u64,
+    removed_pages: usize,
+    removed_strokes: usize,
+    assigned_ids: usize,
+}
+
+// Tidies the strokes of one page: drops strokes without points, gives legacy strokes an id
+// and keeps only the last of strokes sharing an id, like `PdfStrokes::merge` would have.
+// Existing ids stay as they are, the frontend refers to strokes by them.
+fn tidy_page(strokes: Vec<Stroke>, report: &mut CompactStrokesReport) -> Vec<Stroke> {
+    let mut tidy: Vec<Stroke> = Vec::with_capacity(strokes.len());
+    let mut positions: HashMap<String, usize> = HashMap::new();
+
+    for mut stroke in strokes {
+        if stroke.path.is_empty() {
+            report.removed_strokes += 1;
+            continue;
+        }
+
+        let id = stroke
+            .id
+            .get_or_insert_with(|| {
+                report.assigned_ids += 1;
+                uuid::Uuid::new_v4().to_string()
+            })
+            .clone();
+        match positions.get(&id) {
+            Some(&position) => {
+                tidy[position] = stroke;
+                report.removed_strokes += 1;
+            }
+            None => {
+                positions.insert(id, tidy.len());
+                tidy.push(stroke);
+            }
+        }
+    }
+
+    tidy
+}
+
+// Rewrites strokes.json without the leftovers of undo and erasing: pages whose strokes are
+// all gone, strokes without points and duplicate ids. Always written minified, whatever
+// `data_file_format` says. A missing or already tidy file is left alone.
+#[tauri::command]
+pub fn compact_strokes(
+    app_handle: AppHandle,
+    pdf_id: u64,
+) -> Result<CompactStrokesReport, AppError> {
+    log::info!("Compacting strokes of pdf {pdf_id}");
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    find_pdf_entry(&app_data_dir, pdf_id)?;
+    let strokes_path = app_data_dir.join(format!("pdf_{pdf_id}/strokes.json"));
+
+    flush_pending_strokes(&app_handle, pdf_id)?;
+
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    let Some(data) = read_data_file(&app_handle, &strokes_path)? else {
+        return Ok(CompactStrokesReport::default());
+    };
+    let bytes_before = fs::metadata(&strokes_path)?.len();
+    let mut report = CompactStrokesReport {
+        bytes_before,
+        bytes_after: bytes_before,
+        ..CompactStrokesReport::default()
+    };
+
+    let strokes = load_strokes_from_file(&app_handle, &strokes_path)?;
+    let mut compacted = PdfStrokes::new();
+    for (page, page_strokes) in strokes.inner {
+        let page_strokes = tidy_page(page_strokes, &mut report);
+        if page_strokes.is_empty() {
+            report.removed_pages += 1;
+        } else {
+            compacted.inner.insert(page, page_strokes);
+        }
+    }
+
+    let serialized = to_json_string(&compacted, FileKind::Strokes, JsonFormat::Compact)?;
+    let unchanged =
+        report.removed_pages == 0 && report.removed_strokes == 0 && report.assigned_ids == 0;
+    if unchanged && serialized.len() >= data.len() {
+        return Ok(report);
+    }
+
+    write_json_as(
+        &app_handle,
+        &strokes_path,
+        &compacted,
+        FileKind::Strokes,
+        JsonFormat::Compact,
+    )?;
+    report.bytes_after = fs::metadata(&strokes_path)?.len();
+
+    log::info!(
+        "Compacted strokes of pdf {pdf_id} from {} to {} bytes",
+        report.bytes_before,
+        report.bytes_after
+    );
+    Ok(report)
+}
@@ -0,0 +1,156 @@
+use image::{imageops, DynamicImage, Rgb, RgbImage};
+use pdfium_render::prelude::*;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::collections::get_collection_pdfs;
+use crate::error::AppError;
+use crate::state::AppState;
+
+const MAX_COLUMNS: u32 = 20;
+// cell layout in pixels, covers are fitted into COVER_WIDTH x COVER_HEIGHT
+const COVER_WIDTH: u32 = 200;
+const COVER_HEIGHT: u32 = 280;
+const CAPTION_HEIGHT: u32 = 28;
+const PADDING: u32 = 16;
+const CAPTION_FONT_SIZE: f32 = 11.0;
+// file names longer than this are cut with an ellipsis, roughly what fits under a cover
+const MAX_CAPTION_CHARS: usize = 34;
+
+const BACKGROUND: Rgb<u8> = Rgb([255, 255, 255]);
+const PLACEHOLDER: Rgb<u8> = Rgb([224, 224, 224]);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ContactSheetReport {
+    output_path: String,
+    columns: u32,
+    rows: u32,
+    // members drawn as a placeholder because their cover couldn't be loaded
+    placeholders: Vec<u64>,
+}
+
+fn caption_text(file_name: &str) -> String {
+    let name = file_name.strip_suffix(".pdf").unwrap_or(file_name);
+    if name.chars().count() <= MAX_CAPTION_CHARS {
+        return name.to_string();
+    }
+    let cut: String = name.chars().take(MAX_CAPTION_CHARS - 3).collect();
+    format!("{}...", cut.trim_end())
+}
+
+// The image crate can't draw text, so captions are laid out on a scratch pdf page with a
+// built-in font and rendered like any other page
+fn render_caption(
+    document: &mut PdfDocument,
+    font: PdfFontToken,
+    text: &str,
+) -> Result<DynamicImage, AppError> {
+    let size = PdfPagePaperSize::Custom(
+        PdfPoints::new(COVER_WIDTH as f32),
+        PdfPoints::new(CAPTION_HEIGHT as f32),
+    );
+    let mut page = document.pages_mut().create_page_at_end(size)?;
+    page.objects_mut().create_text_object(
+        PdfPoints::new(2.0),
+        PdfPoints::new((CAPTION_HEIGHT as f32 - CAPTION_FONT_SIZE) / 2.0),
+        text,
+        font,
+        PdfPoints::new(CAPTION_FONT_SIZE),
+    )?;
+
+    let bitmap = page.render(COVER_WIDTH as i32, CAPTION_HEIGHT as i32, None)?;
+    Ok(bitmap.as_image())
+}
+
+// Cover scaled to fit its cell, None when the file is missing or not an image
+fn load_cover(cover_path: &str) -> Option<DynamicImage> {
+    if cover_path.is_empty() {
+        return None;
+    }
+    match image::open(cover_path) {
+        Ok(cover) => Some(cover.resize(COVER_WIDTH, COVER_HEIGHT, imageops::FilterType::Triangle)),
+        Err(e) => {
+            log::warn!("Using a placeholder for cover {cover_path}: {e}");
+            None
+        }
+    }
+}
+
+// Lays out the covers of a collection's members in a grid with their file names underneath,
+// in the collection's sort order, and saves it as a png. Members without a usable cover get
+// a grey placeholder cell.
+#[tauri::command]
+pub fn export_collection_contact_sheet(
+    app_handle: AppHandle,
+    collection_id: String,
+    columns: u32,
+    output_path: String,
+) -> Result<ContactSheetReport, AppError> {
+    log::info!("Exporting contact sheet of collection {collection_id} to {output_path}");
+
+    if columns == 0 || columns > MAX_COLUMNS {
+        return Err(AppError::InvalidInput(format!(
+            "Columns must be between 1 and {MAX_COLUMNS}, got {columns}"
+        )));
+    }
+
+    let members = get_collection_pdfs(app_handle.clone(), collection_id, None)?;
+    if members.is_empty() {
+        return Err(AppError::InvalidInput(
+            "The collection has no documents".to_string(),
+        ));
+    }
+
+    let columns = columns.min(members.len() as u32);
+    let rows = (members.len() as u32).div_ceil(columns);
+    let cell_width = COVER_WIDTH + PADDING;
+    let cell_height = COVER_HEIGHT + CAPTION_HEIGHT + PADDING;
+    let mut sheet = RgbImage::from_pixel(
+        columns * cell_width + PADDING,
+        rows * cell_height + PADDING,
+        BACKGROUND,
+    );
+
+    let pdfium = app_handle.state::<AppState>().pdfium()?;
+    let mut captions = pdfium.create_new_pdf()?;
+    let font = captions.fonts_mut().helvetica();
+
+    let mut placeholders = Vec::new();
+    for (i, member) in members.iter().enumerate() {
+        let x = PADDING + (i as u32 % columns) * cell_width;
+        let y = PADDING + (i as u32 / columns) * cell_height;
+
+        match load_cover(&member.cover_path) {
+            Some(cover) => {
+                // centered horizontally, sitting on the caption
+                let cover_x = x + (COVER_WIDTH - cover.width()) / 2;
+                let cover_y = y + COVER_HEIGHT - cover.height();
+                imageops::overlay(&mut sheet, &cover.to_rgb8(), cover_x as i64, cover_y as i64);
+            }
+            None => {
+                let cell = RgbImage::from_pixel(COVER_WIDTH, COVER_HEIGHT, PLACEHOLDER);
+                imageops::overlay(&mut sheet, &cell, x as i64, y as i64);
+                placeholders.push(member.id);
+            }
+        }
+
+        let caption = render_caption(&mut captions, font, &caption_text(&member.file_name))?;
+        imageops::overlay(
+            &mut sheet,
+            &caption.to_rgb8(),
+            x as i64,
+            (y + COVER_HEIGHT) as i64,
+        );
+    }
+
+    sheet
+        .save_with_format(&output_path, image::ImageFormat::Png)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(ContactSheetReport {
+        output_path,
+        columns,
+        rows,
+        placeholders,
+    })
+}
@@ -0,0 +1,244 @@
+use std::fs;
+use std::path::Path;
+
+use chrono::Local;
+use tauri::{AppHandle, Manager};
+
+use crate::encryption::{carry_encryption, shared_encryption};
+use crate::error::AppError;
+use crate::pdf::{
+    load_bookmarks_from_file, load_strokes_from_file, load_thumbnails_from_file, read_pdf_entries,
+    rebuild_derived_data, save_bookmarks_to_file, write_pdf_entries, PdfBookmarks, PdfEntry,
+    PdfStrokes,
+};
+use crate::persistence::{write_json, FileKind};
+use crate::profiles::stored_profile_files;
+use crate::state::AppState;
+use crate::storage::ensure_space_for_import;
+use crate::stroke_queue::flush_pending_strokes;
+use crate::timestamp::file_timestamp;
+
+// Annotations of a profile that isn't active, read decrypted like the live ones
+enum StoredAnnotations {
+    Strokes(PdfStrokes),
+    Bookmarks(PdfBookmarks),
+}
+
+// Page-derived caches that don't refer to the id or to paths, copied as they are
+const PAGE_CACHES: [&str; 4] = [
+    "dims.json",
+    "thumb_layouts.json",
+    "text.json",
+    "text_stats.json",
+];
+
+// Copies the cover, thumbnails and page caches of `source` into the folder of `new_id`.
+// Returns the new cover path, or None when the source's previews are incomplete (e.g. still
+// being extracted) and have to be rendered instead.
+fn copy_previews(
+    app_handle: &AppHandle,
+    source: &PdfEntry,
+    source_folder: &Path,
+    new_id: u64,
+    folder: &Path,
+) -> Result<Option<String>, AppError> {
+    let thumbs_path = source_folder.join("thumbs.json");
+    let cover = Path::new(&source.cover_path);
+    if !thumbs_path.exists() || !source_folder.join("dims.json").exists() || !cover.is_file() {
+        return Ok(None);
+    }
+
+    let mut thumbnails = load_thumbnails_from_file(&thumbs_path)?;
+    let thumbs_dir = folder.join("thumbnails");
+    fs::create_dir_all(&thumbs_dir)?;
+    for path in thumbnails.inner.values_mut() {
+        let source_thumb = Path::new(path.as_str());
+        let Some(file_name) = source_thumb.file_name() else {
+            return Ok(None);
+        };
+        let target = thumbs_dir.join(file_name);
+        fs::copy(source_thumb, &target)?;
+        *path = target.to_string_lossy().into_owned();
+    }
+
+    for cache in PAGE_CACHES {
+        let path = source_folder.join(cache);
+        if path.exists() {
+            fs::copy(&path, folder.join(cache))?;
+        }
+    }
+
+    // covers carry their pdf's id, storage usage counts `_cover_` files as previews
+    let extension = cover
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("jpg");
+    let timestamp = file_timestamp();
+    let cover_path = folder.join(format!("{new_id}_cover_{timestamp}.{extension}"));
+    fs::copy(cover, &cover_path)?;
+
+    // written last, every thumbnail it lists is in place by now
+    write_json(
+        app_handle,
+        &folder.join("thumbs.json"),
+        &thumbnails,
+        FileKind::Thumbnails,
+    )?;
+
+    Ok(Some(cover_path.to_string_lossy().into_owned()))
+}
+
+// Registers a copy of a pdf under `new_name`, e.g. to try a different set of annotations on
+// the same book. The previews are copied rather than rendered since the content is identical.
+// Annotations start empty unless `copy_annotations` is set, which copies every profile's;
+// the copy takes on the source's encryption, a locked pdf has to be unlocked first. Returns
+// the new id.
+#[tauri::command]
+pub fn duplicate_pdf(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    new_name: String,
+    copy_annotations: Option<bool>,
+) -> Result<u64, AppError> {
+    log::info!("Duplicating pdf {pdf_id} as {new_name}");
+
+    let new_name = new_name.trim().to_string();
+    if new_name.is_empty() {
+        return Err(AppError::InvalidInput("Name cannot be empty".to_string()));
+    }
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    let state_path = app_data_dir.join("pdfs.json");
+    let source_folder = app_data_dir.join(format!("pdf_{pdf_id}"));
+
+    // the copy is taken from strokes.json
+    flush_pending_strokes(&app_handle, pdf_id)?;
+
+    let state = app_handle.state::<AppState>();
+    let lock = state.pdf_lock(pdf_id);
+    let _pdf_guard = lock.lock().unwrap();
+    let registry_guard = state.registry_lock.lock().unwrap();
+
+    let mut pdfs = read_pdf_entries(&state_path)?;
+    let source = pdfs
+        .iter()
+        .find(|pdf| pdf.id == pdf_id)
+        .cloned()
+        .ok_or_else(|| AppError::NotFound(format!("PDF with id {pdf_id} not found")))?;
+
+    // read before anything is written, a locked pdf fails here without leaving a folder behind
+    let annotations = if copy_annotations.unwrap_or(false) {
+        Some((
+            load_strokes_from_file(&app_handle, &source_folder.join("strokes.json"))?,
+            load_bookmarks_from_file(&app_handle, &source_folder.join("bookmarks.json"))?,
+        ))
+    } else {
+        None
+    };
+    let mut stored_profiles = Vec::new();
+    if annotations.is_some() {
+        for (path, is_strokes) in stored_profile_files(&source_folder)? {
+            let stored = if is_strokes {
+                StoredAnnotations::Strokes(load_strokes_from_file(&app_handle, &path)?)
+            } else {
+                StoredAnnotations::Bookmarks(load_bookmarks_from_file(&app_handle, &path)?)
+            };
+            if let Some(name) = path.file_name() {
+                stored_profiles.push((name.to_owned(), stored));
+            }
+        }
+    }
+    let profiles_path = source_folder.join("profiles.json");
+    // copied notes of an encrypted pdf stay encrypted in the copy
+    let encryption = if annotations.is_some() {
+        shared_encryption(&app_handle, &[pdf_id])?
+    } else {
+        None
+    };
+
+    let new_id = match pdfs.last() {
+        Some(pdf_entry) => pdf_entry.id + 1,
+        None => 1,
+    };
+    let folder_path = app_data_dir.join(format!("pdf_{new_id}"));
+    let clone_path = folder_path.join(format!("{new_id}.pdf"));
+
+    let clone_size = fs::metadata(&source.clone_path)?.len();
+    ensure_space_for_import(&app_data_dir, clone_size)?;
+
+    fs::create_dir_all(&folder_path)?;
+
+    let copied = (|| -> Result<Option<String>, AppError> {
+        fs::copy(&source.clone_path, &clone_path)?;
+
+        if let Some(source_id) = encryption {
+            carry_encryption(&app_handle, source_id, new_id)?;
+        }
+        if let Some((strokes, bookmarks)) = &annotations {
+            write_json(
+                &app_handle,
+                &folder_path.join("strokes.json"),
+                strokes,
+                FileKind::Strokes,
+            )?;
+            write_json(
+                &app_handle,
+                &folder_path.join("bookmarks.json"),
+                bookmarks,
+                FileKind::Bookmarks,
+            )?;
+
+            if profiles_path.exists() {
+                fs::copy(&profiles_path, folder_path.join("profiles.json"))?;
+            }
+            for (name, stored) in &stored_profiles {
+                let path = folder_path.join(name);
+                match stored {
+                    StoredAnnotations::Strokes(strokes) => {
+                        write_json(&app_handle, &path, strokes, FileKind::Strokes)?
+                    }
+                    StoredAnnotations::Bookmarks(bookmarks) => {
+                        save_bookmarks_to_file(&app_handle, &path, bookmarks)?
+                    }
+                }
+            }
+        }
+
+        copy_previews(&app_handle, &source, &source_folder, new_id, &folder_path)
+    })();
+    let cover_path = match copied {
+        Ok(cover_path) => cover_path,
+        Err(e) => {
+            // nothing refers to the folder yet, don't leave a partial copy behind
+            let _ = fs::remove_dir_all(&folder_path);
+            return Err(e);
+        }
+    };
+
+    let mut entry = PdfEntry::new(
+        new_id,
+        source.original_path.clone(),
+        clone_path.to_string_lossy().into_owned(),
+        cover_path.clone().unwrap_or_default(),
+        new_name,
+        source.content_hash.clone(),
+        Some(Local::now().to_rfc3339()),
+    );
+    entry.clone_hash = source.clone_hash.clone();
+    entry.page_count = source.page_count;
+    entry.custom_cover = source.custom_cover && cover_path.is_some();
+    entry.cover_page = source.cover_page;
+    entry.original_mtime = source.original_mtime;
+    entry.tags = source.tags.clone();
+
+    pdfs.push(entry.clone());
+    write_pdf_entries(&app_handle, &state_path, &pdfs)?;
+    drop(registry_guard);
+
+    if cover_path.is_none() {
+        log::info!("Previews of pdf {pdf_id} are incomplete, rendering them for pdf {new_id}");
+        rebuild_derived_data(&app_handle, &entry, &folder_path)?;
+    }
+
+    Ok(new_id)
+}
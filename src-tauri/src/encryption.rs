@@ -0,0 +1,478 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tauri::{AppHandle, Manager};
+
+use crate::error::AppError;
+use crate::pdf::find_pdf_entry;
+use crate::persistence::{backup_path, write_json, FileKind};
+use crate::state::AppState;
+use crate::stroke_queue::flush_pending_strokes;
+
+// Prefix of encrypted data files, followed by the nonce and the AES-256-GCM ciphertext
+const MAGIC: &[u8] = b"AKDAENC1";
+const NONCE_LEN: usize = 12;
+const PBKDF2_ROUNDS: u32 = 600_000;
+// encrypted into encryption.json to tell a wrong passphrase from a right one
+const CHECK_PLAINTEXT: &[u8] = b"akda";
+
+// Data files holding the user's own notes, encrypted when their pdf has encryption on
+const SENSITIVE_FILES: [&str; 3] = ["strokes.json", "bookmarks.json", "note.json"];
+
+// Stored as encryption.json in the pdf folder, its presence is what turns encryption on
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptionInfo {
+    rounds: u32,
+    salt: String,
+    check: String,
+}
+
+pub(crate) fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+// Backups and snapshot copies of a sensitive file are sensitive too, as are the stored
+// strokes_{name}.json and bookmarks_{name}.json of annotation profiles
+pub(crate) fn is_sensitive(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("");
+    let name = name.strip_suffix(".bak").unwrap_or(name);
+    SENSITIVE_FILES.contains(&name)
+        || (name.ends_with(".json")
+            && (name.starts_with("strokes_") || name.starts_with("bookmarks_")))
+}
+
+// Id of the pdf whose folder `path` is in, at any depth
+fn pdf_id_of(app_data_dir: &Path, path: &Path) -> Option<u64> {
+    match path.strip_prefix(app_data_dir).ok()?.components().next()? {
+        Component::Normal(folder) => folder.to_str()?.strip_prefix("pdf_")?.parse().ok(),
+        _ => None,
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], rounds: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, rounds, &mut key);
+    key
+}
+
+pub(crate) fn encrypt(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new(key.into());
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| "Failed to encrypt data".to_string())?;
+
+    let mut data = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len());
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&nonce);
+    data.extend_from_slice(&ciphertext);
+    Ok(data)
+}
+
+pub(crate) fn decrypt(key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>, AppError> {
+    let payload = data
+        .strip_prefix(MAGIC)
+        .filter(|payload| payload.len() >= NONCE_LEN)
+        .ok_or_else(|| AppError::Parse("Not an encrypted data file".to_string()))?;
+    let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+
+    Aes256Gcm::new(key.into())
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| {
+            AppError::Parse("Failed to decrypt data, it is damaged or the key is wrong".to_string())
+        })
+}
+
+// A sensitive file re-encrypted in memory, swapped in through `temp`
+struct Rewrite {
+    path: PathBuf,
+    temp: PathBuf,
+    original: Vec<u8>,
+    output: Vec<u8>,
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    PathBuf::from(temp)
+}
+
+// Writes every rewritten file next to its original, then renames them over the originals.
+// Nothing is renamed unless every temp file was written, and no temp file is left behind.
+fn swap_rewritten(rewritten: &[Rewrite]) -> Result<(), AppError> {
+    let written = rewritten
+        .iter()
+        .try_for_each(|rewrite| fs::write(&rewrite.temp, &rewrite.output));
+    let swapped = written.and_then(|()| {
+        rewritten
+            .iter()
+            .try_for_each(|rewrite| fs::rename(&rewrite.temp, &rewrite.path))
+    });
+
+    if swapped.is_err() {
+        for rewrite in rewritten {
+            let _ = fs::remove_file(&rewrite.temp);
+        }
+    }
+    Ok(swapped?)
+}
+
+// Puts back what the files held before a failed change, renamed or not
+fn restore_originals(rewritten: &[Rewrite]) {
+    for rewrite in rewritten {
+        if let Err(e) = fs::write(&rewrite.path, &rewrite.original) {
+            log::error!("Failed to restore {}: {e}", rewrite.path.display());
+        }
+    }
+}
+
+fn info_path(folder: &Path) -> PathBuf {
+    folder.join("encryption.json")
+}
+
+fn read_info(folder: &Path) -> Result<Option<EncryptionInfo>, AppError> {
+    let path = info_path(folder);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let data = fs::read_to_string(path)?;
+    Ok(Some(serde_json::from_str::<EncryptionInfo>(&data)?))
+}
+
+// Key for the data files of the pdf `path` belongs to. None when that pdf doesn't have
+// encryption on, Locked when it does but hasn't been unlocked this session.
+pub(crate) fn file_key(app_handle: &AppHandle, path: &Path) -> Result<Option<[u8; 32]>, AppError> {
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    let Some(pdf_id) = pdf_id_of(&app_data_dir, path) else {
+        return Ok(None);
+    };
+    if !info_path(&app_data_dir.join(format!("pdf_{pdf_id}"))).exists() {
+        return Ok(None);
+    }
+
+    match app_handle
+        .state::<AppState>()
+        .unlocked_keys
+        .lock()
+        .unwrap()
+        .get(&pdf_id)
+    {
+        Some(key) => Ok(Some(*key)),
+        None => Err(AppError::Locked(format!(
+            "Annotations of pdf {pdf_id} are locked"
+        ))),
+    }
+}
+
+// Every sensitive file of a pdf: the live ones, their backups and their snapshot copies
+fn sensitive_files(folder: &Path) -> Result<Vec<PathBuf>, AppError> {
+    let mut dirs = vec![folder.to_path_buf()];
+    let snapshots = folder.join("snapshots");
+    if snapshots.exists() {
+        for entry in fs::read_dir(snapshots)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                dirs.push(entry.path());
+            }
+        }
+    }
+
+    let mut files = Vec::new();
+    for dir in dirs {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_file() && is_sensitive(&path) {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+// Turns encryption of a pdf's notes on, changes its passphrase, or turns it off when
+// `passphrase` is None. An encrypted pdf has to be unlocked first. Files are rewritten
+// next to the originals and only swapped in once all of them are written.
+#[tauri::command]
+pub async fn set_pdf_encryption(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    passphrase: Option<String>,
+) -> Result<(), AppError> {
+    log::info!("Setting annotation encryption of pdf {pdf_id}");
+
+    if passphrase.as_deref().is_some_and(str::is_empty) {
+        return Err(AppError::InvalidInput(
+            "Passphrase cannot be empty".to_string(),
+        ));
+    }
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    find_pdf_entry(&app_data_dir, pdf_id)?;
+    let folder = app_data_dir.join(format!("pdf_{pdf_id}"));
+
+    // queued strokes are written with the current key, and then rewritten with the new one
+    flush_pending_strokes(&app_handle, pdf_id)?;
+
+    let state = app_handle.state::<AppState>();
+    let lock = state.pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    let current_key = file_key(&app_handle, &folder.join("strokes.json"))?;
+
+    let new_info = passphrase.map(|passphrase| {
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let key = derive_key(&passphrase, &salt, PBKDF2_ROUNDS);
+        (key, salt)
+    });
+    let new_key = new_info.as_ref().map(|(key, _)| *key);
+
+    // everything is decrypted and re-encrypted in memory first, a file that can't be
+    // read stops the change before anything on disk is touched
+    let mut rewritten = Vec::new();
+    for path in sensitive_files(&folder)? {
+        let data = fs::read(&path)?;
+        let plaintext = if is_encrypted(&data) {
+            let key = current_key.ok_or_else(|| {
+                AppError::Locked(format!("Annotations of pdf {pdf_id} are locked"))
+            })?;
+            decrypt(&key, &data)?
+        } else {
+            data.clone()
+        };
+        let output = match &new_key {
+            Some(key) => encrypt(key, &plaintext)?,
+            None => plaintext,
+        };
+        rewritten.push(Rewrite {
+            temp: temp_path(&path),
+            path,
+            original: data,
+            output,
+        });
+    }
+
+    // the old encryption.json stays until every file is swapped, so a failure part way
+    // through can put the old files back and leave them readable with the old key
+    let swapped = swap_rewritten(&rewritten).and_then(|()| match &new_info {
+        Some((key, salt)) => {
+            let info = EncryptionInfo {
+                rounds: PBKDF2_ROUNDS,
+                salt: hex::encode(salt),
+                check: hex::encode(encrypt(key, CHECK_PLAINTEXT)?),
+            };
+            write_json(
+                &app_handle,
+                &info_path(&folder),
+                &info,
+                FileKind::Encryption,
+            )?;
+            Ok(())
+        }
+        None => Ok(()),
+    });
+    if let Err(e) = swapped {
+        restore_originals(&rewritten);
+        return Err(e);
+    }
+
+    match &new_info {
+        Some((key, _)) => {
+            state.unlocked_keys.lock().unwrap().insert(pdf_id, *key);
+        }
+        None => {
+            state.unlocked_keys.lock().unwrap().remove(&pdf_id);
+        }
+    }
+
+    // removed last, plaintext files under a leftover encryption.json still read fine
+    if new_info.is_none() {
+        let _ = fs::remove_file(backup_path(&info_path(&folder)));
+        if info_path(&folder).exists() {
+            fs::remove_file(info_path(&folder))?;
+        }
+    }
+
+    Ok(())
+}
+
+// The source whose encryption a document generated from `source_ids` (a merge, a split, a
+// duplicate) takes on, so the notes carried into it stay encrypted. None when no source has
+// encryption on. Encrypted sources have to be unlocked and share one key, a pdf only has one
+// passphrase.
+pub(crate) fn shared_encryption(
+    app_handle: &AppHandle,
+    source_ids: &[u64],
+) -> Result<Option<u64>, AppError> {
+    let app_data_dir = app_handle.path().app_data_dir()?;
+
+    let mut shared: Option<(u64, [u8; 32])> = None;
+    for &pdf_id in source_ids {
+        let folder = app_data_dir.join(format!("pdf_{pdf_id}"));
+        let Some(key) = file_key(app_handle, &folder.join("strokes.json"))? else {
+            continue;
+        };
+        match shared {
+            None => shared = Some((pdf_id, key)),
+            Some((_, shared_key)) if shared_key == key => {}
+            Some((first_id, _)) => {
+                return Err(AppError::InvalidInput(format!(
+                    "PDFs {first_id} and {pdf_id} are encrypted separately, turn encryption off on one of them first"
+                )))
+            }
+        }
+    }
+
+    Ok(shared.map(|(pdf_id, _)| pdf_id))
+}
+
+// Turns encryption on for `target_id` with the key of `source_id`, before any notes are
+// written to it. The source has to be unlocked.
+pub(crate) fn carry_encryption(
+    app_handle: &AppHandle,
+    source_id: u64,
+    target_id: u64,
+) -> Result<(), AppError> {
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    let source = app_data_dir.join(format!("pdf_{source_id}"));
+    let target = app_data_dir.join(format!("pdf_{target_id}"));
+
+    let key = file_key(app_handle, &source.join("strokes.json"))?.ok_or_else(|| {
+        AppError::InvalidInput(format!("Annotations of pdf {source_id} aren't encrypted"))
+    })?;
+
+    fs::create_dir_all(&target)?;
+    fs::copy(info_path(&source), info_path(&target))?;
+    app_handle
+        .state::<AppState>()
+        .unlocked_keys
+        .lock()
+        .unwrap()
+        .insert(target_id, key);
+    Ok(())
+}
+
+// Key of `passphrase` when it's the one encryption was turned on with
+fn check_passphrase(info: &EncryptionInfo, passphrase: &str) -> Result<[u8; 32], AppError> {
+    let salt = hex::decode(&info.salt).map_err(|e| AppError::Parse(e.to_string()))?;
+    let check = hex::decode(&info.check).map_err(|e| AppError::Parse(e.to_string()))?;
+
+    let key = derive_key(passphrase, &salt, info.rounds);
+    match decrypt(&key, &check) {
+        Ok(plaintext) if plaintext == CHECK_PLAINTEXT => Ok(key),
+        _ => Err(AppError::InvalidInput("Wrong passphrase".to_string())),
+    }
+}
+
+// Checks the passphrase and keeps the key for the rest of the session
+#[tauri::command]
+pub async fn unlock_pdf(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    passphrase: String,
+) -> Result<(), AppError> {
+    log::info!("Unlocking annotations of pdf {pdf_id}");
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    let folder = app_data_dir.join(format!("pdf_{pdf_id}"));
+
+    let info = read_info(&folder)?.ok_or_else(|| {
+        AppError::InvalidInput(format!("Annotations of pdf {pdf_id} aren't encrypted"))
+    })?;
+    let key = check_passphrase(&info, &passphrase)?;
+
+    app_handle
+        .state::<AppState>()
+        .unlocked_keys
+        .lock()
+        .unwrap()
+        .insert(pdf_id, key);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // few rounds keep the tests fast, the format is the same
+    const TEST_ROUNDS: u32 = 1_000;
+
+    fn info_for(passphrase: &str) -> EncryptionInfo {
+        let salt = [7u8; 16];
+        let key = derive_key(passphrase, &salt, TEST_ROUNDS);
+        EncryptionInfo {
+            rounds: TEST_ROUNDS,
+            salt: hex::encode(salt),
+            check: hex::encode(encrypt(&key, CHECK_PLAINTEXT).unwrap()),
+        }
+    }
+
+    #[test]
+    fn round_trips_with_the_right_passphrase() {
+        let info = info_for("correct horse");
+        let key = check_passphrase(&info, "correct horse").unwrap();
+
+        let plaintext = br#"{"inner":{"1":[]}}"#;
+        let data = encrypt(&key, plaintext).unwrap();
+        assert!(is_encrypted(&data));
+        assert_ne!(&data[MAGIC.len()..], plaintext);
+        assert_eq!(decrypt(&key, &data).unwrap(), plaintext);
+    }
+
+    #[test]
+    fn rejects_a_wrong_passphrase() {
+        let info = info_for("correct horse");
+        assert!(matches!(
+            check_passphrase(&info, "battery staple"),
+            Err(AppError::InvalidInput(_))
+        ));
+
+        let key = check_passphrase(&info, "correct horse").unwrap();
+        let data = encrypt(&key, b"notes").unwrap();
+        let wrong_key = derive_key("battery staple", &[7u8; 16], TEST_ROUNDS);
+        assert!(matches!(
+            decrypt(&wrong_key, &data),
+            Err(AppError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn leaves_no_temp_files_when_a_swap_fails() {
+        let dir = std::env::temp_dir().join(format!("akda_encryption_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("strokes.json");
+        fs::write(&path, "old").unwrap();
+        let rewritten = vec![
+            Rewrite {
+                temp: temp_path(&path),
+                path: path.clone(),
+                original: b"old".to_vec(),
+                output: b"new".to_vec(),
+            },
+            // the temp file can't be written into a missing dir
+            Rewrite {
+                temp: dir.join("missing/bookmarks.json.tmp"),
+                path: dir.join("missing/bookmarks.json"),
+                original: Vec::new(),
+                output: b"new".to_vec(),
+            },
+        ];
+
+        assert!(swap_rewritten(&rewritten).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert!(!temp_path(&path).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
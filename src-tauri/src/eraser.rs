@@ -0,0 +1,160 @@
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::pdf::{
+    load_editor_settings, load_strokes_from_file, DrawingToolType, EraserMode, Stroke, StrokePath,
+};
+use crate::persistence::{write_json, FileKind};
+use crate::state::AppState;
+use crate::stroke_queue::flush_pending_strokes;
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct EraseResult {
+    // ids of strokes that were removed, including the ones split into pieces
+    removed: Vec<String>,
+    // ids of the pieces left over from split strokes
+    created: Vec<String>,
+    // also set for legacy strokes without an id, which can't be listed above
+    #[serde(skip)]
+    changed: bool,
+}
+
+pub(crate) fn distance_to_segment(point: &StrokePath, a: &StrokePath, b: &StrokePath) -> f64 {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let length_sq = dx * dx + dy * dy;
+
+    let t = if length_sq == 0.0 {
+        0.0
+    } else {
+        (((point.x - a.x) * dx + (point.y - a.y) * dy) / length_sq).clamp(0.0, 1.0)
+    };
+
+    let (cx, cy) = (a.x + t * dx, a.y + t * dy);
+    ((point.x - cx).powi(2) + (point.y - cy).powi(2)).sqrt()
+}
+
+// Whether `point` lies under the eraser swept along `eraser` with the given reach
+fn is_erased(point: &StrokePath, eraser: &[StrokePath], reach: f64) -> bool {
+    match eraser {
+        [] => false,
+        [single] => distance_to_segment(point, single, single) <= reach,
+        _ => eraser
+            .windows(2)
+            .any(|segment| distance_to_segment(point, &segment[0], &segment[1]) <= reach),
+    }
+}
+
+// Runs of consecutive points the eraser missed, each becomes its own stroke
+fn surviving_runs(stroke: &Stroke, eraser: &[StrokePath], reach: f64) -> Vec<Vec<StrokePath>> {
+    let mut runs = Vec::new();
+    let mut current = Vec::new();
+
+    for point in &stroke.path {
+        if is_erased(point, eraser, reach) {
+            if !current.is_empty() {
+                runs.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(point.clone());
+        }
+    }
+    if !current.is_empty() {
+        runs.push(current);
+    }
+
+    runs
+}
+
+// Erases `eraser` out of one page's strokes in place
+pub(crate) fn erase_strokes(
+    strokes: &mut Vec<Stroke>,
+    eraser: &[StrokePath],
+    radius: f64,
+    mode: EraserMode,
+) -> EraseResult {
+    let mut result = EraseResult::default();
+    let mut kept = Vec::with_capacity(strokes.len());
+
+    for stroke in strokes.drain(..) {
+        // legacy eraser strokes only mask the canvas, they have nothing to erase
+        if matches!(stroke.tool, DrawingToolType::Eraser) {
+            kept.push(stroke);
+            continue;
+        }
+
+        // a stroke is touched when the eraser reaches the edge of its line, not just its center
+        let reach = radius + stroke.thickness as f64 / 2.0;
+        let touched = stroke.path.iter().any(|p| is_erased(p, eraser, reach));
+        if !touched {
+            kept.push(stroke);
+            continue;
+        }
+
+        result.changed = true;
+        if let Some(id) = &stroke.id {
+            result.removed.push(id.clone());
+        }
+
+        if mode == EraserMode::Segment {
+            for run in surviving_runs(&stroke, eraser, reach) {
+                let id = uuid::Uuid::new_v4().to_string();
+                result.created.push(id.clone());
+                kept.push(Stroke {
+                    id: Some(id),
+                    path: run,
+                    ..stroke.clone()
+                });
+            }
+        }
+    }
+
+    *strokes = kept;
+    result
+}
+
+#[tauri::command]
+pub fn apply_eraser(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    page: u32,
+    path: Vec<StrokePath>,
+    radius: Option<f64>,
+    mode: Option<EraserMode>,
+) -> Result<EraseResult, String> {
+    log::info!("Applying eraser on page {page} of pdf {pdf_id}");
+
+    if path.is_empty() {
+        return Err("Eraser path cannot be empty".to_string());
+    }
+
+    // falls back to the eraser the user picked in the editor
+    let settings = load_editor_settings(app_handle.clone(), pdf_id)?;
+    let mode = mode.unwrap_or(settings.eraser_mode);
+    let radius = radius.unwrap_or(settings.eraser_thickness as f64 / 2.0);
+    if !radius.is_finite() || radius < 0.0 {
+        return Err(format!("Invalid eraser radius: {radius}"));
+    }
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let strokes_path = app_data_dir.join(format!("pdf_{pdf_id}/strokes.json"));
+
+    flush_pending_strokes(&app_handle, pdf_id)?;
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    let mut strokes = load_strokes_from_file(&app_handle, &strokes_path)?;
+    let Some(page_strokes) = strokes.inner.get_mut(&page) else {
+        return Ok(EraseResult::default());
+    };
+
+    let result = erase_strokes(page_strokes, &path, radius, mode);
+    if result.changed {
+        write_json(&app_handle, &strokes_path, &strokes, FileKind::Strokes)?;
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,144 @@
+use std::fmt;
+use std::io;
+
+use pdfium_render::prelude::{PdfiumError, PdfiumInternalError};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+// Error returned by commands, serialized as `{ kind, message }` so the frontend can branch on
+// `kind` instead of matching message text. Only some commands return it so far: the older
+// ones, most of pdf.rs included, still reject with a plain message string. Both convert into
+// each other so helpers can be shared between the two.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AppError {
+    NotFound(String),
+    Io(String),
+    Parse(String),
+    Pdfium(String),
+    // the document needs a password the app doesn't have
+    Encrypted(String),
+    // the document's annotations are encrypted and it hasn't been unlocked this session
+    Locked(String),
+    InvalidInput(String),
+    // something else (an export, OCR, ...) holds what the command needs
+    Busy(String),
+    // errors from helpers that still report a plain String
+    Other(String),
+}
+
+impl AppError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "notFound",
+            AppError::Io(_) => "io",
+            AppError::Parse(_) => "parse",
+            AppError::Pdfium(_) => "pdfium",
+            AppError::Encrypted(_) => "encrypted",
+            AppError::Locked(_) => "locked",
+            AppError::InvalidInput(_) => "invalidInput",
+            AppError::Busy(_) => "busy",
+            AppError::Other(_) => "other",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::NotFound(message)
+            | AppError::Io(message)
+            | AppError::Parse(message)
+            | AppError::Pdfium(message)
+            | AppError::Encrypted(message)
+            | AppError::Locked(message)
+            | AppError::InvalidInput(message)
+            | AppError::Busy(message)
+            | AppError::Other(message) => message,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("AppError", 2)?;
+        error.serialize_field("kind", self.kind())?;
+        error.serialize_field("message", self.message())?;
+        error.end()
+    }
+}
+
+impl From<io::Error> for AppError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => AppError::NotFound(e.to_string()),
+            _ => AppError::Io(e.to_string()),
+        }
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(e: serde_json::Error) -> Self {
+        AppError::Parse(e.to_string())
+    }
+}
+
+impl From<PdfiumError> for AppError {
+    fn from(e: PdfiumError) -> Self {
+        match e {
+            PdfiumError::PdfiumLibraryInternalError(PdfiumInternalError::PasswordError) => {
+                AppError::Encrypted("The document is password protected".to_string())
+            }
+            e => AppError::Pdfium(e.to_string()),
+        }
+    }
+}
+
+impl From<tauri::Error> for AppError {
+    // tauri::Error covers path resolution, IPC and window failures, io is only one of them
+    fn from(e: tauri::Error) -> Self {
+        AppError::Other(e.to_string())
+    }
+}
+
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        AppError::Other(message)
+    }
+}
+
+// Lets String commands keep using `?` on helpers that already return AppError
+impl From<AppError> for String {
+    fn from(e: AppError) -> Self {
+        e.message().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_kind_and_message() {
+        let error = AppError::NotFound("PDF with id 3 not found".to_string());
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({ "kind": "notFound", "message": "PDF with id 3 not found" })
+        );
+    }
+
+    #[test]
+    fn maps_foreign_errors_to_their_kind() {
+        let missing = io::Error::new(io::ErrorKind::NotFound, "gone");
+        assert_eq!(AppError::from(missing).kind(), "notFound");
+        assert_eq!(AppError::from(tauri::Error::UnknownPath).kind(), "other");
+        assert_eq!(
+            AppError::from(serde_json::from_str::<u32>("x").unwrap_err()).kind(),
+            "parse"
+        );
+    }
+}
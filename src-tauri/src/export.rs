@@ -0,0 +1,565 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use chrono::Local;
+use pdfium_render::prelude::*;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::collections::{collections_file_path, read_collections};
+use crate::pdf::{
+    find_pdf_entry, load_bookmarks_from_file, load_strokes_from_file, pdf_page_count,
+    read_pdf_entries, DrawingToolType, Stroke,
+};
+use crate::state::AppState;
+use crate::stroke_queue::flush_pending_strokes;
+use crate::svg::stroke_color;
+use crate::timestamp::unique_id;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportProgress {
+    export_id: String,
+    page: u32,
+    total: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportComplete {
+    export_id: String,
+    output_path: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportFailed {
+    export_id: String,
+    error: String,
+}
+
+// Draws the strokes of one page as vector paths. Stroke coordinates are in page points
+// with a top-left origin, so y is flipped against the page height.
+pub(crate) fn flatten_page_strokes<'a>(
+    document: &PdfDocument<'a>,
+    page: &mut PdfPage<'a>,
+    strokes: &[Stroke],
+) -> Result<(), String> {
+    let page_height = page.height().value;
+
+    for stroke in strokes {
+        // erasers only mask the overlay canvas, there is nothing to draw for them
+        if matches!(stroke.tool, DrawingToolType::Eraser) {
+            continue;
+        }
+
+        let Some(first) = stroke.path.first() else {
+            continue;
+        };
+
+        let rgba = stroke_color(stroke).with_opacity(stroke.opacity);
+        let color = PdfColor::new(rgba.r, rgba.g, rgba.b, rgba.a);
+        let mut path = PdfPagePathObject::new(
+            document,
+            PdfPoints::new(first.x as f32),
+            PdfPoints::new(page_height - first.y as f32),
+            Some(color),
+            Some(PdfPoints::new(stroke.thickness as f32)),
+            None,
+        )
+        .map_err(|e| e.to_string())?;
+
+        if stroke.path.len() == 1 {
+            // single tap, draw a dot
+            path.line_to(
+                PdfPoints::new(first.x as f32),
+                PdfPoints::new(page_height - first.y as f32),
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        for point in stroke.path.iter().skip(1) {
+            path.line_to(
+                PdfPoints::new(point.x as f32),
+                PdfPoints::new(page_height - point.y as f32),
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        if matches!(stroke.tool, DrawingToolType::Highlighter) {
+            path.set_blend_mode(PdfPageObjectBlendMode::Multiply)
+                .map_err(|e| e.to_string())?;
+        }
+
+        page.objects_mut()
+            .add_path_object(path)
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+// The document to export and, for each of its pages, the page of the clone it came from.
+// Without `pages` that's the clone itself; otherwise a new document with just those pages.
+fn open_export_document<'a>(
+    pdfium: &'a Pdfium,
+    clone_path: &str,
+    pages: Option<&[u32]>,
+) -> Result<(PdfDocument<'a>, Vec<u32>), String> {
+    let source = pdfium
+        .load_pdf_from_file(clone_path, None)
+        .map_err(|e| e.to_string())?;
+
+    let Some(pages) = pages else {
+        let page_numbers = (1..=source.pages().len() as u32).collect();
+        return Ok((source, page_numbers));
+    };
+
+    let mut document = pdfium.create_new_pdf().map_err(|e| e.to_string())?;
+    for (index, page_no) in pages.iter().enumerate() {
+        document
+            .pages_mut()
+            .copy_page_from_document(
+                &source,
+                (page_no - 1) as PdfPageIndex,
+                index as PdfPageIndex,
+            )
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok((document, pages.to_vec()))
+}
+
+fn run_export(
+    app_handle: &AppHandle,
+    export_id: &str,
+    document: &PdfDocument,
+    page_numbers: &[u32],
+    strokes_path: &Path,
+    partial_path: &Path,
+    cancelled: &AtomicBool,
+) -> Result<bool, String> {
+    let strokes = load_strokes_from_file(app_handle, strokes_path)?;
+
+    let total = page_numbers.len() as u32;
+
+    for (index, page_no) in page_numbers.iter().enumerate() {
+        if cancelled.load(Ordering::Relaxed) {
+            return Ok(false);
+        }
+
+        if let Some(page_strokes) = strokes.inner.get(page_no) {
+            let mut page = document
+                .pages()
+                .get(index as PdfPageIndex)
+                .map_err(|e| e.to_string())?;
+            flatten_page_strokes(document, &mut page, page_strokes)?;
+        }
+
+        app_handle
+            .emit(
+                "export-progress",
+                ExportProgress {
+                    export_id: export_id.to_string(),
+                    page: index as u32 + 1,
+                    total,
+                },
+            )
+            .unwrap();
+    }
+
+    if cancelled.load(Ordering::Relaxed) {
+        return Ok(false);
+    }
+
+    document
+        .save_to_file(partial_path)
+        .map_err(|e| e.to_string())?;
+
+    Ok(true)
+}
+
+// Starts a background export of the clone (or of `pages` of it) with strokes flattened,
+// returning the id progress and completion events are tagged with
+fn start_export(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    pages: Option<Vec<u32>>,
+    output_path: String,
+) -> Result<String, String> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let pdf_entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+    let strokes_path = app_data_dir.join(format!("pdf_{pdf_id}/strokes.json"));
+
+    // the export reads strokes.json, queued strokes belong in it
+    flush_pending_strokes(&app_handle, pdf_id)?;
+
+    // two exports started in the same millisecond still get their own id
+    let export_id = format!("export_{pdf_id}_{}", unique_id());
+
+    let state = app_handle.state::<AppState>();
+    let pdfium = state.pdfium()?;
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    state
+        .export_jobs
+        .lock()
+        .unwrap()
+        .insert(export_id.clone(), cancelled.clone());
+
+    let thread_export_id = export_id.clone();
+
+    // a folder gets the document's export name inside it
+    let mut output_path = PathBuf::from(output_path);
+    if output_path.is_dir() {
+        output_path.push(pdf_entry.export_file_name());
+    }
+
+    tauri::async_runtime::spawn_blocking(move || {
+        // pdfium only writes on save, so the partial file exists just between save and rename
+        let partial_path = output_path.with_extension("pdf.part");
+
+        let result = open_export_document(&pdfium, &pdf_entry.clone_path, pages.as_deref())
+            .and_then(|(document, page_numbers)| {
+                run_export(
+                    &app_handle,
+                    &thread_export_id,
+                    &document,
+                    &page_numbers,
+                    &strokes_path,
+                    &partial_path,
+                    &cancelled,
+                )
+            })
+            .and_then(|completed| {
+                if completed {
+                    fs::rename(&partial_path, &output_path).map_err(|e| e.to_string())?;
+                }
+                Ok(completed)
+            });
+
+        app_handle
+            .state::<AppState>()
+            .export_jobs
+            .lock()
+            .unwrap()
+            .remove(&thread_export_id);
+
+        match result {
+            Ok(true) => {
+                log::info!("Export {thread_export_id} finished");
+                app_handle
+                    .emit(
+                        "export-complete",
+                        ExportComplete {
+                            export_id: thread_export_id,
+                            output_path: output_path.to_string_lossy().to_string(),
+                        },
+                    )
+                    .unwrap();
+            }
+            outcome => {
+                let error = match outcome {
+                    Err(e) => e,
+                    _ => "Export cancelled".to_string(),
+                };
+                log::warn!("Export {thread_export_id} did not finish: {error}");
+
+                if partial_path.exists() {
+                    let _ = fs::remove_file(&partial_path);
+                }
+
+                app_handle
+                    .emit(
+                        "export-failed",
+                        ExportFailed {
+                            export_id: thread_export_id,
+                            error,
+                        },
+                    )
+                    .unwrap();
+            }
+        }
+    });
+
+    Ok(export_id)
+}
+
+#[tauri::command]
+pub fn export_annotated_pdf(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    output_path: String,
+) -> Result<String, String> {
+    log::info!("Exporting annotated pdf {pdf_id} to {output_path}");
+    start_export(app_handle, pdf_id, None, output_path)
+}
+
+// Exports only `pages`, in the given order, e.g. one annotated chapter as a handout
+#[tauri::command]
+pub fn export_annotated_pages(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    pages: Vec<u32>,
+    output_path: String,
+) -> Result<String, String> {
+    log::info!("Exporting pages {pages:?} of annotated pdf {pdf_id} to {output_path}");
+
+    if pages.is_empty() {
+        return Err("Select at least one page to export".to_string());
+    }
+
+    let page_count = pdf_page_count(&app_handle, pdf_id)?;
+    if let Some(page) = pages.iter().find(|&&p| p == 0 || p > page_count) {
+        return Err(format!("Page {page} is out of range (1-{page_count})"));
+    }
+
+    start_export(app_handle, pdf_id, Some(pages), output_path)
+}
+
+#[tauri::command]
+pub fn cancel_export(app_handle: AppHandle, export_id: String) -> Result<bool, String> {
+    log::info!("Cancelling export {export_id}");
+
+    let state = app_handle.state::<AppState>();
+    let jobs = state.export_jobs.lock().unwrap();
+
+    match jobs.get(&export_id) {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::Relaxed);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MissingMember {
+    pdf_id: String,
+    reason: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ManifestEntry {
+    pdf_id: u64,
+    file_name: String,
+    archive_path: String,
+    annotations: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct CollectionManifest {
+    collection_id: String,
+    name: String,
+    color: String,
+    description: Option<String>,
+    exported_at: String,
+    entries: Vec<ManifestEntry>,
+    missing: Vec<MissingMember>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CollectionExportReport {
+    output_path: String,
+    exported: usize,
+    missing: Vec<MissingMember>,
+}
+
+fn add_bytes_to_zip<W: Write + std::io::Seek>(
+    zip: &mut ZipWriter<W>,
+    bytes: &[u8],
+    archive_path: &str,
+) -> Result<(), String> {
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    zip.start_file(archive_path, options)
+        .map_err(|e| e.to_string())?;
+    zip.write_all(bytes).map_err(|e| e.to_string())
+}
+
+fn add_file_to_zip<W: Write + std::io::Seek>(
+    zip: &mut ZipWriter<W>,
+    source: &Path,
+    archive_path: &str,
+) -> Result<(), String> {
+    let bytes = fs::read(source).map_err(|e| e.to_string())?;
+    add_bytes_to_zip(zip, &bytes, archive_path)
+}
+
+// strokes.json and bookmarks.json of a pdf as readable JSON, decrypted when it has encryption
+// on. A locked pdf fails rather than putting ciphertext in the archive.
+fn annotation_sidecars(
+    app_handle: &AppHandle,
+    app_data_dir: &Path,
+    pdf_id: u64,
+) -> Result<Vec<(&'static str, String)>, String> {
+    let folder_path = app_data_dir.join(format!("pdf_{pdf_id}"));
+
+    // strokes still queued from an open reader belong in the archive too
+    flush_pending_strokes(app_handle, pdf_id)?;
+
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    let mut sidecars = Vec::new();
+    let strokes_path = folder_path.join("strokes.json");
+    if strokes_path.exists() {
+        let strokes = load_strokes_from_file(app_handle, &strokes_path)?;
+        let json = serde_json::to_string_pretty(&strokes).map_err(|e| e.to_string())?;
+        sidecars.push(("strokes.json", json));
+    }
+    let bookmarks_path = folder_path.join("bookmarks.json");
+    if bookmarks_path.exists() {
+        let bookmarks = load_bookmarks_from_file(app_handle, &bookmarks_path)?;
+        let json = serde_json::to_string_pretty(&bookmarks).map_err(|e| e.to_string())?;
+        sidecars.push(("bookmarks.json", json));
+    }
+    Ok(sidecars)
+}
+
+// Keeps file and archive entry names portable across platforms
+fn sanitize_file_name(file_name: &str) -> String {
+    file_name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+// A display name turned into a `.pdf` file name any platform accepts
+pub(crate) fn export_file_name(name: &str) -> String {
+    let name = sanitize_file_name(name.trim());
+    let stem = name
+        .strip_suffix(".pdf")
+        .or_else(|| name.strip_suffix(".PDF"))
+        .unwrap_or(&name);
+    // Windows drops trailing dots and spaces, which would change the name under us
+    let stem = stem.trim_end_matches(['.', ' ']);
+
+    if stem.is_empty() {
+        "document.pdf".to_string()
+    } else {
+        format!("{stem}.pdf")
+    }
+}
+
+#[tauri::command]
+pub fn export_collection(
+    app_handle: AppHandle,
+    collection_id: String,
+    output_zip: String,
+    include_annotations: Option<bool>,
+) -> Result<CollectionExportReport, String> {
+    log::info!("Exporting collection {collection_id} to {output_zip}");
+
+    let include_annotations = include_annotations.unwrap_or(false);
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let collections = read_collections(&collections_file_path(&app_handle)?)?;
+    let collection = collections
+        .collections
+        .into_iter()
+        .find(|c| c.id == collection_id)
+        .ok_or("Collection not found")?;
+
+    let pdfs = read_pdf_entries(&app_data_dir.join("pdfs.json"))?;
+
+    let mut member_ids: Vec<&String> = collection.pdf_ids.keys().collect();
+    member_ids.sort();
+
+    let file = fs::File::create(&output_zip).map_err(|e| e.to_string())?;
+    let mut zip = ZipWriter::new(file);
+
+    let mut entries = Vec::new();
+    let mut missing = Vec::new();
+
+    for member_id in member_ids {
+        let entry = member_id
+            .parse::<u64>()
+            .ok()
+            .and_then(|id| pdfs.iter().find(|pdf| pdf.id == id));
+
+        let Some(entry) = entry else {
+            missing.push(MissingMember {
+                pdf_id: member_id.clone(),
+                reason: "Not in library".to_string(),
+            });
+            continue;
+        };
+
+        let clone_path = Path::new(&entry.clone_path);
+        if !clone_path.exists() {
+            missing.push(MissingMember {
+                pdf_id: member_id.clone(),
+                reason: format!("PDF file missing: {}", entry.clone_path),
+            });
+            continue;
+        }
+
+        let archive_path = format!("pdfs/{}_{}", entry.id, entry.export_file_name());
+        add_file_to_zip(&mut zip, clone_path, &archive_path)?;
+
+        let mut annotations = Vec::new();
+        if include_annotations {
+            for (sidecar, json) in annotation_sidecars(&app_handle, &app_data_dir, entry.id)? {
+                let sidecar_archive_path = format!("annotations/{}/{sidecar}", entry.id);
+                add_bytes_to_zip(&mut zip, json.as_bytes(), &sidecar_archive_path)?;
+                annotations.push(sidecar_archive_path);
+            }
+        }
+
+        entries.push(ManifestEntry {
+            pdf_id: entry.id,
+            file_name: entry.file_name.clone(),
+            archive_path,
+            annotations,
+        });
+    }
+
+    if !missing.is_empty() {
+        log::warn!(
+            "{} member(s) of collection {collection_id} could not be exported",
+            missing.len()
+        );
+    }
+
+    let exported = entries.len();
+    let manifest = CollectionManifest {
+        collection_id: collection.id,
+        name: collection.name,
+        color: collection.color,
+        description: collection.description,
+        exported_at: Local::now().to_rfc3339(),
+        entries,
+        missing: missing.clone(),
+    };
+
+    let manifest_json = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+    zip.start_file(
+        "manifest.json",
+        SimpleFileOptions::default().compression_method(CompressionMethod::Deflated),
+    )
+    .map_err(|e| e.to_string())?;
+    zip.write_all(manifest_json.as_bytes())
+        .map_err(|e| e.to_string())?;
+    zip.finish().map_err(|e| e.to_string())?;
+
+    Ok(CollectionExportReport {
+        output_path: output_zip,
+        exported,
+        missing,
+    })
+}
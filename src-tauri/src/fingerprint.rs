@@ -0,0 +1,124 @@
+use std::path::Path;
+
+use image::imageops::FilterType;
+use image::DynamicImage;
+use tauri::{AppHandle, Manager};
+
+use crate::pdf::{find_pdf_entry, load_thumbnails_from_file};
+use crate::state::AppState;
+
+// Difference hash: 64 bits, one per horizontally adjacent pixel pair of a 9x8 grayscale
+// downscale. Visually identical renders land within a few bits of each other.
+pub fn dhash(image: &DynamicImage) -> u64 {
+    let small = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y)[0];
+            let right = small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(left > right);
+        }
+    }
+    hash
+}
+
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+// Groups items whose hashes are within `max_distance` of each other (transitively),
+// returning only groups with more than one member.
+pub fn group_similar<T: Copy>(hashes: &[(T, u64)], max_distance: u32) -> Vec<Vec<T>> {
+    let mut group_of: Vec<Option<usize>> = vec![None; hashes.len()];
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+
+    for i in 0..hashes.len() {
+        if group_of[i].is_some() {
+            continue;
+        }
+
+        let group_idx = groups.len();
+        group_of[i] = Some(group_idx);
+        let mut members = vec![i];
+        let mut cursor = 0;
+
+        while cursor < members.len() {
+            let current = members[cursor];
+            for j in 0..hashes.len() {
+                if group_of[j].is_none()
+                    && hamming_distance(hashes[current].1, hashes[j].1) <= max_distance
+                {
+                    group_of[j] = Some(group_idx);
+                    members.push(j);
+                }
+            }
+            cursor += 1;
+        }
+
+        groups.push(members);
+    }
+
+    groups
+        .into_iter()
+        .filter(|members| members.len() > 1)
+        .map(|mut members| {
+            members.sort_unstable();
+            members.into_iter().map(|i| hashes[i].0).collect()
+        })
+        .collect()
+}
+
+#[tauri::command]
+pub fn find_duplicate_pages(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    max_distance: Option<u32>,
+) -> Result<Vec<Vec<u32>>, String> {
+    log::info!("Looking for duplicate pages in pdf {pdf_id}");
+
+    let max_distance = max_distance.unwrap_or(4);
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let pdf_entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+    let thumbnails =
+        load_thumbnails_from_file(&app_data_dir.join(format!("pdf_{pdf_id}/thumbs.json")))?;
+
+    let pdfium = app_handle.state::<AppState>().pdfium()?;
+    let document = pdfium
+        .load_pdf_from_file(&pdf_entry.clone_path, None)
+        .map_err(|e| e.to_string())?;
+
+    let mut hashes: Vec<(u32, u64)> = Vec::new();
+
+    for (i, page) in document.pages().iter().enumerate() {
+        let page_no = i as u32 + 1;
+
+        // the extracted thumbnail is already a small render of the page, reuse it when present
+        let cached = thumbnails
+            .inner
+            .get(&page_no)
+            .filter(|thumb| Path::new(thumb).exists())
+            .and_then(|thumb| image::open(thumb).ok());
+
+        let image = match cached {
+            Some(image) => image,
+            None => {
+                let size = page.page_size();
+                let width = (size.width().value / 3.0) as i32;
+                let height = (size.height().value / 3.0) as i32;
+                page.render(width, height, None)
+                    .map_err(|e| e.to_string())?
+                    .as_image()
+            }
+        };
+
+        hashes.push((page_no, dhash(&image)));
+    }
+
+    Ok(group_similar(&hashes, max_distance))
+}
@@ -0,0 +1,649 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::collections::{collections_file_path, read_collections, retain_known_members};
+use crate::encryption::is_sensitive;
+use crate::error::AppError;
+use crate::pdf::{
+    get_bookmarks_path, load_bookmarks_from_file, load_dims_from_file, load_strokes_from_file,
+    load_thumbnails_from_file, pdf_page_count, read_pdf_entries, regenerate_previews,
+    save_bookmarks_to_file, sort_bookmarks, PdfBookmarks, PdfEditorSyncProps, PdfEntry,
+};
+use crate::persistence::{backup_path, read_data_file};
+use crate::starred::load_starred;
+use crate::state::AppState;
+use crate::text::load_text_cache;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    // cosmetic, e.g. a thumbnail that will be regenerated
+    Info,
+    // part of a document's data is lost or ignored
+    Warning,
+    // the document can't be opened
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IssueKind {
+    MissingClone,
+    UnreadableClone,
+    MissingCover,
+    CorruptSidecar,
+    MissingThumbnails,
+    DanglingCollectionMember,
+    BookmarkOutOfRange,
+    // a backup left behind by a file that was removed since, e.g. page_labels.json
+    OrphanBackup,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthIssue {
+    pub(crate) kind: IssueKind,
+    pub(crate) severity: Severity,
+    // None for issues outside any one document's folder
+    pub(crate) pdf_id: Option<u64>,
+    // set for collection issues
+    pub(crate) collection_id: Option<String>,
+    // sidecar file name for sidecar issues
+    pub(crate) file: Option<String>,
+    // affected pages for thumbnail and bookmark issues
+    pub(crate) pages: Vec<u32>,
+    pub(crate) detail: String,
+}
+
+impl HealthIssue {
+    fn new(kind: IssueKind, severity: Severity, pdf_id: Option<u64>, detail: String) -> Self {
+        Self {
+            kind,
+            severity,
+            pdf_id,
+            collection_id: None,
+            file: None,
+            pages: Vec::new(),
+            detail,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    checked_pdfs: usize,
+    // most severe first
+    issues: Vec<HealthIssue>,
+}
+
+pub(crate) const SIDECARS: [&str; 8] = [
+    "strokes.json",
+    "bookmarks.json",
+    "thumbs.json",
+    "dims.json",
+    "editor.json",
+    "text.json",
+    "starred.json",
+    "annotated_thumbs.json",
+];
+
+// Encrypted notes can't be checked while locked, that isn't damage
+fn readable<T>(loaded: Result<T, AppError>) -> Result<(), String> {
+    match loaded {
+        Ok(_) | Err(AppError::Locked(_)) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// Reads a sidecar with the loader the app uses for it, so "corrupt" means what the app would hit
+pub(crate) fn check_sidecar(app_handle: &AppHandle, name: &str, path: &Path) -> Result<(), String> {
+    match name {
+        "strokes.json" => readable(load_strokes_from_file(app_handle, path)),
+        "bookmarks.json" => readable(load_bookmarks_from_file(app_handle, path)),
+        "thumbs.json" => load_thumbnails_from_file(path).map(|_| ()),
+        "dims.json" => load_dims_from_file(path).map(|_| ()),
+        "text.json" => load_text_cache(path).map(|_| ()),
+        "starred.json" => load_starred(path).map(|_| ()),
+        "editor.json" => {
+            let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+            serde_json::from_str::<PdfEditorSyncProps>(&data)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }
+        // note.json and the stored profile files, possibly encrypted like the ones above
+        _ if is_sensitive(path) => readable(read_data_file(app_handle, path).and_then(|data| {
+            serde_json::from_str::<serde_json::Value>(data.as_deref().unwrap_or("null"))
+                .map_err(AppError::from)
+        })),
+        _ => {
+            let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+            serde_json::from_str::<serde_json::Value>(&data)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }
+    }
+}
+
+// Backups in `folder` (name, size) whose file is gone, nothing can restore from them
+fn orphan_backups(folder: &Path) -> Vec<(String, u64)> {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return Vec::new();
+    };
+
+    let mut orphans: Vec<(String, u64)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let original = name.strip_suffix(".bak")?;
+            if folder.join(original).exists() {
+                return None;
+            }
+            Some((name, entry.metadata().ok()?.len()))
+        })
+        .collect();
+    orphans.sort();
+    orphans
+}
+
+// Problems with one document's clone, cover and sidecars
+pub(crate) fn check_pdf(
+    app_handle: &AppHandle,
+    app_data_dir: &Path,
+    entry: &PdfEntry,
+) -> Vec<HealthIssue> {
+    let mut issues = Vec::new();
+    let pdf_id = Some(entry.id);
+    let folder_path = app_data_dir.join(format!("pdf_{}", entry.id));
+
+    let page_count = if !Path::new(&entry.clone_path).exists() {
+        issues.push(HealthIssue::new(
+            IssueKind::MissingClone,
+            Severity::Error,
+            pdf_id,
+            format!("PDF file missing: {}", entry.clone_path),
+        ));
+        None
+    } else {
+        match pdf_page_count(app_handle, entry.id) {
+            Ok(page_count) => Some(page_count),
+            Err(e) => {
+                issues.push(HealthIssue::new(
+                    IssueKind::UnreadableClone,
+                    Severity::Error,
+                    pdf_id,
+                    format!("PDF file can't be opened: {e}"),
+                ));
+                None
+            }
+        }
+    };
+
+    // an empty cover path means it's still being rendered
+    if !entry.cover_path.is_empty() && !Path::new(&entry.cover_path).exists() {
+        issues.push(HealthIssue::new(
+            IssueKind::MissingCover,
+            Severity::Info,
+            pdf_id,
+            format!("Cover missing: {}", entry.cover_path),
+        ));
+    }
+
+    for name in SIDECARS {
+        let path = folder_path.join(name);
+        if !path.exists() {
+            continue;
+        }
+        if let Err(e) = check_sidecar(app_handle, name, &path) {
+            issues.push(HealthIssue {
+                file: Some(name.to_string()),
+                ..HealthIssue::new(
+                    IssueKind::CorruptSidecar,
+                    Severity::Warning,
+                    pdf_id,
+                    format!("{name} can't be read: {e}"),
+                )
+            });
+        }
+    }
+
+    issues.extend(
+        orphan_backups(&folder_path)
+            .into_iter()
+            .map(|(name, size)| HealthIssue {
+                file: Some(name.clone()),
+                ..HealthIssue::new(
+                    IssueKind::OrphanBackup,
+                    Severity::Info,
+                    pdf_id,
+                    format!("{name} is the backup of a file that no longer exists ({size} bytes)"),
+                )
+            }),
+    );
+
+    if let Ok(thumbnails) = load_thumbnails_from_file(&folder_path.join("thumbs.json")) {
+        let mut missing: Vec<u32> = thumbnails
+            .inner
+            .iter()
+            .filter(|(_, path)| !Path::new(path).exists())
+            .map(|(page, _)| *page)
+            .collect();
+        if !missing.is_empty() {
+            missing.sort_unstable();
+            issues.push(HealthIssue {
+                pages: missing,
+                ..HealthIssue::new(
+                    IssueKind::MissingThumbnails,
+                    Severity::Info,
+                    pdf_id,
+                    "Thumbnails are listed but their images are gone".to_string(),
+                )
+            });
+        }
+    }
+
+    if let Some(page_count) = page_count {
+        let bookmarks = load_bookmarks_from_file(app_handle, &folder_path.join("bookmarks.json"));
+        if let Ok(bookmarks) = bookmarks {
+            let mut out_of_range: Vec<u32> = bookmarks
+                .iter()
+                .filter(|bm| bm.page_number > page_count || bm.end_page > Some(page_count))
+                .map(|bm| bm.page_number)
+                .collect();
+            if !out_of_range.is_empty() {
+                out_of_range.sort_unstable();
+                out_of_range.dedup();
+                issues.push(HealthIssue {
+                    pages: out_of_range,
+                    ..HealthIssue::new(
+                        IssueKind::BookmarkOutOfRange,
+                        Severity::Warning,
+                        pdf_id,
+                        format!("Bookmarks point past the last page ({page_count})"),
+                    )
+                });
+            }
+        }
+    }
+
+    issues
+}
+
+// Collection members that aren't in the library anymore
+pub(crate) fn check_collections(
+    app_handle: &AppHandle,
+    pdfs: &[PdfEntry],
+) -> Result<Vec<HealthIssue>, String> {
+    let known: HashSet<String> = pdfs.iter().map(|pdf| pdf.id.to_string()).collect();
+    let collections = read_collections(&collections_file_path(app_handle)?)?;
+
+    let mut issues = Vec::new();
+    for collection in collections.collections {
+        let mut dangling: Vec<&String> = collection
+            .pdf_ids
+            .keys()
+            .filter(|id| !known.contains(*id))
+            .collect();
+        dangling.sort();
+
+        for member_id in dangling {
+            issues.push(HealthIssue {
+                collection_id: Some(collection.id.clone()),
+                ..HealthIssue::new(
+                    IssueKind::DanglingCollectionMember,
+                    Severity::Info,
+                    member_id.parse().ok(),
+                    format!(
+                        "Collection '{}' lists pdf {member_id} which is not in the library",
+                        collection.name
+                    ),
+                )
+            });
+        }
+    }
+
+    Ok(issues)
+}
+
+// Every issue in the library, `pdfs` being the current registry
+fn collect_issues(
+    app_handle: &AppHandle,
+    app_data_dir: &Path,
+    pdfs: &[PdfEntry],
+) -> Vec<HealthIssue> {
+    let mut issues: Vec<HealthIssue> = pdfs
+        .iter()
+        .flat_map(|entry| check_pdf(app_handle, app_data_dir, entry))
+        .collect();
+
+    match check_collections(app_handle, pdfs) {
+        Ok(collection_issues) => issues.extend(collection_issues),
+        Err(e) => issues.push(HealthIssue {
+            file: Some("collections.json".to_string()),
+            ..HealthIssue::new(
+                IssueKind::CorruptSidecar,
+                Severity::Warning,
+                None,
+                format!("collections.json can't be read: {e}"),
+            )
+        }),
+    }
+
+    // stable, so issues of one document stay together within a severity
+    issues.sort_by(|a, b| b.severity.cmp(&a.severity));
+    issues
+}
+
+// Read-only: reports what's wrong without touching anything
+#[tauri::command]
+pub fn library_health_check(app_handle: AppHandle) -> Result<HealthReport, String> {
+    log::info!("Checking library health");
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let pdfs = read_pdf_entries(&app_data_dir.join("pdfs.json"))?;
+    let issues = collect_issues(&app_handle, &app_data_dir, &pdfs);
+
+    if !issues.is_empty() {
+        log::warn!("Library health check found {} issue(s)", issues.len());
+    }
+
+    Ok(HealthReport {
+        checked_pdfs: pdfs.len(),
+        issues,
+    })
+}
+
+// Which fixes repair_library may apply, all off unless asked for
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct RepairOptions {
+    // re-render covers and thumbnails whose files are gone
+    regenerate_previews: bool,
+    // remove collection memberships of pdfs no longer in the library
+    drop_dangling_members: bool,
+    // move bookmarks past the last page onto it
+    clamp_bookmarks: bool,
+    // replace unreadable json files with their previous version, when that one reads
+    restore_backups: bool,
+    // delete backups of files that no longer exist
+    remove_orphan_backups: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RepairAction {
+    kind: IssueKind,
+    pdf_id: Option<u64>,
+    detail: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RepairReport {
+    repaired: Vec<RepairAction>,
+    // issues a selected fix applied to but couldn't resolve
+    failed: Vec<RepairAction>,
+}
+
+impl RepairReport {
+    fn record(&mut self, issue: &HealthIssue, outcome: Result<String, String>) {
+        let action = |detail| RepairAction {
+            kind: issue.kind,
+            pdf_id: issue.pdf_id,
+            detail,
+        };
+        match outcome {
+            Ok(detail) => self.repaired.push(action(detail)),
+            Err(e) => {
+                log::warn!(
+                    "Failed to repair {:?} of pdf {:?}: {e}",
+                    issue.kind,
+                    issue.pdf_id
+                );
+                self.failed.push(action(e));
+            }
+        }
+    }
+}
+
+// Copies `file`'s backup over it when the backup passes the same check
+fn restore_backup(
+    file: &Path,
+    check: impl Fn(&Path) -> Result<(), String>,
+) -> Result<String, String> {
+    let backup = backup_path(file);
+    if !backup.exists() {
+        return Err(format!("No backup of {}", file.display()));
+    }
+    check(&backup).map_err(|e| format!("Backup is unreadable too: {e}"))?;
+
+    fs::copy(&backup, file).map_err(|e| e.to_string())?;
+    Ok(format!("Restored {} from its backup", file.display()))
+}
+
+// Pulls every bookmark onto the document's pages, then drops the copies clamping created
+// so a page never ends up with the same bookmark twice. Returns how many were moved.
+pub(crate) fn clamp_bookmark_pages(bookmarks: &mut PdfBookmarks, page_count: u32) -> usize {
+    let mut clamped = 0;
+    for bookmark in bookmarks.iter_mut() {
+        let before = (bookmark.page_number, bookmark.end_page);
+        bookmark.page_number = bookmark.page_number.clamp(1, page_count.max(1));
+        bookmark.end_page = bookmark
+            .end_page
+            .map(|end| end.min(page_count))
+            .filter(|&end| end > bookmark.page_number);
+        if (bookmark.page_number, bookmark.end_page) != before {
+            clamped += 1;
+        }
+    }
+
+    sort_bookmarks(bookmarks);
+    bookmarks.dedup_by(|a, b| {
+        a.page_number == b.page_number && a.label == b.label && a.end_page == b.end_page
+    });
+    clamped
+}
+
+fn clamp_bookmarks(app_handle: &AppHandle, pdf_id: u64) -> Result<String, String> {
+    let page_count = pdf_page_count(app_handle, pdf_id)?;
+    let path = get_bookmarks_path(app_handle, pdf_id)?;
+
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    let mut bookmarks = load_bookmarks_from_file(app_handle, &path)?;
+    let clamped = clamp_bookmark_pages(&mut bookmarks, page_count);
+    save_bookmarks_to_file(app_handle, &path, &bookmarks)?;
+    Ok(format!(
+        "Moved {clamped} bookmark(s) onto page {page_count}"
+    ))
+}
+
+// Applies the selected fixes for what library_health_check would report. Backups are
+// restored first so the other fixes see the recovered files.
+#[tauri::command]
+pub fn repair_library(app_handle: AppHandle, fix: RepairOptions) -> Result<RepairReport, String> {
+    log::info!("Repairing library: {fix:?}");
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let state_path = app_data_dir.join("pdfs.json");
+    let mut report = RepairReport::default();
+
+    if fix.restore_backups {
+        if let Err(e) = read_pdf_entries(&state_path) {
+            let issue = HealthIssue {
+                file: Some("pdfs.json".to_string()),
+                ..HealthIssue::new(IssueKind::CorruptSidecar, Severity::Error, None, e)
+            };
+            let state = app_handle.state::<AppState>();
+            let _guard = state.registry_lock.lock().unwrap();
+            let outcome = restore_backup(&state_path, |path| read_pdf_entries(path).map(|_| ()));
+            report.record(&issue, outcome);
+        }
+    }
+
+    let pdfs = read_pdf_entries(&state_path)?;
+
+    if fix.restore_backups {
+        let corrupt = collect_issues(&app_handle, &app_data_dir, &pdfs)
+            .into_iter()
+            .filter(|issue| issue.kind == IssueKind::CorruptSidecar);
+        for issue in corrupt {
+            let Some(name) = issue.file.clone() else {
+                continue;
+            };
+            let outcome = match issue.pdf_id {
+                Some(pdf_id) => {
+                    let path = app_data_dir.join(format!("pdf_{pdf_id}/{name}"));
+                    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+                    let _guard = lock.lock().unwrap();
+                    restore_backup(&path, |backup| check_sidecar(&app_handle, &name, backup))
+                }
+                None => collections_file_path(&app_handle).and_then(|path| {
+                    restore_backup(&path, |backup| {
+                        read_collections(&backup.to_path_buf()).map(|_| ())
+                    })
+                }),
+            };
+            report.record(&issue, outcome);
+        }
+    }
+
+    let issues = collect_issues(&app_handle, &app_data_dir, &pdfs);
+
+    if fix.regenerate_previews {
+        let mut regenerated = HashSet::new();
+        let stale = issues.iter().filter(|issue| {
+            matches!(
+                issue.kind,
+                IssueKind::MissingCover | IssueKind::MissingThumbnails
+            )
+        });
+        for issue in stale {
+            let Some(entry) = pdfs.iter().find(|pdf| Some(pdf.id) == issue.pdf_id) else {
+                continue;
+            };
+            // one pass renders both, a second issue of the same pdf only needs reporting
+            let outcome = if regenerated.insert(entry.id) {
+                let folder_path = app_data_dir.join(format!("pdf_{}", entry.id));
+                regenerate_previews(&app_handle, entry, &folder_path)
+                    .map(|_| "Re-rendering cover and thumbnails".to_string())
+            } else {
+                Ok("Re-rendering cover and thumbnails".to_string())
+            };
+            report.record(issue, outcome);
+        }
+    }
+
+    if fix.clamp_bookmarks {
+        for issue in issues
+            .iter()
+            .filter(|i| i.kind == IssueKind::BookmarkOutOfRange)
+        {
+            if let Some(pdf_id) = issue.pdf_id {
+                report.record(issue, clamp_bookmarks(&app_handle, pdf_id));
+            }
+        }
+    }
+
+    if fix.drop_dangling_members {
+        let dangling: Vec<&HealthIssue> = issues
+            .iter()
+            .filter(|issue| issue.kind == IssueKind::DanglingCollectionMember)
+            .collect();
+        if !dangling.is_empty() {
+            let known: HashSet<String> = pdfs.iter().map(|pdf| pdf.id.to_string()).collect();
+            let outcome = retain_known_members(&app_handle, &known);
+            for issue in dangling {
+                let outcome = outcome
+                    .as_ref()
+                    .map(|_| "Removed from the collection".to_string())
+                    .map_err(|e| e.clone());
+                report.record(issue, outcome);
+            }
+        }
+    }
+
+    if fix.remove_orphan_backups {
+        for issue in issues.iter().filter(|i| i.kind == IssueKind::OrphanBackup) {
+            if let (Some(pdf_id), Some(name)) = (issue.pdf_id, &issue.file) {
+                let path = app_data_dir.join(format!("pdf_{pdf_id}/{name}"));
+                let outcome = fs::remove_file(&path)
+                    .map(|_| format!("Removed {name}"))
+                    .map_err(|e| e.to_string());
+                report.record(issue, outcome);
+            }
+        }
+    }
+
+    log::info!(
+        "Library repair fixed {} issue(s), {} failed",
+        report.repaired.len(),
+        report.failed.len()
+    );
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::PdfBookmark;
+
+    fn bookmark(page_number: u32, label: &str, end_page: Option<u32>) -> PdfBookmark {
+        PdfBookmark {
+            page_number,
+            label: label.to_string(),
+            end_page,
+        }
+    }
+
+    #[test]
+    fn clamping_drops_the_duplicates_it_creates() {
+        let mut bookmarks = vec![
+            bookmark(3, "Appendix", None),
+            bookmark(9, "Appendix", None),
+            bookmark(2, "Intro", Some(12)),
+            bookmark(0, "Cover", None),
+        ];
+
+        assert_eq!(clamp_bookmark_pages(&mut bookmarks, 3), 3);
+        let pages: Vec<_> = bookmarks
+            .iter()
+            .map(|b| (b.page_number, b.label.as_str(), b.end_page))
+            .collect();
+        assert_eq!(
+            pages,
+            vec![
+                (1, "Cover", None),
+                (2, "Intro", Some(3)),
+                (3, "Appendix", None)
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_backups_without_their_file() {
+        let dir = std::env::temp_dir().join(format!("akda_health_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("strokes.json"), "{}").unwrap();
+        fs::write(dir.join("strokes.json.bak"), "{}").unwrap();
+        fs::write(dir.join("page_labels.json.bak"), "{\"1\":\"i\"}").unwrap();
+
+        assert_eq!(
+            orphan_backups(&dir),
+            vec![("page_labels.json.bak".to_string(), 9)]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,64 @@
+use std::fs;
+use std::path::Path;
+
+use pdfium_render::prelude::*;
+use tauri::{AppHandle, Manager};
+
+use crate::error::AppError;
+use crate::pdf::find_pdf_entry;
+use crate::state::AppState;
+
+// Saves the images embedded in a page to `output_dir` as png, in the page's object order.
+// Images are saved as stored in the file, without the page's scaling or clipping.
+// Returns the written paths, empty when the page has no images.
+#[tauri::command]
+pub fn extract_page_images(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    page_number: u32,
+    output_dir: String,
+) -> Result<Vec<String>, AppError> {
+    log::info!("Extracting images of page {page_number} of pdf {pdf_id} to {output_dir}");
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    let pdf_entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+    let pdfium = app_handle.state::<AppState>().pdfium()?;
+    let document = pdfium.load_pdf_from_file(&pdf_entry.clone_path, None)?;
+
+    let page_count = document.pages().len() as u32;
+    if page_number == 0 || page_number > page_count {
+        return Err(AppError::InvalidInput(format!(
+            "Page {page_number} is out of range (1-{page_count})"
+        )));
+    }
+    let page = document.pages().get((page_number - 1) as PdfPageIndex)?;
+
+    let output_dir = Path::new(&output_dir);
+    fs::create_dir_all(output_dir)?;
+
+    // named after the export name so images of different documents don't collide
+    let export_name = pdf_entry.export_file_name();
+    let stem = export_name.strip_suffix(".pdf").unwrap_or(&export_name);
+
+    let mut paths = Vec::new();
+    for object in page.objects().iter() {
+        let Some(image_object) = object.as_image_object() else {
+            continue;
+        };
+
+        let image = match image_object.get_raw_image() {
+            Ok(image) => image,
+            Err(e) => {
+                log::warn!("Skipping undecodable image on page {page_number}: {e}");
+                continue;
+            }
+        };
+
+        let path = output_dir.join(format!("{stem}_page{page_number}_{}.png", paths.len() + 1));
+        image.save(&path).map_err(|e| AppError::Io(e.to_string()))?;
+        paths.push(path.to_string_lossy().into_owned());
+    }
+
+    log::info!("Extracted {} image(s) from page {page_number}", paths.len());
+    Ok(paths)
+}
@@ -0,0 +1,212 @@
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::error::AppError;
+use crate::pdf::{
+    find_pdf_entry, hash_file, load_bookmarks_from_file, load_strokes_from_file,
+    load_thumbnails_from_file, pdf_page_count, ExtractionProgress, PdfEntry, PdfPagesDimensions,
+};
+use crate::state::AppState;
+
+const SIDECARS: [&str; 7] = [
+    "dims.json",
+    "thumbs.json",
+    "strokes.json",
+    "bookmarks.json",
+    "editor.json",
+    "text.json",
+    "starred.json",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SidecarInfo {
+    name: String,
+    exists: bool,
+    size: u64,
+}
+
+// Counts are None when the file exists but can't be parsed
+#[derive(Debug, Clone, Serialize)]
+pub struct PdfInspection {
+    entry: PdfEntry,
+    clone_exists: bool,
+    original_exists: bool,
+    page_count: Option<u32>,
+    // why the clone couldn't be opened when page_count is None
+    page_count_error: Option<String>,
+    sidecars: Vec<SidecarInfo>,
+    stroke_count: Option<usize>,
+    stroked_pages: Option<usize>,
+    bookmark_count: Option<usize>,
+    dims_pages: Option<usize>,
+    thumbnail_pages: Option<usize>,
+    ocr_running: bool,
+    // the running thumbnail extraction, None when nothing is being extracted
+    extraction: Option<ExtractionProgress>,
+}
+
+fn read_dims_count(path: &Path) -> Result<usize, String> {
+    if !path.exists() {
+        return Ok(0);
+    }
+
+    let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let dims = serde_json::from_str::<PdfPagesDimensions>(&data).map_err(|e| e.to_string())?;
+    Ok(dims.inner.len())
+}
+
+#[tauri::command]
+pub fn inspect_pdf(app_handle: AppHandle, pdf_id: u64) -> Result<PdfInspection, AppError> {
+    log::info!("Inspecting pdf {pdf_id}");
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+
+    let entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+    let folder_path = app_data_dir.join(format!("pdf_{pdf_id}"));
+
+    let sidecars = SIDECARS
+        .iter()
+        .map(|name| {
+            let metadata = fs::metadata(folder_path.join(name)).ok();
+            SidecarInfo {
+                name: name.to_string(),
+                exists: metadata.is_some(),
+                size: metadata.map(|m| m.len()).unwrap_or(0),
+            }
+        })
+        .collect();
+
+    let strokes = load_strokes_from_file(&app_handle, &folder_path.join("strokes.json")).ok();
+    let bookmarks = load_bookmarks_from_file(&app_handle, &folder_path.join("bookmarks.json")).ok();
+    let thumbnails = load_thumbnails_from_file(&folder_path.join("thumbs.json")).ok();
+
+    let state = app_handle.state::<AppState>();
+    let ocr_running = state.ocr_jobs.lock().unwrap().contains_key(&pdf_id);
+    let extraction = state
+        .extraction_progress
+        .lock()
+        .unwrap()
+        .get(&pdf_id)
+        .cloned();
+
+    let page_count = pdf_page_count(&app_handle, pdf_id);
+
+    Ok(PdfInspection {
+        clone_exists: Path::new(&entry.clone_path).exists(),
+        original_exists: Path::new(&entry.original_path).exists(),
+        page_count: page_count.as_ref().ok().copied(),
+        page_count_error: page_count.err(),
+        sidecars,
+        stroke_count: strokes
+            .as_ref()
+            .map(|s| s.inner.values().map(Vec::len).sum()),
+        stroked_pages: strokes
+            .as_ref()
+            .map(|s| s.inner.values().filter(|page| !page.is_empty()).count()),
+        bookmark_count: bookmarks.map(|b| b.len()),
+        dims_pages: read_dims_count(&folder_path.join("dims.json")).ok(),
+        thumbnail_pages: thumbnails.map(|t| t.inner.len()),
+        ocr_running,
+        extraction,
+        entry,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IntegrityStatus {
+    Ok,
+    // opens fine, but the entry predates clone checksums so there's nothing to compare against
+    Unverified,
+    Missing,
+    ChecksumMismatch,
+    OpenFailed,
+    PageCountMismatch,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityReport {
+    pdf_id: u64,
+    status: IntegrityStatus,
+    expected_hash: Option<String>,
+    actual_hash: Option<String>,
+    expected_pages: Option<u32>,
+    actual_pages: Option<u32>,
+    // pdfium's error when the clone doesn't open
+    open_error: Option<String>,
+    // command that repairs the clone, None when it's intact or the original is gone too
+    remediation: Option<String>,
+}
+
+// Re-hashes the clone and opens it, comparing both against what was recorded when the app
+// last wrote it. A checksum mismatch on a clone that still opens is reported as such; an
+// unreadable clone is an open failure whatever its hash.
+#[tauri::command]
+pub fn verify_pdf_integrity(
+    app_handle: AppHandle,
+    pdf_id: u64,
+) -> Result<IntegrityReport, AppError> {
+    log::info!("Verifying integrity of pdf {pdf_id}");
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+
+    let entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+    let clone_path = Path::new(&entry.clone_path);
+    let pdfium = app_handle.state::<AppState>().pdfium()?;
+
+    let mut report = IntegrityReport {
+        pdf_id,
+        status: IntegrityStatus::Ok,
+        expected_hash: entry.clone_hash.clone(),
+        actual_hash: None,
+        expected_pages: entry.page_count,
+        actual_pages: None,
+        open_error: None,
+        remediation: None,
+    };
+
+    if !clone_path.exists() {
+        report.status = IntegrityStatus::Missing;
+    } else {
+        // the clone isn't rewritten while its pdf lock is held
+        let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+        let _guard = lock.lock().unwrap();
+
+        report.actual_hash = Some(hash_file(clone_path)?);
+        match pdfium.load_pdf_from_file(clone_path, None) {
+            Ok(document) => report.actual_pages = Some(document.pages().len() as u32),
+            Err(e) => report.open_error = Some(e.to_string()),
+        }
+
+        report.status = if report.open_error.is_some() {
+            IntegrityStatus::OpenFailed
+        } else if report.expected_hash.is_some() && report.expected_hash != report.actual_hash {
+            IntegrityStatus::ChecksumMismatch
+        } else if report.expected_pages.is_some() && report.expected_pages != report.actual_pages {
+            IntegrityStatus::PageCountMismatch
+        } else if report.expected_hash.is_none() {
+            IntegrityStatus::Unverified
+        } else {
+            IntegrityStatus::Ok
+        };
+    }
+
+    let damaged = !matches!(
+        report.status,
+        IntegrityStatus::Ok | IntegrityStatus::Unverified
+    );
+    if damaged {
+        log::warn!(
+            "Clone of pdf {pdf_id} failed verification: {:?}",
+            report.status
+        );
+        if Path::new(&entry.original_path).exists() {
+            report.remediation = Some("refresh_clone".to_string());
+        }
+    }
+
+    Ok(report)
+}
@@ -1,6 +1,47 @@
+mod annotated;
+mod archive;
 mod collections;
+mod color;
+mod compact;
+mod compact_strokes;
+mod contact_sheet;
+mod duplicate;
+mod encryption;
+mod eraser;
+mod error;
+mod export;
+mod fingerprint;
+mod health;
+mod images;
+mod inspect;
+mod note;
+mod ocr;
+mod outline;
+mod page_labels;
+mod pages;
 mod pdf;
+mod pdfium;
+mod persistence;
+mod phash;
+mod profiles;
+mod render;
+mod replay;
+mod report;
+mod settings;
+mod shutdown;
+mod simplify;
+mod snapshots;
+mod starred;
 mod state;
+mod storage;
+mod stroke_queue;
+mod study_sets;
+mod svg;
+mod tags;
+mod template;
+mod text;
+mod timestamp;
+mod watch;
 
 use regex::Regex;
 use serde::{Deserialize, Serialize};
@@ -67,6 +108,7 @@ fn greet(name: &str) -> String {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppPreferences {
     pub theme: String,
+    // Settings the backend acts on live in settings.json, see settings::AppSettings
     // Add new persistent preferences here, e.g.:
     // pub auto_save: bool,
     // pub language: String,
@@ -94,10 +136,8 @@ fn get_preferences_path(app: &AppHandle) -> Result<PathBuf, String> {
     Ok(app_data_dir.join("preferences.json"))
 }
 
-#[tauri::command]
-async fn load_preferences(app: AppHandle) -> Result<AppPreferences, String> {
-    log::debug!("Loading preferences from disk");
-    let prefs_path = get_preferences_path(&app)?;
+fn read_preferences(app: &AppHandle) -> Result<AppPreferences, String> {
+    let prefs_path = get_preferences_path(app)?;
 
     if !prefs_path.exists() {
         log::info!("Preferences file not found, using defaults");
@@ -114,6 +154,14 @@ async fn load_preferences(app: AppHandle) -> Result<AppPreferences, String> {
         format!("Failed to parse preferences: {e}")
     })?;
 
+    Ok(preferences)
+}
+
+#[tauri::command]
+async fn load_preferences(app: AppHandle) -> Result<AppPreferences, String> {
+    log::debug!("Loading preferences from disk");
+    let preferences = read_preferences(&app)?;
+
     log::info!("Successfully loaded preferences");
     Ok(preferences)
 }
@@ -521,7 +569,23 @@ pub fn run() {
                 pdfium_dir.join("linux")
             };
 
-            app.manage(AppState { lib_path });
+            let pdfium = pdfium::bind_pdfium(&lib_path);
+            if let Err(e) = &pdfium {
+                log::error!("PDF features disabled: {e}");
+            }
+
+            app.manage(AppState::new(lib_path, pdfium));
+
+            settings::init_settings(app.handle());
+
+            match pdf::migrate_registry_paths(app.handle()) {
+                Ok(true) => log::info!("Stored library paths relative to the app data dir"),
+                Ok(false) => {}
+                Err(e) => log::warn!("Failed to migrate library paths: {e}"),
+            }
+
+            // registers into the registry, so only once it's migrated
+            watch::init_watch_folder(app.handle());
 
             Ok(())
         })
@@ -529,34 +593,153 @@ pub fn run() {
             greet,
             load_preferences,
             save_preferences,
+            settings::get_settings,
+            settings::update_settings,
             send_native_notification,
             save_emergency_data,
             load_emergency_data,
             cleanup_old_recovery_files,
+            pdf::probe_pdf,
             pdf::register_pdf,
             pdf::list_pdf,
+            pdf::stream_pdf_list,
+            pdf::list_pdf_page,
             pdf::remove_pdf,
+            archive::restore_archived_annotations,
             pdf::load_pdf,
             pdf::save_pdf_strokes,
             pdf::load_pdf_strokes,
+            pdf::load_pdf_strokes_range,
+            stroke_queue::queue_stroke,
+            stroke_queue::flush_strokes,
+            pdf::get_annotation_activity,
+            pdf::annotation_heatmap,
+            pdf::pdfs_with_annotations,
             pdf::load_thumbnails,
+            pdf::load_thumbnail_layouts,
+            pdf::missing_thumbnail_pages,
+            pdf::get_extraction_eta,
+            pdf::set_max_concurrent_extractions,
             pdf::rename_pdf,
+            pdf::relink_original,
+            pdf::original_file_status,
+            pdf::refresh_clone,
+            pdf::set_cover_from_page,
+            pdf::set_custom_cover,
+            pdf::open_pdf_externally,
             pdf::save_editor_settings,
             pdf::load_editor_settings,
+            pdf::reset_editor_settings,
+            pdf::get_page_count,
             pdf::get_pdf_bookmarks,
             pdf::add_pdf_bookmark,
             pdf::update_pdf_bookmark,
             pdf::delete_pdf_bookmark,
+            pdf::export_bookmarks,
+            pdf::import_bookmarks,
+            pdf::next_bookmark,
+            note::get_pdf_note,
+            note::set_pdf_note,
+            pdf::previous_bookmark,
+            pdf::validate_bookmarks,
+            pdf::fix_bookmarks,
+            profiles::create_annotation_profile,
+            profiles::list_profiles,
+            profiles::switch_profile,
+            export::export_annotated_pdf,
+            export::export_annotated_pages,
+            export::cancel_export,
+            export::export_collection,
+            fingerprint::find_duplicate_pages,
+            phash::cover_phash,
+            phash::find_similar_covers,
+            pdfium::get_pdfium_info,
+            render::render_page_dpi,
+            render::benchmark_render,
+            text::page_has_text,
+            text::pdf_text_coverage,
+            text::document_text_stats,
+            text::search_pdf_text_limited,
+            text::export_document_text,
+            ocr::ocr_pdf,
+            ocr::cancel_ocr,
+            storage::get_pdf_disk_usage,
+            storage::list_pdf_with_usage,
+            storage::get_free_disk_space,
+            template::create_blank_pdf,
+            pages::append_blank_pages,
+            pages::insert_blank_page,
+            pages::delete_pages,
+            pages::reorder_pages,
+            pages::merge_pdfs,
+            pages::split_pdf,
+            outline::export_outline_markdown,
+            inspect::inspect_pdf,
+            inspect::verify_pdf_integrity,
+            health::library_health_check,
+            health::repair_library,
+            compact::compact_ids,
+            duplicate::duplicate_pdf,
+            eraser::apply_eraser,
+            simplify::simplify_strokes,
+            compact_strokes::compact_strokes,
+            snapshots::snapshot_annotations,
+            snapshots::list_snapshots,
+            snapshots::restore_snapshot,
+            replay::export_stroke_replay,
+            encryption::set_pdf_encryption,
+            encryption::unlock_pdf,
+            images::extract_page_images,
+            annotated::generate_annotated_thumbnails,
+            annotated::export_annotated_page_images,
+            starred::get_starred_pages,
+            starred::toggle_starred_page,
+            tags::tag_pdf,
+            tags::untag_pdf,
+            tags::tag_pdfs,
+            tags::untag_pdfs,
+            tags::tag_collection,
+            tags::untag_collection,
+            study_sets::get_study_sets,
+            study_sets::create_study_set,
+            study_sets::delete_study_set,
+            study_sets::add_to_study_set,
+            study_sets::remove_from_study_set,
+            study_sets::get_study_set,
+            watch::set_watch_folder,
+            watch::clear_watch_folder,
+            page_labels::get_page_labels,
+            page_labels::set_page_label,
+            svg::export_page_annotations_svg,
+            svg::export_all_annotations_svg,
             collections::get_collections,
+            collections::get_collections_by_size,
             collections::create_collection,
             collections::rename_collection,
             collections::change_collection_color,
+            collections::set_collection_description,
             collections::delete_collection,
             collections::add_pdf_to_collection,
             collections::remove_pdf_from_collection,
             collections::toggle_pdf_in_collection,
-            collections::remove_pdf_from_all_collections
+            collections::remove_pdf_from_all_collections,
+            collections::list_uncategorized_pdfs,
+            collections::set_collection_sort,
+            collections::toggle_collection_pin,
+            collections::get_collection_pdfs,
+            contact_sheet::export_collection_contact_sheet,
+            color::contrasting_text_color,
+            shutdown::shutdown,
+            report::export_reading_report,
+            report::export_reading_report_json
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app_handle, event| {
+            // covers closing the last window and app.exit(), a prior shutdown call makes
+            // this return right away
+            if let tauri::RunEvent::Exit = event {
+                shutdown::flush_and_close(app_handle);
+            }
+        });
 }
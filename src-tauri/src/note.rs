@@ -0,0 +1,65 @@
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::error::AppError;
+use crate::pdf::find_pdf_entry;
+use crate::persistence::{read_data_file, write_json, FileKind};
+use crate::state::AppState;
+
+// A freeform note about the whole document, kept in note.json rather than pdfs.json since it
+// can grow long. Encrypted along with the annotations when the pdf has encryption on.
+#[derive(Debug, Serialize, Deserialize)]
+struct PdfNote {
+    note: String,
+    updated_at: String,
+}
+
+fn note_path(app_handle: &AppHandle, pdf_id: u64) -> Result<PathBuf, AppError> {
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    Ok(app_data_dir.join(format!("pdf_{pdf_id}/note.json")))
+}
+
+// Empty string when no note was set
+#[tauri::command]
+pub fn get_pdf_note(app_handle: AppHandle, pdf_id: u64) -> Result<String, AppError> {
+    log::info!("Loading note of pdf {pdf_id}");
+
+    let path = note_path(&app_handle, pdf_id)?;
+    match read_data_file(&app_handle, &path)? {
+        Some(data) if !data.trim().is_empty() => Ok(serde_json::from_str::<PdfNote>(&data)?.note),
+        _ => Ok(String::new()),
+    }
+}
+
+// Replaces the note, an empty one removes it. Returns the stored note.
+#[tauri::command]
+pub fn set_pdf_note(app_handle: AppHandle, pdf_id: u64, note: String) -> Result<String, AppError> {
+    log::info!("Saving note of pdf {pdf_id}");
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    find_pdf_entry(&app_data_dir, pdf_id)?;
+    let path = note_path(&app_handle, pdf_id)?;
+
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    let note = note.trim_end().to_string();
+    if note.is_empty() {
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        return Ok(note);
+    }
+
+    let pdf_note = PdfNote {
+        note,
+        updated_at: Local::now().to_rfc3339(),
+    };
+    write_json(&app_handle, &path, &pdf_note, FileKind::Note)?;
+
+    Ok(pdf_note.note)
+}
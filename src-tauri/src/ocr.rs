@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use pdfium_render::prelude::*;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::pdf::find_pdf_entry;
+use crate::persistence::{write_json, FileKind};
+use crate::state::AppState;
+use crate::text::{
+    load_text_cache, text_cache_path, text_stats_path, PageText, TextSource, WordBox,
+};
+
+// Pages are rendered at 3x their point size (216 dpi), enough for tesseract to be reliable
+const OCR_SCALE: f32 = 3.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OcrProgress {
+    pdf_id: u64,
+    page: u32,
+    total: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OcrComplete {
+    pdf_id: u64,
+    pages_recognized: usize,
+    cancelled: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OcrFailed {
+    pdf_id: u64,
+    error: String,
+}
+
+// Parses `tesseract ... tsv` output into page text (one line per recognized line) and word boxes
+fn parse_tesseract_tsv(tsv: &str) -> PageText {
+    let mut lines: BTreeMap<(u32, u32, u32, u32), Vec<String>> = BTreeMap::new();
+    let mut words = Vec::new();
+
+    for row in tsv.lines().skip(1) {
+        let columns: Vec<&str> = row.split('\t').collect();
+        if columns.len() < 12 || columns[0] != "5" {
+            continue;
+        }
+
+        let text = columns[11].trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        let number = |i: usize| columns[i].parse::<u32>().unwrap_or(0);
+        let line_key = (number(1), number(2), number(3), number(4));
+        lines.entry(line_key).or_default().push(text.to_string());
+
+        words.push(WordBox {
+            text: text.to_string(),
+            x: number(6) as f32 / OCR_SCALE,
+            y: number(7) as f32 / OCR_SCALE,
+            width: number(8) as f32 / OCR_SCALE,
+            height: number(9) as f32 / OCR_SCALE,
+        });
+    }
+
+    let text = lines
+        .into_values()
+        .map(|line| line.join(" "))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let char_count = text.chars().filter(|c| !c.is_whitespace()).count();
+
+    PageText {
+        text,
+        char_count,
+        source: TextSource::Ocr,
+        words,
+    }
+}
+
+fn recognize_page(page: &PdfPage, image_path: &Path, lang: &str) -> Result<PageText, String> {
+    let size = page.page_size();
+    let width = (size.width().value * OCR_SCALE) as i32;
+    let height = (size.height().value * OCR_SCALE) as i32;
+
+    page.render(width, height, None)
+        .map_err(|e| e.to_string())?
+        .as_image()
+        .save(image_path)
+        .map_err(|e| e.to_string())?;
+
+    let output = Command::new("tesseract")
+        .arg(image_path)
+        .arg("stdout")
+        .args(["-l", lang, "tsv"])
+        .output()
+        .map_err(|e| format!("Failed to run tesseract (is it installed?): {e}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "tesseract failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(parse_tesseract_tsv(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+fn run_ocr(
+    app_handle: &AppHandle,
+    pdf_id: u64,
+    lang: &str,
+    force: bool,
+    cancelled: &AtomicBool,
+) -> Result<(usize, bool), String> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let pdf_entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+    let cache_path = text_cache_path(&app_data_dir, pdf_id);
+    let image_path = app_data_dir.join(format!("pdf_{pdf_id}/ocr_page.png"));
+
+    let state = app_handle.state::<AppState>();
+    let pdfium = state.pdfium()?;
+    let document = pdfium
+        .load_pdf_from_file(&pdf_entry.clone_path, None)
+        .map_err(|e| e.to_string())?;
+
+    let total = document.pages().len() as u32;
+    let mut recognized: Vec<(u32, PageText)> = Vec::new();
+    let mut was_cancelled = false;
+
+    for (i, page) in document.pages().iter().enumerate() {
+        if cancelled.load(Ordering::Relaxed) {
+            was_cancelled = true;
+            break;
+        }
+
+        let page_no = i as u32 + 1;
+
+        // scans have no text layer, native text is already better than OCR
+        let native = page.text().map(|text| text.all()).unwrap_or_default();
+        if force || native.chars().all(char::is_whitespace) {
+            recognized.push((page_no, recognize_page(&page, &image_path, lang)?));
+        }
+
+        app_handle
+            .emit(
+                "ocr-progress",
+                OcrProgress {
+                    pdf_id,
+                    page: page_no,
+                    total,
+                },
+            )
+            .unwrap();
+    }
+
+    if image_path.exists() {
+        let _ = fs::remove_file(&image_path);
+    }
+
+    // keep whatever was recognized before a cancel
+    let pages_recognized = recognized.len();
+    if pages_recognized > 0 {
+        let lock = state.pdf_lock(pdf_id);
+        let _guard = lock.lock().unwrap();
+
+        let mut cache = load_text_cache(&cache_path)?;
+        for (page_no, page_text) in recognized {
+            cache.inner.insert(page_no, page_text);
+        }
+        write_json(app_handle, &cache_path, &cache, FileKind::Text)?;
+
+        // counted from the text that was just replaced
+        let stats_path = text_stats_path(&app_data_dir, pdf_id);
+        if stats_path.exists() {
+            fs::remove_file(&stats_path).map_err(|e| e.to_string())?;
+        }
+    }
+
+    Ok((pages_recognized, was_cancelled))
+}
+
+#[tauri::command]
+pub fn ocr_pdf(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    lang: Option<String>,
+    force: Option<bool>,
+) -> Result<(), String> {
+    let lang = lang.unwrap_or_else(|| "eng".to_string());
+    let force = force.unwrap_or(false);
+    log::info!("Starting OCR of pdf {pdf_id} ({lang})");
+
+    if lang.is_empty()
+        || !lang
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '_')
+    {
+        return Err(format!("Invalid OCR language: {lang}"));
+    }
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    {
+        let state = app_handle.state::<AppState>();
+        let mut jobs = state.ocr_jobs.lock().unwrap();
+        if jobs.contains_key(&pdf_id) {
+            return Err(format!("OCR is already running for pdf {pdf_id}"));
+        }
+        jobs.insert(pdf_id, cancelled.clone());
+    }
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let result = run_ocr(&app_handle, pdf_id, &lang, force, &cancelled);
+
+        app_handle
+            .state::<AppState>()
+            .ocr_jobs
+            .lock()
+            .unwrap()
+            .remove(&pdf_id);
+
+        match result {
+            Ok((pages_recognized, cancelled)) => {
+                log::info!("OCR of pdf {pdf_id} recognized {pages_recognized} page(s)");
+                app_handle
+                    .emit(
+                        "ocr-complete",
+                        OcrComplete {
+                            pdf_id,
+                            pages_recognized,
+                            cancelled,
+                        },
+                    )
+                    .unwrap();
+            }
+            Err(error) => {
+                log::error!("OCR of pdf {pdf_id} failed: {error}");
+                app_handle
+                    .emit("ocr-failed", OcrFailed { pdf_id, error })
+                    .unwrap();
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn cancel_ocr(app_handle: AppHandle, pdf_id: u64) -> Result<bool, String> {
+    log::info!("Cancelling OCR of pdf {pdf_id}");
+
+    let state = app_handle.state::<AppState>();
+    let jobs = state.ocr_jobs.lock().unwrap();
+
+    match jobs.get(&pdf_id) {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::Relaxed);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
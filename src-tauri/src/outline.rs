@@ -0,0 +1,108 @@
+use std::fs;
+
+use pdfium_render::prelude::*;
+use tauri::{AppHandle, Manager};
+
+use crate::error::AppError;
+use crate::pdf::{find_pdf_entry, get_bookmarks_path, load_bookmarks_from_file, sort_bookmarks};
+use crate::state::AppState;
+
+// Malformed outlines can point back at their own ancestors, these bound the walk
+const MAX_OUTLINE_DEPTH: usize = 32;
+const MAX_OUTLINE_ITEMS: usize = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct OutlineItem {
+    pub(crate) title: String,
+    // 1-based, None when the entry doesn't point at a page of this document
+    pub(crate) page: Option<u32>,
+    pub(crate) depth: usize,
+}
+
+fn collect_outline(first: Option<PdfBookmark>, depth: usize, items: &mut Vec<OutlineItem>) {
+    let mut current = first;
+    while let Some(bookmark) = current {
+        if depth >= MAX_OUTLINE_DEPTH || items.len() >= MAX_OUTLINE_ITEMS {
+            return;
+        }
+
+        let page = bookmark
+            .destination()
+            .and_then(|destination| destination.page_index().ok())
+            .map(|index| u32::from(index) + 1);
+
+        items.push(OutlineItem {
+            title: bookmark.title().unwrap_or_default(),
+            page,
+            depth,
+        });
+
+        collect_outline(bookmark.first_child(), depth + 1, items);
+        current = bookmark.next_sibling();
+    }
+}
+
+// The document's own table of contents, in reading order
+pub(crate) fn read_outline(document: &PdfDocument) -> Vec<OutlineItem> {
+    let mut items = Vec::new();
+    collect_outline(document.bookmarks().root(), 0, &mut items);
+    items
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+}
+
+fn outline_to_markdown(title: &str, items: &[OutlineItem]) -> String {
+    let mut markdown = format!("# {}\n\n", escape_markdown(title));
+    for item in items {
+        let indent = "  ".repeat(item.depth);
+        let label = escape_markdown(item.title.trim());
+        match item.page {
+            Some(page) => markdown.push_str(&format!("{indent}- [{label}](page:{page})\n")),
+            None => markdown.push_str(&format!("{indent}- {label}\n")),
+        }
+    }
+    markdown
+}
+
+#[tauri::command]
+pub fn export_outline_markdown(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    output_path: String,
+) -> Result<usize, AppError> {
+    log::info!("Exporting outline of pdf {pdf_id} to {output_path}");
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+
+    let pdf_entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+    let pdfium = app_handle.state::<AppState>().pdfium()?;
+    let document = pdfium.load_pdf_from_file(&pdf_entry.clone_path, None)?;
+
+    let mut items = read_outline(&document);
+
+    // no embedded table of contents, the user's bookmarks are the next best structure
+    if items.is_empty() {
+        let mut bookmarks =
+            load_bookmarks_from_file(&app_handle, &get_bookmarks_path(&app_handle, pdf_id)?)?;
+        sort_bookmarks(&mut bookmarks);
+        items = bookmarks
+            .into_iter()
+            .map(|bm| OutlineItem {
+                title: bm.label,
+                page: Some(bm.page_number),
+                depth: 0,
+            })
+            .collect();
+    }
+
+    fs::write(
+        &output_path,
+        outline_to_markdown(&pdf_entry.file_name, &items),
+    )?;
+
+    Ok(items.len())
+}
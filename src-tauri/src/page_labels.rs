@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tauri::{AppHandle, Manager};
+
+use crate::error::AppError;
+use crate::pdf::{find_pdf_entry, pdf_page_count};
+use crate::persistence::{write_json, FileKind};
+use crate::state::AppState;
+
+const MAX_LABEL_LEN: usize = 32;
+
+// Page number to the label shown for it, e.g. 5 -> "iii". Pages without one show their number.
+pub type PageLabels = BTreeMap<u32, String>;
+
+pub(crate) fn page_labels_path(app_data_dir: &Path, pdf_id: u64) -> PathBuf {
+    app_data_dir.join(format!("pdf_{pdf_id}/page_labels.json"))
+}
+
+pub(crate) fn load_page_labels(path: &Path) -> Result<Option<PageLabels>, AppError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let data = fs::read_to_string(path)?;
+    if data.trim().is_empty() {
+        return Ok(Some(PageLabels::new()));
+    }
+
+    Ok(Some(serde_json::from_str::<PageLabels>(&data)?))
+}
+
+// Labels from the pdf's own /PageLabels, skipping pages labelled with their plain number
+fn labels_from_pdf(app_handle: &AppHandle, pdf_id: u64) -> Result<PageLabels, AppError> {
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    let pdf_entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+    let pdfium = app_handle.state::<AppState>().pdfium()?;
+    let document = pdfium.load_pdf_from_file(&pdf_entry.clone_path, None)?;
+
+    let mut labels = PageLabels::new();
+    for (index, page) in document.pages().iter().enumerate() {
+        let page_no = index as u32 + 1;
+        if let Some(label) = page.label() {
+            let label = label.trim();
+            if !label.is_empty() && label != page_no.to_string() {
+                labels.insert(page_no, label.to_string());
+            }
+        }
+    }
+
+    Ok(labels)
+}
+
+// Labels of a pdf, seeded from the pdf itself the first time. Expects the pdf lock to be held.
+pub(crate) fn current_page_labels(
+    app_handle: &AppHandle,
+    pdf_id: u64,
+    path: &Path,
+) -> Result<PageLabels, AppError> {
+    if let Some(labels) = load_page_labels(path)? {
+        return Ok(labels);
+    }
+
+    let labels = labels_from_pdf(app_handle, pdf_id)?;
+    if !labels.is_empty() {
+        log::info!("Seeded {} page label(s) of pdf {pdf_id}", labels.len());
+    }
+    // written even when empty, so documents without labels aren't read again
+    write_json(app_handle, path, &labels, FileKind::PageLabels)?;
+    Ok(labels)
+}
+
+// Page labels of a pdf. Until one is set, they come from the pdf's own page labels (e.g. roman
+// numerals for front matter), or none when it has no such dictionary.
+#[tauri::command]
+pub fn get_page_labels(app_handle: AppHandle, pdf_id: u64) -> Result<PageLabels, AppError> {
+    log::info!("Loading page labels of pdf {pdf_id}");
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    let path = page_labels_path(&app_data_dir, pdf_id);
+
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    current_page_labels(&app_handle, pdf_id, &path)
+}
+
+// Sets the label of a page, an empty or missing `label` goes back to the page number.
+// Returns every label of the pdf.
+#[tauri::command]
+pub fn set_page_label(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    page_number: u32,
+    label: Option<String>,
+) -> Result<PageLabels, AppError> {
+    log::info!("Setting label of page {page_number} of pdf {pdf_id}");
+
+    let label = label
+        .map(|label| label.trim().to_string())
+        .filter(|label| !label.is_empty());
+    if label
+        .as_ref()
+        .is_some_and(|label| label.chars().count() > MAX_LABEL_LEN)
+    {
+        return Err(AppError::InvalidInput(format!(
+            "Page labels can be at most {MAX_LABEL_LEN} characters"
+        )));
+    }
+
+    let page_count = pdf_page_count(&app_handle, pdf_id)?;
+    if page_number == 0 || page_number > page_count {
+        return Err(AppError::InvalidInput(format!(
+            "Page {page_number} is out of range (1-{page_count})"
+        )));
+    }
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    let path = page_labels_path(&app_data_dir, pdf_id);
+
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    let mut labels = current_page_labels(&app_handle, pdf_id, &path)?;
+    match label {
+        Some(label) => labels.insert(page_number, label),
+        None => labels.remove(&page_number),
+    };
+
+    write_json(&app_handle, &path, &labels, FileKind::PageLabels)?;
+    Ok(labels)
+}
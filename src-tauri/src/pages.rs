@@ -0,0 +1,808 @@
+use std::collections::HashMap;
+use std::fs;
+use std::os::raw::{c_int, c_ulong};
+use std::path::{Path, PathBuf};
+
+use pdfium_render::prelude::*;
+use tauri::{AppHandle, Manager};
+
+use crate::encryption::{carry_encryption, file_key, shared_encryption};
+use crate::page_labels::{current_page_labels, page_labels_path, PageLabels};
+use crate::pdf::{
+    find_pdf_entry, get_bookmarks_path, hash_file, load_bookmarks_from_file,
+    load_strokes_from_file, pdf_page_count, rebuild_derived_data, register_generated_pdf,
+    remove_pdf, save_bookmarks_to_file, sort_bookmarks, update_pdf_entry, PdfBookmark,
+    PdfBookmarks, PdfStrokes,
+};
+use crate::persistence::{write_json, FileKind};
+use crate::profiles::stored_profile_files;
+use crate::starred::{load_starred, starred_path, StarredPages};
+use crate::state::AppState;
+use crate::stroke_queue::flush_pending_strokes;
+use crate::study_sets::{
+    read_study_sets, remap_study_set_pages, study_sets_path, write_study_sets, StudySetsFile,
+};
+use crate::template::{insert_template_page, PageTemplate};
+use crate::timestamp::file_timestamp;
+
+// Page numbers are 1-based everywhere below, matching stroke keys and bookmarks.
+// A remap takes an old page number to its new one, None when the page no longer exists.
+
+// Inserting `count` pages after `after_page` (0 = before the first page): pages up to and
+// including `after_page` keep their number, every later page moves up by `count`.
+pub(crate) fn remap_for_insert(after_page: u32, count: u32) -> impl Fn(u32) -> Option<u32> {
+    move |page| {
+        Some(if page > after_page {
+            page + count
+        } else {
+            page
+        })
+    }
+}
+
+// Deleting pages drops them and closes the gaps: a surviving page moves down by the number
+// of deleted pages before it. `binary_search` on the sorted list yields that count directly.
+pub(crate) fn remap_for_delete(deleted: &[u32]) -> impl Fn(u32) -> Option<u32> {
+    let mut deleted = deleted.to_vec();
+    deleted.sort_unstable();
+    deleted.dedup();
+
+    move |page| match deleted.binary_search(&page) {
+        Ok(_) => None,
+        Err(deleted_before) => Some(page - deleted_before as u32),
+    }
+}
+
+// `new_order[i]` is the old number of the page that ends up at position i + 1
+pub(crate) fn remap_for_reorder(new_order: &[u32]) -> impl Fn(u32) -> Option<u32> {
+    let mut positions = HashMap::new();
+    for (index, old_page) in new_order.iter().enumerate() {
+        positions.insert(*old_page, index as u32 + 1);
+    }
+
+    move |page| positions.get(&page).copied()
+}
+
+// Extracting pages `start..=end` into their own document makes `start` page 1
+pub(crate) fn remap_for_range(start: u32, end: u32) -> impl Fn(u32) -> Option<u32> {
+    move |page| (start..=end).contains(&page).then(|| page - start + 1)
+}
+
+pub(crate) fn remap_strokes(strokes: PdfStrokes, remap: impl Fn(u32) -> Option<u32>) -> PdfStrokes {
+    let inner: HashMap<u32, _> = strokes
+        .inner
+        .into_iter()
+        .filter_map(|(page, page_strokes)| remap(page).map(|new_page| (new_page, page_strokes)))
+        .collect();
+
+    PdfStrokes { inner }
+}
+
+// A range bookmark keeps the span of its surviving pages, so it grows when pages are inserted
+// inside it, shrinks when some of its pages are deleted and is dropped once none are left.
+// After a reorder it spans from the lowest to the highest new position of its pages.
+pub(crate) fn remap_bookmarks(
+    bookmarks: PdfBookmarks,
+    remap: impl Fn(u32) -> Option<u32>,
+) -> PdfBookmarks {
+    let mut remapped: PdfBookmarks = bookmarks
+        .into_iter()
+        .filter_map(|bm| {
+            let last_page = bm.end_page.unwrap_or(bm.page_number);
+            let surviving = (bm.page_number..=last_page).filter_map(&remap);
+            let (first, last) = surviving.fold(None, |span, page| match span {
+                None => Some((page, page)),
+                Some((first, last)) => Some((page.min(first), page.max(last))),
+            })?;
+
+            Some(PdfBookmark {
+                page_number: first,
+                end_page: (last != first).then_some(last),
+                ..bm
+            })
+        })
+        .collect();
+
+    sort_bookmarks(&mut remapped);
+    remapped
+}
+
+// A sidecar with its pages already moved, held in memory until every one of them could be
+// computed so that a failure part way through changes nothing on disk
+enum Remapped {
+    Strokes(PdfStrokes),
+    Bookmarks(PdfBookmarks),
+    Starred(StarredPages),
+    Labels(PageLabels),
+    StudySets(StudySetsFile),
+}
+
+impl Remapped {
+    fn write(&self, app_handle: &AppHandle, path: &Path) -> Result<(), String> {
+        match self {
+            Remapped::Strokes(strokes) => write_json(app_handle, path, strokes, FileKind::Strokes),
+            Remapped::Bookmarks(bookmarks) => {
+                save_bookmarks_to_file(app_handle, &path.to_path_buf(), bookmarks)
+            }
+            Remapped::Starred(starred) => write_json(app_handle, path, starred, FileKind::Starred),
+            Remapped::Labels(labels) => write_json(app_handle, path, labels, FileKind::PageLabels),
+            Remapped::StudySets(data) => write_study_sets(app_handle, path, data),
+        }
+    }
+}
+
+// Writes every remapped sidecar, putting back what each held before if any write fails.
+// Returns the original contents so a later failure can restore them as well.
+fn write_remapped(
+    app_handle: &AppHandle,
+    remapped: &[(PathBuf, Remapped)],
+) -> Result<Vec<(PathBuf, Option<Vec<u8>>)>, String> {
+    let mut originals = Vec::new();
+    let written = remapped.iter().try_for_each(|(path, file)| {
+        let original = if path.exists() {
+            Some(fs::read(path).map_err(|e| e.to_string())?)
+        } else {
+            None
+        };
+        originals.push((path.clone(), original));
+        file.write(app_handle, path)
+    });
+
+    if let Err(e) = written {
+        restore_sidecars(&originals);
+        return Err(e);
+    }
+    Ok(originals)
+}
+
+// Puts back the sidecars `write_remapped` replaced, removing the ones it created
+fn restore_sidecars(originals: &[(PathBuf, Option<Vec<u8>>)]) {
+    for (path, original) in originals {
+        let restored = match original {
+            Some(bytes) => fs::write(path, bytes),
+            None if path.exists() => fs::remove_file(path),
+            None => Ok(()),
+        };
+        if let Err(e) = restored {
+            log::error!("Failed to restore {}: {e}", path.display());
+        }
+    }
+}
+
+// Replaces the clone with what `write` saves to the path it's given, moving strokes, bookmarks
+// and everything else keyed by page along with their pages, then regenerates what's derived
+// from the page layout. `write` gets the clone path and the output path and returns the new
+// page count. The remapped files are all computed before anything is written and the new pdf
+// is swapped in last, so a failure leaves the pdf and its sidecars as they were.
+pub(crate) fn rewrite_pages<W, R>(
+    app_handle: &AppHandle,
+    pdf_id: u64,
+    write: W,
+    remap: R,
+) -> Result<u32, String>
+where
+    W: FnOnce(&Pdfium, &str, &str) -> Result<u32, String>,
+    R: Fn(u32) -> Option<u32>,
+{
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let pdf_entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+    let folder_path = app_data_dir.join(format!("pdf_{pdf_id}"));
+
+    // queued strokes have to be on disk to move with their pages
+    flush_pending_strokes(app_handle, pdf_id)?;
+
+    let state = app_handle.state::<AppState>();
+    let pdfium = state.pdfium()?;
+    let lock = state.pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    // encrypted notes have to be readable before their pages are moved
+    file_key(app_handle, &folder_path.join("strokes.json"))?;
+
+    let mut remapped = Vec::new();
+
+    let strokes_path = folder_path.join("strokes.json");
+    if strokes_path.exists() {
+        let strokes = remap_strokes(load_strokes_from_file(app_handle, &strokes_path)?, &remap);
+        remapped.push((strokes_path, Remapped::Strokes(strokes)));
+    }
+
+    let bookmarks_path = get_bookmarks_path(app_handle, pdf_id)?;
+    if bookmarks_path.exists() {
+        let bookmarks = remap_bookmarks(
+            load_bookmarks_from_file(app_handle, &bookmarks_path)?,
+            &remap,
+        );
+        remapped.push((bookmarks_path, Remapped::Bookmarks(bookmarks)));
+    }
+
+    // annotations of the profiles that aren't active follow the same pages
+    for (path, is_strokes) in stored_profile_files(&folder_path)? {
+        let file = if is_strokes {
+            Remapped::Strokes(remap_strokes(
+                load_strokes_from_file(app_handle, &path)?,
+                &remap,
+            ))
+        } else {
+            Remapped::Bookmarks(remap_bookmarks(
+                load_bookmarks_from_file(app_handle, &path)?,
+                &remap,
+            ))
+        };
+        remapped.push((path, file));
+    }
+
+    let starred_path = starred_path(app_handle, pdf_id)?;
+    if starred_path.exists() {
+        let starred: StarredPages = load_starred(&starred_path)?
+            .into_iter()
+            .filter_map(&remap)
+            .collect();
+        remapped.push((starred_path, Remapped::Starred(starred)));
+    }
+
+    // labels still only in the pdf's own dictionary are numbered by position, so they're
+    // seeded from the clone before it's replaced and then follow the pages like the user's
+    let labels_path = page_labels_path(&app_data_dir, pdf_id);
+    let labels = current_page_labels(app_handle, pdf_id, &labels_path)?;
+    if !labels.is_empty() {
+        let labels: PageLabels = labels
+            .into_iter()
+            .filter_map(|(page, label)| remap(page).map(|page| (page, label)))
+            .collect();
+        remapped.push((labels_path, Remapped::Labels(labels)));
+    }
+
+    // held until the sidecars are written so no other change to the sets is lost
+    let study_sets_path = study_sets_path(app_handle)?;
+    let study_sets_guard = state.study_sets_lock.lock().unwrap();
+    let mut study_sets = read_study_sets(&study_sets_path)?;
+    if remap_study_set_pages(&mut study_sets, pdf_id, &remap) {
+        remapped.push((study_sets_path, Remapped::StudySets(study_sets)));
+    }
+
+    // written next to the clone and swapped in, pdfium reads the source lazily while open
+    let part_path = format!("{}.part", pdf_entry.clone_path);
+    let page_count = match write(&pdfium, &pdf_entry.clone_path, &part_path) {
+        Ok(page_count) => page_count,
+        Err(e) => {
+            let _ = fs::remove_file(&part_path);
+            return Err(e);
+        }
+    };
+
+    let originals = match write_remapped(app_handle, &remapped) {
+        Ok(originals) => originals,
+        Err(e) => {
+            let _ = fs::remove_file(&part_path);
+            return Err(e);
+        }
+    };
+
+    if let Err(e) = fs::rename(&part_path, &pdf_entry.clone_path) {
+        restore_sidecars(&originals);
+        let _ = fs::remove_file(&part_path);
+        return Err(e.to_string());
+    }
+    drop(study_sets_guard);
+
+    // the clone no longer matches what was imported, keep duplicate detection honest
+    let content_hash = hash_file(Path::new(&pdf_entry.clone_path))?;
+    let pdf_entry = update_pdf_entry(app_handle, pdf_id, |entry| {
+        entry.clone_hash = Some(content_hash.clone());
+        entry.content_hash = Some(content_hash);
+        entry.page_count = Some(page_count);
+        // a picked cover page follows its page, or falls back to the first one
+        entry.cover_page = entry.cover_page.and_then(&remap);
+    })?;
+
+    rebuild_derived_data(app_handle, &pdf_entry, &folder_path)?;
+
+    Ok(page_count)
+}
+
+// Edits the clone in place with `edit`, see `rewrite_pages`
+pub(crate) fn modify_pages<E, R>(
+    app_handle: &AppHandle,
+    pdf_id: u64,
+    edit: E,
+    remap: R,
+) -> Result<u32, String>
+where
+    E: FnOnce(&mut PdfDocument) -> Result<(), String>,
+    R: Fn(u32) -> Option<u32>,
+{
+    rewrite_pages(
+        app_handle,
+        pdf_id,
+        |pdfium, clone_path, output_path| {
+            let mut document = pdfium
+                .load_pdf_from_file(clone_path, None)
+                .map_err(|e| e.to_string())?;
+
+            edit(&mut document)?;
+
+            document
+                .save_to_file(output_path)
+                .map_err(|e| e.to_string())?;
+            Ok(document.pages().len() as u32)
+        },
+        remap,
+    )
+}
+
+// Size of the 1-based `page`, new blank pages match their neighbour
+fn page_size(document: &PdfDocument, page: u32) -> Result<(f32, f32), String> {
+    let page = document
+        .pages()
+        .get(page.saturating_sub(1) as PdfPageIndex)
+        .map_err(|e| e.to_string())?;
+
+    Ok((page.width().value, page.height().value))
+}
+
+fn insert_blank_pages(
+    document: &mut PdfDocument,
+    after_page: u32,
+    count: u32,
+    template: PageTemplate,
+) -> Result<(), String> {
+    let page_count = document.pages().len() as u32;
+    if after_page > page_count {
+        return Err(format!(
+            "Page {after_page} is out of range (document has {page_count} pages)"
+        ));
+    }
+    if page_count + count > PdfPageIndex::MAX as u32 {
+        return Err("Too many pages".to_string());
+    }
+
+    let (width, height) = page_size(document, after_page.max(1))?;
+    for offset in 0..count {
+        // `after_page` as a 0-based index is the slot right after that 1-based page
+        let index = (after_page + offset) as PdfPageIndex;
+        insert_template_page(document, index, width, height, template)?;
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn append_blank_pages(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    count: u32,
+    template: PageTemplate,
+) -> Result<u32, String> {
+    log::info!("Appending {count} {template} pages to pdf {pdf_id}");
+
+    if count == 0 {
+        return Err("Page count must be at least 1".to_string());
+    }
+
+    // appended pages come after every existing page, nothing is renumbered
+    modify_pages(
+        &app_handle,
+        pdf_id,
+        |document| {
+            let page_count = document.pages().len() as u32;
+            insert_blank_pages(document, page_count, count, template)
+        },
+        Some,
+    )
+}
+
+#[tauri::command]
+pub fn insert_blank_page(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    after_page: u32,
+    template: PageTemplate,
+) -> Result<u32, String> {
+    log::info!("Inserting a {template} page after page {after_page} of pdf {pdf_id}");
+
+    modify_pages(
+        &app_handle,
+        pdf_id,
+        |document| insert_blank_pages(document, after_page, 1, template),
+        remap_for_insert(after_page, 1),
+    )
+}
+
+fn delete_document_pages(document: &mut PdfDocument, pages: &[u32]) -> Result<(), String> {
+    let page_count = document.pages().len() as u32;
+
+    let mut pages = pages.to_vec();
+    pages.sort_unstable();
+    pages.dedup();
+
+    if let Some(page) = pages.iter().find(|p| **p == 0 || **p > page_count) {
+        return Err(format!(
+            "Page {page} is out of range (document has {page_count} pages)"
+        ));
+    }
+    if pages.len() as u32 == page_count {
+        return Err("Cannot delete every page of a document".to_string());
+    }
+
+    // last to first, so the indices of pages still to delete stay valid
+    for page in pages.iter().rev() {
+        document
+            .pages()
+            .get((page - 1) as PdfPageIndex)
+            .map_err(|e| e.to_string())?
+            .delete()
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+// Keeps the strokes of pages about to be deleted next to the live strokes file
+fn backup_deleted_strokes(
+    app_handle: &AppHandle,
+    pdf_id: u64,
+    pages: &[u32],
+) -> Result<(), String> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    flush_pending_strokes(app_handle, pdf_id)?;
+
+    let folder_path = app_data_dir.join(format!("pdf_{pdf_id}"));
+    let strokes_path = folder_path.join("strokes.json");
+    if !strokes_path.exists() {
+        return Ok(());
+    }
+
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    let strokes = load_strokes_from_file(app_handle, &strokes_path)?;
+    let inner: HashMap<u32, _> = strokes
+        .inner
+        .into_iter()
+        .filter(|(page, _)| pages.contains(page))
+        .collect();
+    if inner.is_empty() {
+        return Ok(());
+    }
+
+    let timestamp = file_timestamp();
+    let backup_path = folder_path.join(format!("strokes_deleted_{timestamp}.json"));
+    write_json(
+        app_handle,
+        &backup_path,
+        &PdfStrokes { inner },
+        FileKind::Strokes,
+    )
+}
+
+#[tauri::command]
+pub fn delete_pages(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    pages: Vec<u32>,
+    backup_strokes: Option<bool>,
+) -> Result<u32, String> {
+    log::info!("Deleting pages {pages:?} of pdf {pdf_id}");
+
+    if pages.is_empty() {
+        return Err("No pages to delete".to_string());
+    }
+
+    if backup_strokes.unwrap_or(false) {
+        backup_deleted_strokes(&app_handle, pdf_id, &pages)?;
+    }
+
+    modify_pages(
+        &app_handle,
+        pdf_id,
+        |document| delete_document_pages(document, &pages),
+        remap_for_delete(&pages),
+    )
+}
+
+// Moves the pages in place rather than copying them into a new document, which would drop
+// the outline and everything else that belongs to the document rather than its pages
+fn move_document_pages(document: &mut PdfDocument, new_order: &[u32]) -> Result<(), String> {
+    let page_count = document.pages().len() as u32;
+
+    let mut sorted = new_order.to_vec();
+    sorted.sort_unstable();
+    if !sorted.iter().copied().eq(1..=page_count) {
+        return Err(format!(
+            "New order must list every page from 1 to {page_count} exactly once"
+        ));
+    }
+
+    // listing every page moves them to the front in the given order
+    let indices: Vec<c_int> = new_order.iter().map(|page| (page - 1) as c_int).collect();
+    let bindings = document.bindings();
+    let moved = bindings.FPDF_MovePages(
+        bindings.get_handle_from_document(document),
+        indices.as_ptr(),
+        indices.len() as c_ulong,
+        0,
+    );
+    if !bindings.is_true(moved) {
+        return Err("Failed to reorder pages".to_string());
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn reorder_pages(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    new_order: Vec<u32>,
+) -> Result<u32, String> {
+    log::info!("Reordering pages of pdf {pdf_id}");
+
+    modify_pages(
+        &app_handle,
+        pdf_id,
+        |document| move_document_pages(document, &new_order),
+        remap_for_reorder(&new_order),
+    )
+}
+
+// Strokes and bookmarks of a source pdf, rebased with `remap`, for carrying into a new document
+fn rebased_annotations(
+    app_handle: &AppHandle,
+    app_data_dir: &Path,
+    pdf_id: u64,
+    remap: impl Fn(u32) -> Option<u32>,
+) -> Result<(PdfStrokes, PdfBookmarks), String> {
+    flush_pending_strokes(app_handle, pdf_id)?;
+
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    let strokes_path = app_data_dir.join(format!("pdf_{pdf_id}/strokes.json"));
+    let strokes = remap_strokes(load_strokes_from_file(app_handle, &strokes_path)?, &remap);
+
+    let bookmarks_path = get_bookmarks_path(app_handle, pdf_id)?;
+    let bookmarks = remap_bookmarks(
+        load_bookmarks_from_file(app_handle, &bookmarks_path)?,
+        &remap,
+    );
+
+    Ok((strokes, bookmarks))
+}
+
+fn write_annotations(
+    app_handle: &AppHandle,
+    app_data_dir: &Path,
+    pdf_id: u64,
+    strokes: &PdfStrokes,
+    bookmarks: &PdfBookmarks,
+) -> Result<(), String> {
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    if !strokes.inner.is_empty() {
+        let strokes_path = app_data_dir.join(format!("pdf_{pdf_id}/strokes.json"));
+        write_json(app_handle, &strokes_path, strokes, FileKind::Strokes)?;
+    }
+    if !bookmarks.is_empty() {
+        let bookmarks_path = get_bookmarks_path(app_handle, pdf_id)?;
+        save_bookmarks_to_file(app_handle, &bookmarks_path, bookmarks)?;
+    }
+
+    Ok(())
+}
+
+// Removes a pdf a merge or split registered when its annotations couldn't be carried over,
+// so the failed command leaves nothing half made in the library
+fn discard_generated_pdf(app_handle: &AppHandle, pdf_id: u64) {
+    if let Err(e) = remove_pdf(app_handle.clone(), pdf_id, Some(false)) {
+        log::error!("Failed to remove generated pdf {pdf_id}: {e}");
+    }
+}
+
+fn pdf_file_name(name: &str) -> String {
+    let name = name.trim();
+    if name.to_lowercase().ends_with(".pdf") {
+        name.to_string()
+    } else {
+        format!("{name}.pdf")
+    }
+}
+
+#[tauri::command]
+pub fn merge_pdfs(
+    app_handle: AppHandle,
+    pdf_ids: Vec<u64>,
+    name: String,
+    include_annotations: Option<bool>,
+) -> Result<u64, String> {
+    log::info!("Merging pdfs {pdf_ids:?} into {name}");
+
+    if pdf_ids.len() < 2 {
+        return Err("Select at least two PDFs to merge".to_string());
+    }
+    if name.trim().is_empty() {
+        return Err("Name cannot be empty".to_string());
+    }
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let sources = pdf_ids
+        .iter()
+        .map(|id| find_pdf_entry(&app_data_dir, *id))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let include_annotations = include_annotations.unwrap_or(true);
+    // notes of encrypted sources stay encrypted in the merged pdf
+    let encryption = if include_annotations {
+        shared_encryption(&app_handle, &pdf_ids)?
+    } else {
+        None
+    };
+    // read before the merged pdf is registered, a source that can't be read fails the merge
+    // without leaving it behind
+    let source_annotations = if include_annotations {
+        sources
+            .iter()
+            .map(|source| rebased_annotations(&app_handle, &app_data_dir, source.id, Some))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        Vec::new()
+    };
+
+    // pages of every source in order, each source's page 1 lands right after the previous one
+    let mut page_offsets = Vec::with_capacity(sources.len());
+    let entry = register_generated_pdf(&app_handle, pdf_file_name(&name), |pdfium, clone_path| {
+        let mut document = pdfium.create_new_pdf().map_err(|e| e.to_string())?;
+        for source in &sources {
+            let source_document = pdfium
+                .load_pdf_from_file(&source.clone_path, None)
+                .map_err(|e| e.to_string())?;
+
+            page_offsets.push(document.pages().len() as u32);
+            document
+                .pages_mut()
+                .append(&source_document)
+                .map_err(|e| e.to_string())?;
+        }
+        document.save_to_file(clone_path).map_err(|e| e.to_string())
+    })?;
+
+    if include_annotations {
+        let mut strokes = PdfStrokes::new();
+        let mut bookmarks = PdfBookmarks::new();
+
+        for ((source_strokes, source_bookmarks), offset) in
+            source_annotations.into_iter().zip(page_offsets)
+        {
+            let rebase = |page: u32| Some(page + offset);
+            strokes
+                .inner
+                .extend(remap_strokes(source_strokes, rebase).inner);
+            bookmarks.extend(remap_bookmarks(source_bookmarks, rebase));
+        }
+
+        let written = (|| -> Result<(), String> {
+            if let Some(source_id) = encryption {
+                carry_encryption(&app_handle, source_id, entry.id)?;
+            }
+            write_annotations(&app_handle, &app_data_dir, entry.id, &strokes, &bookmarks)
+        })();
+        if let Err(e) = written {
+            discard_generated_pdf(&app_handle, entry.id);
+            return Err(e);
+        }
+    }
+
+    Ok(entry.id)
+}
+
+#[tauri::command]
+pub fn split_pdf(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    ranges: Vec<(u32, u32)>,
+    allow_overlap: Option<bool>,
+) -> Result<Vec<u64>, String> {
+    log::info!("Splitting pdf {pdf_id} into {ranges:?}");
+
+    if ranges.is_empty() {
+        return Err("No page ranges to split".to_string());
+    }
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let source = find_pdf_entry(&app_data_dir, pdf_id)?;
+    let page_count = pdf_page_count(&app_handle, pdf_id)?;
+
+    for (start, end) in &ranges {
+        if *start == 0 || start > end || *end > page_count {
+            return Err(format!(
+                "Range {start}-{end} is out of bounds (document has {page_count} pages)"
+            ));
+        }
+    }
+
+    if !allow_overlap.unwrap_or(false) {
+        let mut sorted = ranges.clone();
+        sorted.sort_unstable();
+        if let Some(pair) = sorted.windows(2).find(|pair| pair[1].0 <= pair[0].1) {
+            return Err(format!(
+                "Ranges {}-{} and {}-{} overlap",
+                pair[0].0, pair[0].1, pair[1].0, pair[1].1
+            ));
+        }
+    }
+
+    let stem = Path::new(&source.file_name)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("Document")
+        .to_string();
+
+    // the parts keep the source's encryption
+    let encryption = shared_encryption(&app_handle, &[pdf_id])?;
+    // every part's annotations are read before the first part is registered
+    let part_annotations = ranges
+        .iter()
+        .map(|(start, end)| {
+            rebased_annotations(
+                &app_handle,
+                &app_data_dir,
+                pdf_id,
+                remap_for_range(*start, *end),
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut new_ids = Vec::with_capacity(ranges.len());
+    for ((start, end), (strokes, bookmarks)) in ranges.into_iter().zip(part_annotations) {
+        let file_name = format!("{stem} (pages {start}-{end}).pdf");
+        let registered = register_generated_pdf(&app_handle, file_name, |pdfium, clone_path| {
+            let source_document = pdfium
+                .load_pdf_from_file(&source.clone_path, None)
+                .map_err(|e| e.to_string())?;
+
+            let mut document = pdfium.create_new_pdf().map_err(|e| e.to_string())?;
+            document
+                .pages_mut()
+                .copy_page_range_from_document(
+                    &source_document,
+                    (start - 1) as PdfPageIndex..=(end - 1) as PdfPageIndex,
+                    0,
+                )
+                .map_err(|e| e.to_string())?;
+            document.save_to_file(clone_path).map_err(|e| e.to_string())
+        });
+
+        let written = registered.and_then(|entry| {
+            new_ids.push(entry.id);
+            if let Some(source_id) = encryption {
+                carry_encryption(&app_handle, source_id, entry.id)?;
+            }
+            write_annotations(&app_handle, &app_data_dir, entry.id, &strokes, &bookmarks)
+        });
+        // a split either makes every part or none of them
+        if let Err(e) = written {
+            for id in new_ids {
+                discard_generated_pdf(&app_handle, id);
+            }
+            return Err(e);
+        }
+    }
+
+    Ok(new_ids)
+}
@@ -1,13 +1,32 @@
-use crate::state::AppState;
-use chrono::Local;
-use pdfium_render::prelude::Pdfium;
+use crate::archive::{archive_annotations, has_archived_annotations};
+use crate::collections::{sort_pdfs, SortBy};
+use crate::color::normalize_color;
+use crate::error::AppError;
+use crate::export::export_file_name;
+use crate::health::clamp_bookmark_pages;
+use crate::persistence::{read_data_file, write_json, FileKind, FlushPolicy, ThrottledFlush};
+use crate::profiles::load_profiles;
+use crate::render::pixels_at_dpi;
+use crate::settings::{patch_settings, AppSettings, AppSettingsPatch};
+use crate::state::{AppState, SemaphorePermit};
+use crate::storage::{ensure_space_for_import, forget_disk_usage};
+use crate::stroke_queue::flush_pending_strokes;
+use crate::timestamp::file_timestamp;
+use chrono::{DateTime, Local};
+use image::{imageops, DynamicImage, Rgb, RgbImage};
+use pdfium_render::prelude::{PdfPageIndex, Pdfium};
 use serde::de::{self, Deserializer};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::io::Read;
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use std::{collections::HashMap, fs, path::Path, process::Command};
 use tauri::{AppHandle, Emitter, Manager};
 
-fn open_folder(path: &std::path::Path) -> Result<(), String> {
+// Opens a folder or file with the platform's default handler
+fn open_path(path: &std::path::Path) -> Result<(), String> {
     if cfg!(target_os = "windows") {
         Command::new("explorer")
             .arg(path)
@@ -29,11 +48,52 @@ fn open_folder(path: &std::path::Path) -> Result<(), String> {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PdfEntry {
-    id: u64,
-    original_path: String,
-    clone_path: String,
-    cover_path: String,
-    file_name: String,
+    pub(crate) id: u64,
+    pub(crate) original_path: String,
+    pub(crate) clone_path: String,
+    pub(crate) cover_path: String,
+    pub(crate) file_name: String,
+    // sha256 of the file contents at registration, None for entries registered before hashing
+    #[serde(default)]
+    pub(crate) content_hash: Option<String>,
+    // RFC 3339 time of import, independent of the sequential id
+    #[serde(default)]
+    pub(crate) created_at: Option<String>,
+    // sha256 and page count of the clone as last written by the app, checked by
+    // verify_pdf_integrity. None for entries registered before clones were checksummed
+    #[serde(default)]
+    pub(crate) clone_hash: Option<String>,
+    #[serde(default)]
+    pub(crate) page_count: Option<u32>,
+    // the cover is an image the user supplied, regenerating derived data keeps it
+    #[serde(default)]
+    pub(crate) custom_cover: bool,
+    // page the cover was rendered from when the user picked one, page edits move it along.
+    // None renders the first page.
+    #[serde(default)]
+    pub(crate) cover_page: Option<u32>,
+    // file name used when exporting, derived from the display name on request; None falls
+    // back to the display name. The clone on disk is always {id}.pdf
+    #[serde(default)]
+    pub(crate) export_name: Option<String>,
+    // modification time of the original (ms since epoch) when it was last copied or linked,
+    // None for entries registered before it was recorded
+    #[serde(default)]
+    pub(crate) original_mtime: Option<i64>,
+    // perceptual hash of the cover, see phash.rs
+    #[serde(default)]
+    pub(crate) cover_phash: Option<CoverHash>,
+    // free-form labels for organizing, see tags.rs
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
+}
+
+// Valid while the cover is still the file it was computed from. Kept by file name, which is
+// unique per rendered or imported cover and survives the folder being renumbered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoverHash {
+    pub(crate) cover_file: String,
+    pub(crate) hash: String,
 }
 
 impl PdfEntry {
@@ -43,6 +103,8 @@ impl PdfEntry {
         clone_path: String,
         cover_path: String,
         file_name: String,
+        content_hash: Option<String>,
+        created_at: Option<String>,
     ) -> Self {
         Self {
             id,
@@ -50,8 +112,42 @@ impl PdfEntry {
             clone_path,
             cover_path,
             file_name,
+            content_hash,
+            created_at,
+            clone_hash: None,
+            page_count: None,
+            custom_cover: false,
+            cover_page: None,
+            export_name: None,
+            original_mtime: None,
+            cover_phash: None,
+            tags: Vec::new(),
+        }
+    }
+
+    // Name exported copies of this document get, safe to use as a file name
+    pub(crate) fn export_file_name(&self) -> String {
+        match &self.export_name {
+            Some(export_name) => export_name.clone(),
+            None => export_file_name(&self.file_name),
+        }
+    }
+}
+
+pub(crate) fn hash_file(path: &Path) -> Result<String, String> {
+    let mut file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
         }
+        hasher.update(&buffer[..read]);
     }
+
+    Ok(hex::encode(hasher.finalize()))
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -64,24 +160,76 @@ pub enum DrawingToolType {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct StrokePath {
-    x: f64,
-    y: f64,
+    pub(crate) x: f64,
+    pub(crate) y: f64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Stroke {
-    tool: DrawingToolType,
-    color: String,
-    opacity: f64,
-    thickness: u64,
-    path: Vec<StrokePath>,
+    // assigned on first save when the client doesn't provide one, None only in legacy files
+    #[serde(default)]
+    pub(crate) id: Option<String>,
+    pub(crate) tool: DrawingToolType,
+    pub(crate) color: String,
+    pub(crate) opacity: f64,
+    pub(crate) thickness: u64,
+    pub(crate) path: Vec<StrokePath>,
+    // ms since epoch, stamped on save, 0 for strokes saved before timestamps existed
+    #[serde(default)]
+    pub(crate) created_at: i64,
+}
+
+pub const DEFAULT_MAX_STROKE_THICKNESS: u64 = 200;
+
+impl Stroke {
+    // Rejects strokes that can't be drawn, clamps out-of-range styling and normalizes the
+    // color, run before persisting
+    pub fn sanitize(&mut self, max_thickness: u64) -> Result<(), String> {
+        self.color = normalize_color(&self.color)?;
+
+        if !self.opacity.is_finite() {
+            return Err(format!("Invalid stroke opacity: {}", self.opacity));
+        }
+        self.opacity = self.opacity.clamp(0.0, 1.0);
+
+        if self.thickness == 0 {
+            return Err("Stroke thickness must be at least 1".to_string());
+        }
+        self.thickness = self.thickness.min(max_thickness.max(1));
+
+        if self.path.is_empty() {
+            return Err("Stroke path cannot be empty".to_string());
+        }
+        if let Some(point) = self
+            .path
+            .iter()
+            .find(|p| !p.x.is_finite() || !p.y.is_finite() || p.x < 0.0 || p.y < 0.0)
+        {
+            return Err(format!("Invalid stroke point ({}, {})", point.x, point.y));
+        }
+
+        Ok(())
+    }
+
+    // Sanitizes the stroke and fills in the id and save time when the frontend left them out
+    pub(crate) fn prepare(&mut self, max_thickness: u64) -> Result<(), String> {
+        self.sanitize(max_thickness)?;
+
+        self.id
+            .get_or_insert_with(|| uuid::Uuid::new_v4().to_string());
+        if self.created_at == 0 {
+            self.created_at = Local::now().timestamp_millis();
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct PdfStrokes {
     #[serde(flatten)]
     #[serde(deserialize_with = "string_key_to_u32")]
-    inner: HashMap<u32, Vec<Stroke>>,
+    pub(crate) inner: HashMap<u32, Vec<Stroke>>,
 }
 
 impl PdfStrokes {
@@ -91,15 +239,23 @@ impl PdfStrokes {
         }
     }
 
-    fn insert(&mut self, page: u32, stroke: Stroke) {
-        self.inner.entry(page).or_insert_with(Vec::new).push(stroke);
+    // Inserts or replaces by stroke id, so re-sending the same stroke doesn't duplicate it
+    pub(crate) fn merge(&mut self, page: u32, stroke: Stroke) {
+        let page_strokes = self.inner.entry(page).or_insert_with(Vec::new);
+        match page_strokes
+            .iter_mut()
+            .find(|existing| existing.id.is_some() && existing.id == stroke.id)
+        {
+            Some(existing) => *existing = stroke,
+            None => page_strokes.push(stroke),
+        }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Dimensions {
-    height: f32,
-    width: f32,
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub(crate) struct Dimensions {
+    pub(crate) height: f32,
+    pub(crate) width: f32,
 }
 
 impl Dimensions {
@@ -108,17 +264,56 @@ impl Dimensions {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FitMode {
+    Width,
+    Page,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum EraserMode {
+    // removes every stroke the eraser touches
+    #[default]
+    WholeStroke,
+    // cuts the touched points out, splitting strokes in two where needed
+    Segment,
+}
+
+// Page layout of the reader, only persisted, the backend renders the same for every mode
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ReadingMode {
+    SinglePage,
+    #[default]
+    Continuous,
+    TwoPage,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PdfEditorSyncProps {
-    id: u64,
+    pub(crate) id: u64,
     pen_color: String,
     pen_thickness: u64,
     highlighter_color: String,
     highlighter_thickness: u64,
-    eraser_thickness: u64,
-    current_page: u64,
+    pub(crate) eraser_thickness: u64,
+    pub(crate) current_page: u64,
     scale: f64,
+    // viewport offsets so resuming lands exactly where the reader left off
+    #[serde(default)]
+    scroll_x: f64,
+    #[serde(default)]
+    scroll_y: f64,
+    // None means the explicit `scale` is used as-is
+    #[serde(default)]
+    fit_mode: Option<FitMode>,
+    #[serde(default)]
+    pub(crate) eraser_mode: EraserMode,
+    #[serde(default)]
+    reading_mode: ReadingMode,
 }
 
 impl Default for PdfEditorSyncProps {
@@ -132,11 +327,16 @@ impl Default for PdfEditorSyncProps {
             eraser_thickness: 12,
             current_page: 1,
             scale: 1.0,
+            scroll_x: 0.0,
+            scroll_y: 0.0,
+            fit_mode: None,
+            eraser_mode: EraserMode::default(),
+            reading_mode: ReadingMode::default(),
         }
     }
 }
 
-fn string_key_to_u32<'de, D, V>(deserializer: D) -> Result<HashMap<u32, V>, D::Error>
+pub(crate) fn string_key_to_u32<'de, D, V>(deserializer: D) -> Result<HashMap<u32, V>, D::Error>
 where
     D: Deserializer<'de>,
     V: Deserialize<'de>,
@@ -155,7 +355,7 @@ where
 pub struct PdfPagesDimensions {
     #[serde(flatten)]
     #[serde(deserialize_with = "string_key_to_u32")]
-    inner: HashMap<u32, Dimensions>,
+    pub(crate) inner: HashMap<u32, Dimensions>,
 }
 
 impl PdfPagesDimensions {
@@ -174,7 +374,7 @@ impl PdfPagesDimensions {
 pub struct PdfPagesThumbnails {
     #[serde(flatten)]
     #[serde(deserialize_with = "string_key_to_u32")]
-    inner: HashMap<u32, String>,
+    pub(crate) inner: HashMap<u32, String>,
 }
 
 impl PdfPagesThumbnails {
@@ -189,38 +389,403 @@ impl PdfPagesThumbnails {
     }
 }
 
+// Where the page sits in its thumbnail image, in pixels. Without a thumbnail box the page
+// fills the image, with one it's centered and the rest is letterboxing.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ThumbnailLayout {
+    width: u32,
+    height: u32,
+    x: u32,
+    y: u32,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PdfThumbnailLayouts {
+    #[serde(flatten)]
+    #[serde(deserialize_with = "string_key_to_u32")]
+    pub(crate) inner: HashMap<u32, ThumbnailLayout>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct LoadPdfResponse {
     pdf_entry: PdfEntry,
     pdf_pages_dims: PdfPagesDimensions,
+    // annotation profile the stroke and bookmark commands work on
+    active_profile: String,
 }
 
 impl LoadPdfResponse {
-    pub fn new(pdf_entry: PdfEntry, pdf_pages_dims: PdfPagesDimensions) -> Self {
+    pub fn new(
+        pdf_entry: PdfEntry,
+        pdf_pages_dims: PdfPagesDimensions,
+        active_profile: String,
+    ) -> Self {
         Self {
             pdf_entry,
             pdf_pages_dims,
+            active_profile,
         }
     }
 }
 
+pub const DEFAULT_EXTRACTION_EVENT_INTERVAL_MS: u64 = 100;
+
 #[derive(Default)]
 struct ExtractOptions {
+    // keys the progress reported by get_extraction_eta
+    pdf_id: u64,
     thumbnail: bool,
     dims: bool,
+    // None renders a thumbnail for every page
+    max_thumbnails: Option<u32>,
+}
+
+const ETA_WINDOW: usize = 16;
+
+// Rolling average of per-page render times. Pages of one document can differ wildly (text vs
+// full-page scans), so when the spread outgrows the mean the window restarts from the latest page.
+#[derive(Debug, Default)]
+struct EtaEstimator {
+    samples: VecDeque<f64>,
+}
+
+impl EtaEstimator {
+    fn record(&mut self, duration: Duration) {
+        self.samples.push_back(duration.as_secs_f64() * 1000.0);
+        if self.samples.len() > ETA_WINDOW {
+            self.samples.pop_front();
+        }
+
+        let mean = self.mean();
+        let variance = self
+            .samples
+            .iter()
+            .map(|sample| (sample - mean).powi(2))
+            .sum::<f64>()
+            / self.samples.len() as f64;
+        if self.samples.len() >= 4 && variance.sqrt() > mean {
+            let latest = self.samples.pop_back();
+            self.samples.clear();
+            self.samples.extend(latest);
+        }
+    }
+
+    fn mean(&self) -> f64 {
+        self.samples.iter().sum::<f64>() / self.samples.len().max(1) as f64
+    }
+
+    fn eta_ms(&self, remaining: u32) -> Option<u64> {
+        (!self.samples.is_empty()).then(|| (self.mean() * remaining as f64).round() as u64)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractionProgress {
+    pdf_id: u64,
+    rendered: u32,
+    total: u32,
+    // None until the first page has been timed
+    eta_ms: Option<u64>,
+}
+
+// Clears the reported progress when extraction ends, including on errors
+struct ProgressGuard<'a> {
+    app_handle: &'a AppHandle,
+    pdf_id: u64,
+}
+
+impl Drop for ProgressGuard<'_> {
+    fn drop(&mut self) {
+        self.app_handle
+            .state::<AppState>()
+            .extraction_progress
+            .lock()
+            .unwrap()
+            .remove(&self.pdf_id);
+    }
+}
+
+// Pages that get a thumbnail under `max_thumbnails`: evenly spaced across the document,
+// always including the first and last page. thumbs.json keys are exactly these pages, so
+// the navigator shows placeholders for every other page.
+fn thumbnail_pages(page_count: u32, max_thumbnails: Option<u32>) -> Vec<u32> {
+    match max_thumbnails {
+        Some(cap) if cap < page_count => {
+            if cap == 0 {
+                return Vec::new();
+            }
+            if cap == 1 {
+                return vec![1];
+            }
+
+            let step = (page_count - 1) as f64 / (cap - 1) as f64;
+            let mut pages: Vec<u32> = (0..cap)
+                .map(|k| 1 + (k as f64 * step).round() as u32)
+                .collect();
+            pages.dedup();
+            pages
+        }
+        _ => (1..=page_count).collect(),
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PdfBookmark {
     pub page_number: u32,
     pub label: String,
+    // last page of a range bookmark, None for a single page
+    #[serde(default)]
+    pub end_page: Option<u32>,
 }
 
 pub type PdfBookmarks = Vec<PdfBookmark>;
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BookmarkFileFormat {
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BookmarkImportMode {
+    Merge,
+    Replace,
+}
+
+// Paths inside the app data dir are stored relative to it, with '/' separators, so the
+// library survives the dir being moved or restored on another machine
+fn relative_to_root(root: &Path, path: &str) -> String {
+    match Path::new(path).strip_prefix(root) {
+        Ok(relative) => relative
+            .iter()
+            .map(|component| component.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+        Err(_) => path.to_string(),
+    }
+}
+
+// Inverse of relative_to_root. Absolute paths under another root, written before paths were
+// stored relative, are re-rooted from the pdf's own folder on.
+fn resolve_from_root(root: &Path, pdf_id: u64, path: &str) -> String {
+    let stored = Path::new(path);
+    if path.is_empty() || stored.starts_with(root) {
+        return path.to_string();
+    }
+    if stored.is_relative() {
+        return root.join(stored).to_string_lossy().into_owned();
+    }
+
+    let folder = format!("pdf_{pdf_id}");
+    let components: Vec<_> = stored.components().collect();
+    match components
+        .iter()
+        .position(|component| component.as_os_str() == folder.as_str())
+    {
+        Some(index) => root
+            .join(components[index..].iter().collect::<PathBuf>())
+            .to_string_lossy()
+            .into_owned(),
+        None => path.to_string(),
+    }
+}
+
+// Reads the registry with clone and cover paths resolved to absolute ones
+pub(crate) fn read_pdf_entries(state_path: &Path) -> Result<Vec<PdfEntry>, String> {
+    if !state_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let data = fs::read_to_string(state_path).map_err(|e| e.to_string())?;
+    let mut pdfs = serde_json::from_str::<Vec<PdfEntry>>(&data).map_err(|e| e.to_string())?;
+
+    let root = state_path.parent().unwrap_or(Path::new(""));
+    for pdf in &mut pdfs {
+        pdf.clone_path = resolve_from_root(root, pdf.id, &pdf.clone_path);
+        pdf.cover_path = resolve_from_root(root, pdf.id, &pdf.cover_path);
+    }
+
+    Ok(pdfs)
+}
+
+// Writes the registry with clone and cover paths relative to the app data dir. The original
+// lives outside of it and stays absolute.
+pub(crate) fn write_pdf_entries(
+    app_handle: &AppHandle,
+    state_path: &Path,
+    pdfs: &[PdfEntry],
+) -> Result<(), String> {
+    let root = state_path.parent().unwrap_or(Path::new(""));
+    let stored: Vec<PdfEntry> = pdfs
+        .iter()
+        .map(|pdf| PdfEntry {
+            clone_path: relative_to_root(root, &pdf.clone_path),
+            cover_path: relative_to_root(root, &pdf.cover_path),
+            ..pdf.clone()
+        })
+        .collect();
+
+    write_json(app_handle, state_path, &stored, FileKind::Registry)
+}
+
+// Rewrites a registry that still holds absolute paths, returns whether it had to
+pub(crate) fn migrate_registry_paths(app_handle: &AppHandle) -> Result<bool, String> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let state_path = app_data_dir.join("pdfs.json");
+    if !state_path.exists() {
+        return Ok(false);
+    }
+
+    let state = app_handle.state::<AppState>();
+    let _guard = state.registry_lock.lock().unwrap();
+
+    let data = fs::read_to_string(&state_path).map_err(|e| e.to_string())?;
+    let stored = serde_json::from_str::<Vec<PdfEntry>>(&data).map_err(|e| e.to_string())?;
+    let has_absolute = stored.iter().any(|pdf| {
+        Path::new(&pdf.clone_path).is_absolute() || Path::new(&pdf.cover_path).is_absolute()
+    });
+    if !has_absolute {
+        return Ok(false);
+    }
+
+    let pdfs = read_pdf_entries(&state_path)?;
+    write_pdf_entries(app_handle, &state_path, &pdfs)?;
+    Ok(true)
+}
+
+pub(crate) fn find_pdf_entry(app_data_dir: &Path, id: u64) -> Result<PdfEntry, AppError> {
+    let pdfs = read_pdf_entries(&app_data_dir.join("pdfs.json"))?;
+    match pdfs.binary_search_by(|pdf| pdf.id.cmp(&id)) {
+        Ok(index) => Ok(pdfs[index].clone()),
+        Err(_) => Err(AppError::NotFound(format!("PDF with id {id} not found"))),
+    }
+}
+
+// Parses strokes one by one so a single malformed stroke (e.g. from a partial write)
+// is dropped instead of failing the whole file
+pub(crate) fn load_strokes_from_file(
+    app_handle: &AppHandle,
+    path: &Path,
+) -> Result<PdfStrokes, AppError> {
+    let Some(data) = read_data_file(app_handle, path)? else {
+        return Ok(PdfStrokes::new());
+    };
+    if data.trim().is_empty() {
+        return Ok(PdfStrokes::new());
+    }
+
+    let raw = serde_json::from_str::<HashMap<String, Vec<serde_json::Value>>>(&data)?;
+
+    let mut strokes = PdfStrokes::new();
+    let mut dropped = 0;
+
+    for (key, values) in raw {
+        let Ok(page) = key.parse::<u32>() else {
+            log::warn!("Skipping strokes under invalid page key {key:?} in {path:?}");
+            dropped += values.len();
+            continue;
+        };
+
+        let page_strokes: Vec<Stroke> = values
+            .into_iter()
+            .filter_map(|value| match serde_json::from_value::<Stroke>(value) {
+                Ok(stroke) => Some(stroke),
+                Err(e) => {
+                    log::warn!("Skipping malformed stroke on page {page}: {e}");
+                    dropped += 1;
+                    None
+                }
+            })
+            .collect();
+
+        strokes.inner.insert(page, page_strokes);
+    }
+
+    if dropped > 0 {
+        log::warn!("Dropped {dropped} unreadable stroke(s) from {path:?}");
+    }
+
+    Ok(strokes)
+}
+
+pub(crate) fn load_dims_from_file(path: &Path) -> Result<PdfPagesDimensions, String> {
+    let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str::<PdfPagesDimensions>(&data).map_err(|e| e.to_string())
+}
+
+// Size the page is rendered at and where it goes in the thumbnail, for a page of
+// `width` x `height` points
+fn thumbnail_layout(width: f32, height: f32, settings: &AppSettings) -> ThumbnailLayout {
+    let Some(size) = settings.thumbnail_box else {
+        let (width, height) = match settings.thumbnail_dpi {
+            Some(dpi) => {
+                let (width, height) = pixels_at_dpi(width, height, dpi);
+                (width as u32, height as u32)
+            }
+            None => (
+                (width / settings.thumbnail_divisor) as u32,
+                (height / settings.thumbnail_divisor) as u32,
+            ),
+        };
+        return ThumbnailLayout {
+            width,
+            height,
+            x: 0,
+            y: 0,
+        };
+    };
+
+    // largest scale that keeps the whole page inside the box
+    let scale = (size.width as f32 / width).min(size.height as f32 / height);
+    let fitted_width = ((width * scale).round() as u32).clamp(1, size.width);
+    let fitted_height = ((height * scale).round() as u32).clamp(1, size.height);
+
+    ThumbnailLayout {
+        width: fitted_width,
+        height: fitted_height,
+        x: (size.width - fitted_width) / 2,
+        y: (size.height - fitted_height) / 2,
+    }
+}
+
+// Centers the rendered page on a white image of the thumbnail box's size
+fn letterbox(
+    rendered: DynamicImage,
+    layout: ThumbnailLayout,
+    settings: &AppSettings,
+) -> DynamicImage {
+    let Some(size) = settings.thumbnail_box else {
+        return rendered;
+    };
+
+    let mut canvas = RgbImage::from_pixel(size.width, size.height, Rgb([255, 255, 255]));
+    imageops::overlay(
+        &mut canvas,
+        &rendered.to_rgb8(),
+        layout.x as i64,
+        layout.y as i64,
+    );
+    DynamicImage::ImageRgb8(canvas)
+}
+
+pub(crate) fn load_thumbnails_from_file(path: &Path) -> Result<PdfPagesThumbnails, String> {
+    if !path.exists() {
+        return Ok(PdfPagesThumbnails::new());
+    }
+
+    let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str::<PdfPagesThumbnails>(&data).map_err(|e| e.to_string())
+}
+
 fn extract_pdf_data(
     app_handle: &AppHandle,
-    pdfium_path: &PathBuf,
+    pdfium: &Pdfium,
     pdf_path: &str,
     folder_path: &PathBuf,
     options: ExtractOptions,
@@ -229,12 +794,6 @@ fn extract_pdf_data(
         return Ok(()); // nothing to do
     }
 
-    let pdfium = Pdfium::new(
-        Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path(pdfium_path))
-            .or_else(|_| Pdfium::bind_to_system_library())
-            .map_err(|e| e.to_string())?,
-    );
-
     let document = pdfium
         .load_pdf_from_file(pdf_path, None)
         .map_err(|e| e.to_string())?;
@@ -242,6 +801,7 @@ fn extract_pdf_data(
     // Prepare output folders/files
     let thumbs_dir = folder_path.join("thumbnails");
     let thumbs_path = folder_path.join("thumbs.json");
+    let layouts_path = folder_path.join("thumb_layouts.json");
     let dims_path = folder_path.join("dims.json");
 
     if options.thumbnail {
@@ -249,177 +809,706 @@ fn extract_pdf_data(
     }
 
     let mut page_thumbs = PdfPagesThumbnails::new();
+    let mut thumb_layouts = PdfThumbnailLayouts::default();
     let mut pdf_pages_dims = PdfPagesDimensions::new();
 
+    let settings = app_handle.state::<AppState>().settings();
+    let flush_policy = settings.thumbnail_flush;
+    let mut thumbs_flush = ThrottledFlush::new(flush_policy);
+    let mut dims_flush = ThrottledFlush::new(flush_policy);
+
+    // events carry only the pages ready since the previous one, and go out at most once per
+    // interval so a large document doesn't flood the IPC channel
+    let event_policy = FlushPolicy {
+        every_updates: u32::MAX,
+        every_ms: settings.extraction_event_interval_ms,
+    };
+    let mut dims_events = ThrottledFlush::new(event_policy);
+    let mut thumbs_events = ThrottledFlush::new(event_policy);
+    let mut dims_delta = PdfPagesDimensions::new();
+    let mut thumbs_delta = PdfPagesThumbnails::new();
+
+    let thumbnail_pages = thumbnail_pages(document.pages().len() as u32, options.max_thumbnails);
+
+    let mut eta = EtaEstimator::default();
+    let mut rendered = 0u32;
+    let _progress_guard = ProgressGuard {
+        app_handle,
+        pdf_id: options.pdf_id,
+    };
+
     for (i, page) in document.pages().iter().enumerate() {
+        // what's done so far is flushed below, missing_thumbnail_pages reports the rest
+        if app_handle.state::<AppState>().is_shutting_down() {
+            log::info!("Stopping extraction of pdf {} for shutdown", options.pdf_id);
+            break;
+        }
+
         let page_no = i as u32 + 1;
         let size = page.page_size();
         let height = size.height().value;
         let width = size.width().value;
 
         if options.dims {
-            pdf_pages_dims.insert(page_no, Dimensions::new(height, width));
-
-            let serialized =
-                serde_json::to_string_pretty(&pdf_pages_dims).map_err(|e| e.to_string())?;
-            fs::write(&dims_path, serialized).map_err(|e| e.to_string())?;
+            let dims = Dimensions::new(height, width);
+            pdf_pages_dims.insert(page_no, dims);
+            dims_delta.insert(page_no, dims);
+
+            // load_pdf reads dims.json to lay out pages, so the first page is written right away
+            if dims_flush.record_page(page_no) {
+                write_json(
+                    app_handle,
+                    &dims_path,
+                    &pdf_pages_dims,
+                    FileKind::Dimensions,
+                )?;
+            }
 
-            app_handle
-                .emit("page-dimensions-extracted", &pdf_pages_dims)
-                .unwrap();
+            if dims_events.record() {
+                app_handle
+                    .emit("page-dimensions-extracted", std::mem::take(&mut dims_delta))
+                    .unwrap();
+            }
         }
 
-        if options.thumbnail {
-            let thumb_width = (width / 3.0) as i32;
-            let thumb_height = (height / 3.0) as i32;
+        if options.thumbnail && thumbnail_pages.binary_search(&page_no).is_ok() {
+            let started = Instant::now();
+            let layout = thumbnail_layout(width, height, &settings);
 
             let bitmap = page
-                .render(thumb_width, thumb_height, None)
+                .render(layout.width as i32, layout.height as i32, None)
                 .map_err(|e| e.to_string())?;
 
-            let now = Local::now();
-            let timestamp = now.format("%Y%m%d_%H%M%S").to_string();
+            let timestamp = file_timestamp();
             let thumb_path = thumbs_dir.join(format!("page_{page_no}_{timestamp}.jpg"));
-            bitmap
-                .as_image()
+            letterbox(bitmap.as_image(), layout, &settings)
                 .save(&thumb_path)
                 .map_err(|e| e.to_string())?;
+            thumb_layouts.inner.insert(page_no, layout);
 
-            page_thumbs.insert(page_no, thumb_path.to_str().unwrap().to_string());
+            eta.record(started.elapsed());
+            rendered += 1;
+            let total = thumbnail_pages.len() as u32;
+            app_handle
+                .state::<AppState>()
+                .extraction_progress
+                .lock()
+                .unwrap()
+                .insert(
+                    options.pdf_id,
+                    ExtractionProgress {
+                        pdf_id: options.pdf_id,
+                        rendered,
+                        total,
+                        eta_ms: eta.eta_ms(total - rendered),
+                    },
+                );
+
+            let thumb_path = thumb_path.to_str().unwrap().to_string();
+            page_thumbs.insert(page_no, thumb_path.clone());
+            thumbs_delta.insert(page_no, thumb_path);
 
             // Incremental emit
-            app_handle
-                .emit("thumbnail-extracted", &page_thumbs)
-                .unwrap();
+            if thumbs_events.record() {
+                app_handle
+                    .emit("thumbnail-extracted", std::mem::take(&mut thumbs_delta))
+                    .unwrap();
+            }
 
-            // Persist thumbnails incrementally
-            let thumbs_serialized =
-                serde_json::to_string_pretty(&page_thumbs).map_err(|e| e.to_string())?;
-            fs::write(&thumbs_path, thumbs_serialized).map_err(|e| e.to_string())?;
+            // Persist thumbnails periodically, rewriting the whole map per page is quadratic
+            if thumbs_flush.record() {
+                write_json(app_handle, &thumbs_path, &page_thumbs, FileKind::Thumbnails)?;
+                write_json(
+                    app_handle,
+                    &layouts_path,
+                    &thumb_layouts,
+                    FileKind::Thumbnails,
+                )?;
+            }
         }
     }
 
-    Ok(())
-}
-
-#[tauri::command]
-pub fn register_pdf(app_handle: tauri::AppHandle, pdf_path: String) -> Result<String, String> {
-    log::info!("Registering new pdf: {pdf_path}");
+    if dims_events.is_dirty() {
+        app_handle
+            .emit("page-dimensions-extracted", &dims_delta)
+            .unwrap();
+    }
+    if thumbs_events.is_dirty() {
+        app_handle
+            .emit("thumbnail-extracted", &thumbs_delta)
+            .unwrap();
+    }
 
-    // This will handle platform specific app data directories
-    let app_data_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?;
+    if dims_flush.is_dirty() {
+        write_json(
+            app_handle,
+            &dims_path,
+            &pdf_pages_dims,
+            FileKind::Dimensions,
+        )?;
+    }
+    if thumbs_flush.is_dirty() {
+        write_json(app_handle, &thumbs_path, &page_thumbs, FileKind::Thumbnails)?;
+        write_json(
+            app_handle,
+            &layouts_path,
+            &thumb_layouts,
+            FileKind::Thumbnails,
+        )?;
+    }
 
-    let state_path = app_data_dir.join("pdfs.json");
+    Ok(())
+}
 
-    if cfg!(debug_assertions) {
-        if let Some(parent) = state_path.parent() {
-            let _ = open_folder(parent);
-        }
+// Copies the thumbnails of a previous registration of the same content into `folder_path`.
+// Returns None when the cached set doesn't cover every page, so the caller falls back to a full render.
+fn reuse_cached_thumbnails(
+    app_handle: &AppHandle,
+    app_data_dir: &Path,
+    source_id: u64,
+    folder_path: &Path,
+    page_count: u32,
+    max_thumbnails: Option<u32>,
+) -> Result<Option<PdfPagesThumbnails>, String> {
+    let source_thumbs_path = app_data_dir.join(format!("pdf_{source_id}/thumbs.json"));
+    if !source_thumbs_path.exists() {
+        return Ok(None);
     }
 
-    let mut pdfs: Vec<PdfEntry> = if state_path.exists() {
-        let data = fs::read_to_string(&state_path).map_err(|e| e.to_string())?;
-        serde_json::from_str::<Vec<PdfEntry>>(&data).map_err(|e| e.to_string())?
-    } else {
-        Vec::new()
-    };
-
-    let latest_id = match pdfs.last() {
-        Some(pdf_entry) => pdf_entry.id + 1,
-        None => 1,
+    let data = fs::read_to_string(&source_thumbs_path).map_err(|e| e.to_string())?;
+    let cached = match serde_json::from_str::<PdfPagesThumbnails>(&data) {
+        Ok(cached) => cached,
+        Err(e) => {
+            log::warn!("Ignoring unreadable cached thumbnails for pdf {source_id}: {e}");
+            return Ok(None);
+        }
     };
 
-    let file_name = Path::new(&pdf_path)
-        .file_name()
-        .and_then(|s| s.to_str())
-        .ok_or("Invalid PDF path")?
-        .to_string();
+    let wanted_pages = thumbnail_pages(page_count, max_thumbnails);
 
-    let folder_name = format!("pdf_{latest_id}");
-    let folder_path = app_data_dir.join(folder_name);
-    let base_path = folder_path.to_str().unwrap().to_string(); // String
-    let clone_path = format!("{base_path}/{latest_id}.pdf");
+    let complete = wanted_pages.iter().all(|page_no| {
+        cached
+            .inner
+            .get(page_no)
+            .is_some_and(|thumb| Path::new(thumb).exists())
+    });
+    if !complete {
+        return Ok(None);
+    }
 
-    fs::create_dir_all(&folder_path).map_err(|e| e.to_string())?;
+    let thumbs_dir = folder_path.join("thumbnails");
+    fs::create_dir_all(&thumbs_dir).map_err(|e| e.to_string())?;
 
-    fs::copy(&pdf_path, &clone_path).map_err(|e| e.to_string())?;
+    let mut page_thumbs = PdfPagesThumbnails::new();
+    for page_no in wanted_pages {
+        let source = Path::new(&cached.inner[&page_no]);
+        let file_name = source.file_name().ok_or("Invalid thumbnail path")?;
+        let target = thumbs_dir.join(file_name);
+        fs::copy(source, &target).map_err(|e| e.to_string())?;
+        page_thumbs.insert(page_no, target.to_str().unwrap().to_string());
+    }
 
-    // extract pdf cover
-    let now = Local::now();
-    let timestamp = now.format("%Y%m%d_%H%M%S").to_string();
-    let cover_path = format!("{base_path}/{latest_id}_cover_{timestamp}.jpg");
-    let state = app_handle.state::<AppState>();
+    // layouts carry no paths, the source's file applies as is
+    let source_layouts_path = app_data_dir.join(format!("pdf_{source_id}/thumb_layouts.json"));
+    if source_layouts_path.exists() {
+        fs::copy(&source_layouts_path, folder_path.join("thumb_layouts.json"))
+            .map_err(|e| e.to_string())?;
+    }
 
-    let pdfium_path = &state.lib_path;
+    write_json(
+        app_handle,
+        &folder_path.join("thumbs.json"),
+        &page_thumbs,
+        FileKind::Thumbnails,
+    )?;
 
-    let pdfium = Pdfium::new(
-        Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path(pdfium_path))
-            .or_else(|_| Pdfium::bind_to_system_library())
-            .unwrap(),
-    );
+    Ok(Some(page_thumbs))
+}
 
+// Renders a page (the first one unless the user picked another) as the library cover, at the
+// configured DPI or else half size
+fn render_cover(
+    pdfium: &Pdfium,
+    clone_path: &str,
+    folder_path: &Path,
+    pdf_id: u64,
+    page_index: PdfPageIndex,
+    dpi: Option<f32>,
+) -> Result<String, String> {
     let document = pdfium
-        .load_pdf_from_file(&clone_path, None)
+        .load_pdf_from_file(clone_path, None)
         .map_err(|e| e.to_string())?;
 
-    let page = document.pages().get(0).map_err(|e| e.to_string())?;
+    let page = document
+        .pages()
+        .get(page_index)
+        .map_err(|e| e.to_string())?;
     let size = page.page_size();
-    let height = (size.height().value / 2.0) as i32;
-    let width = (size.width().value / 2.0) as i32;
+    let (width, height) = match dpi {
+        Some(dpi) => pixels_at_dpi(size.width().value, size.height().value, dpi),
+        None => (
+            (size.width().value / 2.0) as i32,
+            (size.height().value / 2.0) as i32,
+        ),
+    };
 
     let bitmap = page
         .render(width, height, None)
         .map_err(|e| e.to_string())?;
 
+    let timestamp = file_timestamp();
+    let cover_path = folder_path.join(format!("{pdf_id}_cover_{timestamp}.jpg"));
+
     bitmap
         .as_image()
         .save(&cover_path)
         .map_err(|e| e.to_string())?;
-    let entry = PdfEntry::new(
-        latest_id,
-        pdf_path.clone(),
-        clone_path.clone(),
-        cover_path,
-        file_name,
-    );
 
-    pdfs.push(entry);
+    Ok(cover_path.to_str().unwrap().to_string())
+}
 
-    // Save
-    fs::create_dir_all(app_data_dir).map_err(|e| e.to_string())?;
-    let serialized = serde_json::to_string_pretty(&pdfs).map_err(|e| e.to_string())?;
-    fs::write(&state_path, serialized).map_err(|e| e.to_string())?;
+// Points the entry at a new cover, removes the previous file and tells the library.
+// `cover_page` is the page it was rendered from, None for the first page or an image.
+fn replace_cover(
+    app_handle: &AppHandle,
+    pdf_id: u64,
+    cover_path: String,
+    custom_cover: bool,
+    cover_page: Option<u32>,
+) -> Result<(), String> {
+    let mut old_cover_path = String::new();
+    update_pdf_entry(app_handle, pdf_id, |entry| {
+        old_cover_path = std::mem::replace(&mut entry.cover_path, cover_path.clone());
+        entry.custom_cover = custom_cover;
+        entry.cover_page = cover_page;
+    })?;
+
+    if !old_cover_path.is_empty() && old_cover_path != cover_path {
+        let _ = fs::remove_file(&old_cover_path);
+    }
 
-    // cpu heavy
-    let pdfium_lib_path = pdfium_path.clone();
-    let thread_clone_path = clone_path.clone();
-    let thread_folder_path = folder_path.clone();
+    app_handle
+        .emit("cover-ready", CoverReady { pdf_id, cover_path })
+        .map_err(|e| e.to_string())
+}
+
+// Applies `update` to one registry entry and persists the registry
+pub(crate) fn update_pdf_entry<F>(
+    app_handle: &AppHandle,
+    id: u64,
+    update: F,
+) -> Result<PdfEntry, String>
+where
+    F: FnOnce(&mut PdfEntry),
+{
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let state_path = app_data_dir.join("pdfs.json");
+
+    let state = app_handle.state::<AppState>();
+    let _guard = state.registry_lock.lock().unwrap();
+
+    let mut pdfs = read_pdf_entries(&state_path)?;
+    let index = pdfs
+        .binary_search_by(|pdf| pdf.id.cmp(&id))
+        .map_err(|_| format!("PDF with id {id} not found"))?;
+
+    update(&mut pdfs[index]);
+    write_pdf_entries(app_handle, &state_path, &pdfs)?;
+
+    Ok(pdfs[index].clone())
+}
+
+// Registers a document produced in the app rather than imported. `write` saves it to the
+// clone path it's given; cover, thumbnails and dims are then generated like for an import.
+pub(crate) fn register_generated_pdf<W>(
+    app_handle: &AppHandle,
+    file_name: String,
+    write: W,
+) -> Result<PdfEntry, String>
+where
+    W: FnOnce(&Pdfium, &Path) -> Result<(), String>,
+{
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let state_path = app_data_dir.join("pdfs.json");
+
+    let state = app_handle.state::<AppState>();
+    let pdfium = state.pdfium()?;
+    let registry_guard = state.registry_lock.lock().unwrap();
+
+    let mut pdfs = read_pdf_entries(&state_path)?;
+
+    let latest_id = match pdfs.last() {
+        Some(pdf_entry) => pdf_entry.id + 1,
+        None => 1,
+    };
+
+    let folder_path = app_data_dir.join(format!("pdf_{latest_id}"));
+    let clone_path = folder_path.join(format!("{latest_id}.pdf"));
+
+    fs::create_dir_all(&folder_path).map_err(|e| e.to_string())?;
+
+    if let Err(e) = write(&pdfium, &clone_path) {
+        let _ = fs::remove_dir_all(&folder_path);
+        return Err(e);
+    }
+
+    let page_count = pdfium
+        .load_pdf_from_file(&clone_path, None)
+        .map(|document| document.pages().len() as u32)
+        .map_err(|e| e.to_string());
+    let page_count = match page_count {
+        Ok(page_count) => page_count,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&folder_path);
+            return Err(e);
+        }
+    };
+
+    // generated documents have no original on disk, the clone is the only copy
+    let clone_hash = hash_file(&clone_path)?;
+    let mut entry = PdfEntry::new(
+        latest_id,
+        String::new(),
+        clone_path.to_str().unwrap().to_string(),
+        String::new(),
+        file_name,
+        Some(clone_hash.clone()),
+        Some(Local::now().to_rfc3339()),
+    );
+    entry.clone_hash = Some(clone_hash);
+    entry.page_count = Some(page_count);
+
+    pdfs.push(entry.clone());
+    write_pdf_entries(app_handle, &state_path, &pdfs)?;
+    drop(registry_guard);
+
+    rebuild_derived_data(app_handle, &entry, &folder_path)?;
+
+    Ok(entry)
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtractionStatus {
+    Queued,
+    Started,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractionStatusEvent {
+    pdf_id: u64,
+    status: ExtractionStatus,
+}
+
+// Waits for a free extraction slot, telling the UI when the document has to wait in line
+fn acquire_extraction_slot<'a>(
+    app_handle: &AppHandle,
+    state: &'a AppState,
+    pdf_id: u64,
+) -> SemaphorePermit<'a> {
+    let emit = |status| {
+        app_handle
+            .emit(
+                "extraction-status",
+                ExtractionStatusEvent { pdf_id, status },
+            )
+            .unwrap();
+    };
+
+    let permit = match state.extraction_slots.try_acquire() {
+        Some(permit) => permit,
+        None => {
+            emit(ExtractionStatus::Queued);
+            state.extraction_slots.acquire()
+        }
+    };
+    emit(ExtractionStatus::Started);
+    permit
+}
+
+#[tauri::command]
+pub fn set_max_concurrent_extractions(app_handle: AppHandle, max: usize) -> Result<(), String> {
+    log::info!("Allowing {max} concurrent extractions");
+
+    patch_settings(
+        &app_handle,
+        AppSettingsPatch {
+            max_concurrent_extractions: Some(max),
+            ..AppSettingsPatch::default()
+        },
+    )?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RegisteredPdf {
+    pub(crate) pdf_id: u64,
+    // annotations of an earlier copy of this document were archived when it was removed,
+    // restore_archived_annotations brings them back
+    pub(crate) archived_annotations: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PdfProbe {
+    is_encrypted: bool,
+    // None when the file couldn't be opened
+    page_count: Option<u32>,
+    // false for files pdfium can't parse, encrypted ones are valid
+    valid_pdf: bool,
+}
+
+// Tells the import dialog what a file is before registering it, e.g. to ask for a password
+// up front. Only reads the file, nothing is copied or recorded.
+#[tauri::command]
+pub fn probe_pdf(app_handle: AppHandle, path: String) -> Result<PdfProbe, AppError> {
+    log::info!("Probing pdf: {path}");
+
+    if !Path::new(&path).is_file() {
+        return Err(AppError::NotFound(format!("File not found: {path}")));
+    }
+
+    let pdfium = app_handle.state::<AppState>().pdfium()?;
+    let probe = match pdfium
+        .load_pdf_from_file(&path, None)
+        .map_err(AppError::from)
+    {
+        Ok(document) => PdfProbe {
+            is_encrypted: false,
+            page_count: Some(document.pages().len() as u32),
+            valid_pdf: true,
+        },
+        Err(AppError::Encrypted(_)) => PdfProbe {
+            is_encrypted: true,
+            page_count: None,
+            valid_pdf: true,
+        },
+        Err(e) => {
+            log::info!("Not a readable pdf: {e}");
+            PdfProbe {
+                is_encrypted: false,
+                page_count: None,
+                valid_pdf: false,
+            }
+        }
+    };
+
+    Ok(probe)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CoverReady {
+    pdf_id: u64,
+    cover_path: String,
+}
+
+#[tauri::command]
+pub fn register_pdf(
+    app_handle: tauri::AppHandle,
+    pdf_path: String,
+) -> Result<RegisteredPdf, String> {
+    log::info!("Registering new pdf: {pdf_path}");
+
+    register_pdf_file(app_handle, pdf_path, false)?
+        .ok_or_else(|| "PDF is already in the library".to_string())
+}
+
+// Copies a pdf into the library. With `skip_existing` a file whose content is already in the
+// library isn't registered again and None is returned, checked under the registry lock.
+pub(crate) fn register_pdf_file(
+    app_handle: tauri::AppHandle,
+    pdf_path: String,
+    skip_existing: bool,
+) -> Result<Option<RegisteredPdf>, String> {
+    // This will handle platform specific app data directories
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let state_path = app_data_dir.join("pdfs.json");
+
+    if cfg!(debug_assertions) {
+        if let Some(parent) = state_path.parent() {
+            let _ = open_path(parent);
+        }
+    }
+
+    let state = app_handle.state::<AppState>();
+    let pdfium = state.pdfium()?;
+    let max_thumbnails = state.settings().max_thumbnails;
+    let registry_guard = state.registry_lock.lock().unwrap();
+
+    let mut pdfs = read_pdf_entries(&state_path)?;
+
+    let latest_id = match pdfs.last() {
+        Some(pdf_entry) => pdf_entry.id + 1,
+        None => 1,
+    };
+
+    let file_name = Path::new(&pdf_path)
+        .file_name()
+        .and_then(|s| s.to_str())
+        .ok_or("Invalid PDF path")?
+        .to_string();
+
+    let content_hash = hash_file(Path::new(&pdf_path))?;
+    let archived_annotations = has_archived_annotations(&app_data_dir, &content_hash);
+    let previous_id = pdfs
+        .iter()
+        .find(|pdf| pdf.content_hash.as_deref() == Some(content_hash.as_str()))
+        .map(|pdf| pdf.id);
+    if skip_existing && previous_id.is_some() {
+        return Ok(None);
+    }
+
+    let folder_name = format!("pdf_{latest_id}");
+    let folder_path = app_data_dir.join(folder_name);
+    let base_path = folder_path.to_str().unwrap().to_string(); // String
+    let clone_path = format!("{base_path}/{latest_id}.pdf");
+
+    let source_size = fs::metadata(&pdf_path).map_err(|e| e.to_string())?.len();
+    ensure_space_for_import(&app_data_dir, source_size)?;
+
+    fs::create_dir_all(&folder_path).map_err(|e| e.to_string())?;
+
+    // opening is cheap compared to rendering, it validates the file before it's listed
+    let copied = fs::copy(&pdf_path, &clone_path)
+        .map_err(|e| e.to_string())
+        .and_then(|_| {
+            // a short or corrupted copy would otherwise only show up when rendering fails
+            let clone_hash = hash_file(Path::new(&clone_path))?;
+            if clone_hash != content_hash {
+                return Err("Copied PDF doesn't match its original".to_string());
+            }
+            pdfium
+                .load_pdf_from_file(&clone_path, None)
+                .map(|document| document.pages().len() as u32)
+                .map_err(|e| e.to_string())
+        });
+    let page_count = match copied {
+        Ok(page_count) => page_count,
+        Err(e) => {
+            // nothing refers to the folder yet, don't leave a partial copy behind
+            let _ = fs::remove_dir_all(&folder_path);
+            return Err(e);
+        }
+    };
+
+    // cover is rendered in the background, the library shows a placeholder until cover-ready
+    let mut entry = PdfEntry::new(
+        latest_id,
+        pdf_path.clone(),
+        clone_path.clone(),
+        String::new(),
+        file_name,
+        Some(content_hash.clone()),
+        Some(Local::now().to_rfc3339()),
+    );
+    entry.clone_hash = Some(content_hash);
+    entry.page_count = Some(page_count);
+    entry.original_mtime = file_mtime(Path::new(&pdf_path));
+
+    pdfs.push(entry);
+
+    // Save
+    write_pdf_entries(&app_handle, &state_path, &pdfs)?;
+    drop(registry_guard);
+
+    // cpu heavy
+    let thread_pdfium = pdfium.clone();
+    let thread_clone_path = clone_path.clone();
+    let thread_folder_path = folder_path.clone();
 
     tauri::async_runtime::spawn_blocking(move || {
-        // extract_page_thumbnails(&app_handle, &pdfium_lib_path, &thread_clone_path, &thread_folder_path)
+        let state = app_handle.state::<AppState>();
+        let _slot = acquire_extraction_slot(&app_handle, &state, latest_id);
 
-        extract_pdf_data(
+        match render_cover(
+            &thread_pdfium,
+            &thread_clone_path,
+            &thread_folder_path,
+            latest_id,
+            0,
+            state.settings().cover_dpi,
+        ) {
+            Ok(cover_path) => {
+                let updated = update_pdf_entry(&app_handle, latest_id, |entry| {
+                    entry.cover_path = cover_path.clone();
+                });
+                match updated {
+                    Ok(_) => app_handle
+                        .emit(
+                            "cover-ready",
+                            CoverReady {
+                                pdf_id: latest_id,
+                                cover_path,
+                            },
+                        )
+                        .unwrap(),
+                    Err(e) => log::error!("Failed to persist cover of pdf {latest_id}: {e}"),
+                }
+            }
+            Err(e) => log::error!("Failed to render cover of pdf {latest_id}: {e}"),
+        }
+
+        // identical content was registered before, skip re-rendering its thumbnails
+        let reused_thumbs = match previous_id {
+            Some(source_id) => {
+                match reuse_cached_thumbnails(
+                    &app_handle,
+                    &app_data_dir,
+                    source_id,
+                    &thread_folder_path,
+                    page_count,
+                    max_thumbnails,
+                ) {
+                    Ok(reused) => reused,
+                    Err(e) => {
+                        log::warn!("Failed to reuse thumbnails of pdf {source_id}: {e}");
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+
+        if let Some(page_thumbs) = &reused_thumbs {
+            log::info!("Reused cached thumbnails for pdf {latest_id}");
+            app_handle.emit("thumbnail-extracted", page_thumbs).unwrap();
+        }
+
+        if let Err(e) = extract_pdf_data(
             &app_handle,
-            &pdfium_lib_path,
+            &thread_pdfium,
             &thread_clone_path,
             &thread_folder_path,
             ExtractOptions {
-                thumbnail: true,
+                pdf_id: latest_id,
+                thumbnail: reused_thumbs.is_none(),
                 dims: true,
+                max_thumbnails,
             },
-        )
+        ) {
+            log::error!("Failed to extract data of pdf {latest_id}: {e}");
+        }
     });
 
-    Ok(format!("Registered PDF"))
+    Ok(Some(RegisteredPdf {
+        pdf_id: latest_id,
+        archived_annotations,
+    }))
 }
 
-#[tauri::command]
-pub async fn list_pdf(app_handle: tauri::AppHandle) -> Result<Vec<PdfEntry>, String> {
-    log::info!("Listing pdf list");
-
+// Reads the registry once, backfilling missing import times
+fn load_pdf_list(app_handle: &AppHandle) -> Result<Vec<PdfEntry>, String> {
     // This will handle platform specific app data directories
     let app_data_dir = app_handle
         .path()
@@ -428,18 +1517,138 @@ pub async fn list_pdf(app_handle: tauri::AppHandle) -> Result<Vec<PdfEntry>, Str
 
     let state_path = app_data_dir.join("pdfs.json");
 
-    let pdfs: Vec<PdfEntry> = if state_path.exists() {
-        let data = fs::read_to_string(&state_path).map_err(|e| e.to_string())?;
-        serde_json::from_str::<Vec<PdfEntry>>(&data).map_err(|e| e.to_string())?
-    } else {
-        Vec::new()
-    };
+    let state = app_handle.state::<AppState>();
+    let _guard = state.registry_lock.lock().unwrap();
+
+    let mut pdfs = read_pdf_entries(&state_path)?;
+
+    // entries registered before import times were recorded fall back to the clone's creation time
+    let mut backfilled = false;
+    for pdf in pdfs.iter_mut().filter(|pdf| pdf.created_at.is_none()) {
+        let created = fs::metadata(&pdf.clone_path).and_then(|m| m.created());
+        if let Ok(created) = created {
+            pdf.created_at = Some(DateTime::<Local>::from(created).to_rfc3339());
+            backfilled = true;
+        }
+    }
+
+    if backfilled {
+        write_pdf_entries(app_handle, &state_path, &pdfs)?;
+    }
 
     Ok(pdfs)
 }
 
 #[tauri::command]
-pub fn remove_pdf(app_handle: tauri::AppHandle, id: u64) -> Result<bool, String> {
+pub async fn list_pdf(app_handle: tauri::AppHandle) -> Result<Vec<PdfEntry>, String> {
+    log::info!("Listing pdf list");
+    load_pdf_list(&app_handle)
+}
+
+// Largest page list_pdf_page returns at once
+const MAX_PDF_PAGE_LIMIT: usize = 500;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PdfPage {
+    entries: Vec<PdfEntry>,
+    // entries in the whole library
+    total: usize,
+    offset: usize,
+}
+
+// One page of the library for infinite scrolling, sorted before slicing. Without `sort_by`
+// entries keep registry order. An offset past the end gives an empty page.
+#[tauri::command]
+pub async fn list_pdf_page(
+    app_handle: AppHandle,
+    offset: usize,
+    limit: usize,
+    sort_by: Option<SortBy>,
+    ascending: Option<bool>,
+) -> Result<PdfPage, String> {
+    log::info!("Listing pdfs {offset}+{limit} sorted by {sort_by:?}");
+
+    if limit == 0 || limit > MAX_PDF_PAGE_LIMIT {
+        return Err(format!(
+            "Limit must be between 1 and {MAX_PDF_PAGE_LIMIT}, got {limit}"
+        ));
+    }
+
+    let mut pdfs = load_pdf_list(&app_handle)?;
+    let mut reverse = false;
+    if let Some(sort) = sort_by {
+        sort_pdfs(&app_handle, &mut pdfs, sort);
+        reverse = ascending.is_some_and(|ascending| ascending != sort.is_ascending());
+    } else if ascending == Some(false) {
+        reverse = true;
+    }
+    if reverse {
+        pdfs.reverse();
+    }
+
+    let total = pdfs.len();
+    let entries = pdfs.into_iter().skip(offset).take(limit).collect();
+
+    Ok(PdfPage {
+        entries,
+        total,
+        offset,
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PdfListBatch {
+    // position of the first entry of this batch in the full list
+    offset: usize,
+    entries: Vec<PdfEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PdfListDone {
+    total: usize,
+}
+
+// Like list_pdf, but sends the entries as `pdf-list-batch` events of `batch_size` entries
+// followed by `pdf-list-done`, so large libraries can be rendered as they arrive.
+// Returns the number of entries sent.
+#[tauri::command]
+pub async fn stream_pdf_list(app_handle: AppHandle, batch_size: usize) -> Result<usize, String> {
+    log::info!("Streaming pdf list in batches of {batch_size}");
+
+    if batch_size == 0 {
+        return Err("Batch size must be at least 1".to_string());
+    }
+
+    let pdfs = load_pdf_list(&app_handle)?;
+    let total = pdfs.len();
+
+    for (index, entries) in pdfs.chunks(batch_size).enumerate() {
+        app_handle
+            .emit(
+                "pdf-list-batch",
+                PdfListBatch {
+                    offset: index * batch_size,
+                    entries: entries.to_vec(),
+                },
+            )
+            .map_err(|e| e.to_string())?;
+    }
+
+    app_handle
+        .emit("pdf-list-done", PdfListDone { total })
+        .map_err(|e| e.to_string())?;
+
+    Ok(total)
+}
+
+// With `keep_annotations` (or else the archive_removed_annotations setting) the annotations
+// are archived by content hash first, so registering the same document later can bring them back
+#[tauri::command]
+pub fn remove_pdf(
+    app_handle: tauri::AppHandle,
+    id: u64,
+    keep_annotations: Option<bool>,
+) -> Result<bool, String> {
     log::info!("Removing from pdf list {id}");
 
     let app_data_dir = app_handle
@@ -449,19 +1658,34 @@ pub fn remove_pdf(app_handle: tauri::AppHandle, id: u64) -> Result<bool, String>
 
     let state_path = app_data_dir.join("pdfs.json");
 
-    let mut pdfs: Vec<PdfEntry> = if state_path.exists() {
-        let data = fs::read_to_string(&state_path).map_err(|e| e.to_string())?;
-        serde_json::from_str::<Vec<PdfEntry>>(&data).map_err(|e| e.to_string())?
-    } else {
-        Vec::new()
-    };
+    let state = app_handle.state::<AppState>();
+    let keep_annotations =
+        keep_annotations.unwrap_or_else(|| state.settings().archive_removed_annotations);
+    if keep_annotations {
+        // strokes still queued for autosave belong in the archive too
+        flush_pending_strokes(&app_handle, id)?;
+    }
+
+    let lock = state.pdf_lock(id);
+    let _pdf_guard = lock.lock().unwrap();
+    let _registry_guard = state.registry_lock.lock().unwrap();
+
+    let mut pdfs = read_pdf_entries(&state_path)?;
 
     if let Ok(idx) = pdfs.binary_search_by(|pdf| pdf.id.cmp(&id)) {
-        pdfs.remove(idx);
+        let pdf_entry = pdfs.remove(idx);
 
         // recursive removal of subfolders and files
         let folder_name = format!("pdf_{id}");
         let folder_path = app_data_dir.join(folder_name);
+
+        if keep_annotations && folder_path.exists() {
+            archive_annotations(&app_data_dir, &pdf_entry, &folder_path)?;
+        }
+        state.pending_strokes.lock().unwrap().remove(&id);
+        // the id goes to the next pdf registered when this was the last one
+        forget_disk_usage(&app_handle, Some(id));
+
         if folder_path.exists() {
             fs::remove_dir_all(folder_path).map_err(|e| e.to_string())?;
             log::info!("Successfully removed folder{:?}", id);
@@ -470,9 +1694,7 @@ pub fn remove_pdf(app_handle: tauri::AppHandle, id: u64) -> Result<bool, String>
         }
 
         // remove from json config
-        fs::create_dir_all(app_data_dir).map_err(|e| e.to_string())?;
-        let serialized = serde_json::to_string_pretty(&pdfs).map_err(|e| e.to_string())?;
-        fs::write(&state_path, serialized).map_err(|e| e.to_string())?;
+        write_pdf_entries(&app_handle, &state_path, &pdfs)?;
         Ok(true)
     } else {
         Ok(false)
@@ -490,12 +1712,7 @@ pub async fn load_pdf(app_handle: tauri::AppHandle, id: u64) -> Result<LoadPdfRe
 
     let state_path = app_data_dir.join("pdfs.json");
 
-    let pdfs: Vec<PdfEntry> = if state_path.exists() {
-        let data = fs::read_to_string(&state_path).map_err(|e| e.to_string())?;
-        serde_json::from_str::<Vec<PdfEntry>>(&data).map_err(|e| e.to_string())?
-    } else {
-        Vec::new()
-    };
+    let pdfs = read_pdf_entries(&state_path)?;
 
     let pdf_entry = match pdfs.binary_search_by(|pdf| pdf.id.cmp(&id)) {
         Ok(index) => Ok(pdfs[index].clone()),
@@ -504,11 +1721,15 @@ pub async fn load_pdf(app_handle: tauri::AppHandle, id: u64) -> Result<LoadPdfRe
 
     let dims_path = app_data_dir.join(format!("pdf_{id}/dims.json"));
 
-    let data = fs::read_to_string(&dims_path).map_err(|e| e.to_string())?;
-    let pdf_pages_dims =
-        serde_json::from_str::<PdfPagesDimensions>(&data).map_err(|e| e.to_string())?;
+    let pdf_pages_dims = load_dims_from_file(&dims_path)?;
 
-    Ok(LoadPdfResponse::new(pdf_entry, pdf_pages_dims))
+    let active_profile = load_profiles(&app_data_dir.join(format!("pdf_{id}")))?.active;
+
+    Ok(LoadPdfResponse::new(
+        pdf_entry,
+        pdf_pages_dims,
+        active_profile,
+    ))
 }
 
 #[tauri::command]
@@ -516,10 +1737,17 @@ pub async fn save_pdf_strokes(
     app_handle: tauri::AppHandle,
     pdf_id: u32,
     page_id: u32,
-    stroke: Stroke,
-) -> Result<bool, String> {
+    mut stroke: Stroke,
+) -> Result<String, String> {
     log::info!("Saving pdf strokes: {pdf_id}");
 
+    let max_thickness = app_handle
+        .state::<AppState>()
+        .settings()
+        .max_stroke_thickness;
+    stroke.prepare(max_thickness)?;
+    let stroke_id = stroke.id.clone().unwrap_or_default();
+
     // This will handle platform specific app data directories
     let app_data_dir = app_handle
         .path()
@@ -530,102 +1758,767 @@ pub async fn save_pdf_strokes(
 
     // if cfg!(debug_assertions) {
     //     if let Some(parent) = strokes_path.parent() {
-    //         let _ = open_folder(parent);
+    //         let _ = open_path(parent);
     //     }
     // }
 
-    let mut strokes: PdfStrokes = if strokes_path.exists() {
-        let data = fs::read_to_string(&strokes_path).map_err(|e| e.to_string())?;
-        serde_json::from_str::<PdfStrokes>(&data).map_err(|e| e.to_string())?
+    merge_saved_stroke(
+        &app_handle.state::<AppState>(),
+        u64::from(pdf_id),
+        &strokes_path,
+        page_id,
+        stroke,
+        |path| Ok(load_strokes_from_file(&app_handle, path)?),
+        |path, strokes| write_json(&app_handle, path, strokes, FileKind::Strokes),
+    )?;
+
+    // the frontend keeps it to replace or erase the stroke later
+    Ok(stroke_id)
+}
+
+// Adds `stroke` to the strokes file at `strokes_path`, re-read under the pdf lock so
+// concurrent saves from other windows are merged, not clobbered. `load` and `save` read and
+// write the file, decrypting and encrypting it when the pdf has a password.
+fn merge_saved_stroke<L, S>(
+    state: &AppState,
+    pdf_id: u64,
+    strokes_path: &Path,
+    page_id: u32,
+    stroke: Stroke,
+    load: L,
+    save: S,
+) -> Result<(), String>
+where
+    L: FnOnce(&Path) -> Result<PdfStrokes, String>,
+    S: FnOnce(&Path, &PdfStrokes) -> Result<(), String>,
+{
+    let lock = state.pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    let mut strokes = load(strokes_path)?;
+    strokes.merge(page_id, stroke);
+    save(strokes_path, &strokes)
+}
+
+#[tauri::command]
+pub fn load_pdf_strokes(app_handle: tauri::AppHandle, pdf_id: u32) -> Result<PdfStrokes, AppError> {
+    log::info!("Loading pdf strokes: {pdf_id}");
+
+    // queued strokes would otherwise vanish when the page is reloaded
+    flush_pending_strokes(&app_handle, u64::from(pdf_id))?;
+
+    // This will handle platform specific app data directories
+    let app_data_dir = app_handle.path().app_data_dir()?;
+
+    let strokes_path = app_data_dir.join(format!("pdf_{pdf_id}/strokes.json"));
+
+    load_strokes_from_file(&app_handle, &strokes_path)
+}
+
+// Strokes of pages `start_page..=end_page` only, for readers that render a window of pages
+#[tauri::command]
+pub fn load_pdf_strokes_range(
+    app_handle: tauri::AppHandle,
+    pdf_id: u32,
+    start_page: u32,
+    end_page: u32,
+) -> Result<PdfStrokes, String> {
+    log::info!("Loading pdf strokes of pages {start_page}-{end_page}: {pdf_id}");
+
+    if start_page > end_page {
+        return Err(format!(
+            "Invalid page range: {start_page} is after {end_page}"
+        ));
+    }
+
+    let mut strokes = load_pdf_strokes(app_handle, pdf_id)?;
+    strokes
+        .inner
+        .retain(|page, _| (start_page..=end_page).contains(page));
+
+    Ok(strokes)
+}
+
+#[derive(Debug, Serialize)]
+pub struct ActivityEntry {
+    page: u32,
+    stroke_id: Option<String>,
+    tool: DrawingToolType,
+    color: String,
+    point_count: usize,
+    // 0 when the stroke predates timestamps
+    created_at: i64,
+}
+
+#[tauri::command]
+pub fn get_annotation_activity(
+    app_handle: tauri::AppHandle,
+    pdf_id: u64,
+) -> Result<Vec<ActivityEntry>, String> {
+    log::info!("Loading annotation activity: {pdf_id}");
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let strokes = load_strokes_from_file(
+        &app_handle,
+        &app_data_dir.join(format!("pdf_{pdf_id}/strokes.json")),
+    )?;
+
+    let mut activity: Vec<ActivityEntry> = strokes
+        .inner
+        .into_iter()
+        .flat_map(|(page, page_strokes)| {
+            page_strokes.into_iter().map(move |stroke| ActivityEntry {
+                page,
+                stroke_id: stroke.id,
+                tool: stroke.tool,
+                color: stroke.color,
+                point_count: stroke.path.len(),
+                created_at: stroke.created_at,
+            })
+        })
+        .collect();
+
+    // untimed strokes sort first, ties keep a stable page order
+    activity.sort_by_key(|entry| (entry.created_at, entry.page));
+
+    Ok(activity)
+}
+
+// Ink laid down by a stroke, its path length. A single tap still leaves a dot as wide as the
+// stroke, so the length never drops below the thickness.
+fn stroke_ink(stroke: &Stroke) -> f64 {
+    let length: f64 = stroke
+        .path
+        .windows(2)
+        .map(|pair| (pair[1].x - pair[0].x).hypot(pair[1].y - pair[0].y))
+        .sum();
+    length.max(stroke.thickness as f64)
+}
+
+// Ink per page relative to the most annotated page, in 0..=1. Pages without ink are left out.
+#[tauri::command]
+pub fn annotation_heatmap(
+    app_handle: tauri::AppHandle,
+    pdf_id: u64,
+) -> Result<HashMap<u32, f32>, String> {
+    log::info!("Computing annotation heatmap: {pdf_id}");
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let strokes = load_strokes_from_file(
+        &app_handle,
+        &app_data_dir.join(format!("pdf_{pdf_id}/strokes.json")),
+    )?;
+
+    // eraser strokes don't leave ink
+    let ink: HashMap<u32, f64> = strokes
+        .inner
+        .iter()
+        .map(|(page, page_strokes)| {
+            let total = page_strokes
+                .iter()
+                .filter(|stroke| !matches!(stroke.tool, DrawingToolType::Eraser))
+                .map(stroke_ink)
+                .sum();
+            (*page, total)
+        })
+        .filter(|(_, total)| *total > 0.0)
+        .collect();
+
+    let max = ink.values().copied().fold(0.0, f64::max);
+    if max <= 0.0 {
+        return Ok(HashMap::new());
+    }
+
+    Ok(ink
+        .into_iter()
+        .map(|(page, total)| (page, (total / max) as f32))
+        .collect())
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct AnnotationSummary {
+    has_strokes: bool,
+    has_bookmarks: bool,
+    stroke_count: usize,
+    bookmark_count: usize,
+    // annotations are encrypted and not unlocked this session, the counts are unknown
+    locked: bool,
+}
+
+// Annotation badges for every pdf in the library in one pass. Strokes still queued for
+// autosave are counted too. Files that are missing or unreadable count as empty.
+#[tauri::command]
+pub fn pdfs_with_annotations(
+    app_handle: tauri::AppHandle,
+) -> Result<HashMap<u64, AnnotationSummary>, String> {
+    log::info!("Summarizing annotations of the library");
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let pdfs = read_pdf_entries(&app_data_dir.join("pdfs.json"))?;
+    let state = app_handle.state::<AppState>();
+
+    let mut summaries = HashMap::with_capacity(pdfs.len());
+    for pdf in pdfs {
+        let folder_path = app_data_dir.join(format!("pdf_{}", pdf.id));
+        let mut summary = AnnotationSummary::default();
+
+        match load_strokes_from_file(&app_handle, &folder_path.join("strokes.json")) {
+            Ok(strokes) => summary.stroke_count = strokes.inner.values().map(Vec::len).sum(),
+            Err(AppError::Locked(_)) => summary.locked = true,
+            Err(e) => log::warn!("Skipping unreadable strokes of pdf {}: {e}", pdf.id),
+        }
+        summary.stroke_count += state
+            .pending_strokes
+            .lock()
+            .unwrap()
+            .get(&pdf.id)
+            .map_or(0, Vec::len);
+
+        match load_bookmarks_from_file(&app_handle, &folder_path.join("bookmarks.json")) {
+            Ok(bookmarks) => summary.bookmark_count = bookmarks.len(),
+            Err(AppError::Locked(_)) => summary.locked = true,
+            Err(e) => log::warn!("Skipping unreadable bookmarks of pdf {}: {e}", pdf.id),
+        }
+
+        summary.has_strokes = summary.stroke_count > 0;
+        summary.has_bookmarks = summary.bookmark_count > 0;
+        summaries.insert(pdf.id, summary);
+    }
+
+    Ok(summaries)
+}
+
+#[tauri::command]
+pub fn load_thumbnails(
+    app_handle: tauri::AppHandle,
+    pdf_id: u32,
+) -> Result<PdfPagesThumbnails, String> {
+    log::info!("Loading pdf thumbnails: {pdf_id}");
+
+    // This will handle platform specific app data directories
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let thumbnails_path = app_data_dir.join(format!("pdf_{pdf_id}/thumbs.json"));
+
+    load_thumbnails_from_file(&thumbnails_path)
+}
+
+// Placement of the page in each thumbnail, empty for thumbnails rendered before layouts
+// were recorded
+#[tauri::command]
+pub fn load_thumbnail_layouts(
+    app_handle: tauri::AppHandle,
+    pdf_id: u64,
+) -> Result<PdfThumbnailLayouts, String> {
+    log::info!("Loading pdf thumbnail layouts: {pdf_id}");
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let layouts_path = app_data_dir.join(format!("pdf_{pdf_id}/thumb_layouts.json"));
+    if !layouts_path.exists() {
+        return Ok(PdfThumbnailLayouts::default());
+    }
+
+    let data = fs::read_to_string(&layouts_path).map_err(|e| e.to_string())?;
+    serde_json::from_str::<PdfThumbnailLayouts>(&data).map_err(|e| e.to_string())
+}
+
+// Pages of 1..=total_pages without a thumbnail yet, every page when thumbs.json is absent
+#[tauri::command]
+pub fn missing_thumbnail_pages(
+    app_handle: tauri::AppHandle,
+    pdf_id: u64,
+    total_pages: u32,
+) -> Result<Vec<u32>, String> {
+    log::info!("Listing pages without thumbnails: {pdf_id}");
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let thumbnails_path = app_data_dir.join(format!("pdf_{pdf_id}/thumbs.json"));
+    let thumbnails = load_thumbnails_from_file(&thumbnails_path)?;
+
+    Ok((1..=total_pages)
+        .filter(|page| !thumbnails.inner.contains_key(page))
+        .collect())
+}
+
+#[tauri::command]
+pub fn rename_pdf(
+    app_handle: tauri::AppHandle,
+    id: u64,
+    name: String,
+    update_export_name: Option<bool>,
+) -> Result<bool, String> {
+    log::info!("Loading pdf for renaming: {id}");
+
+    // exports keep their previous name unless asked to follow the new one
+    let export_name = update_export_name
+        .unwrap_or(false)
+        .then(|| export_file_name(&name));
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let state_path = app_data_dir.join("pdfs.json");
+
+    let state = app_handle.state::<AppState>();
+    let _guard = state.registry_lock.lock().unwrap();
+
+    let mut pdfs = read_pdf_entries(&state_path)?;
+
+    match pdfs.binary_search_by(|pdf| pdf.id.cmp(&id)) {
+        Ok(index) => {
+            let pdf = &mut pdfs[index];
+            match export_name {
+                Some(export_name) => pdf.export_name = Some(export_name),
+                // an unset export name follows file_name, so the old one is pinned first
+                None => pdf.export_name = Some(pdf.export_file_name()),
+            }
+            pdf.file_name = name;
+            Ok(())
+        }
+        Err(_) => Err(format!("PDF with id {id} not found")),
+    }?;
+
+    write_pdf_entries(&app_handle, &state_path, &pdfs)?;
+
+    Ok(true)
+}
+
+// Modification time in ms since epoch, None when the file is gone or the platform has none
+pub(crate) fn file_mtime(path: &Path) -> Option<i64> {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    Some(DateTime::<Local>::from(modified).timestamp_millis())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OriginalState {
+    Unchanged,
+    // edited since it was copied, refresh_clone picks the changes up
+    Modified,
+    // moved or deleted, relink_original points the entry at its new location
+    Missing,
+    // no modification time was recorded at registration, nothing to compare against
+    Unknown,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OriginalStatus {
+    pdf_id: u64,
+    original_path: String,
+    exists: bool,
+    modified_at: Option<i64>,
+    recorded_mtime: Option<i64>,
+    state: OriginalState,
+}
+
+// Compares the original's modification time with the one recorded when it was last copied,
+// cheap enough to run on every open unlike re-hashing the file
+#[tauri::command]
+pub fn original_file_status(app_handle: AppHandle, pdf_id: u64) -> Result<OriginalStatus, String> {
+    log::info!("Checking original file of pdf {pdf_id}");
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let pdf_entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+    let original_path = Path::new(&pdf_entry.original_path);
+    // generated documents have no original, their path is empty
+    let exists = !pdf_entry.original_path.is_empty() && original_path.is_file();
+    let modified_at = if exists {
+        file_mtime(original_path)
     } else {
-        PdfStrokes::new()
+        None
     };
 
-    strokes.insert(page_id, stroke);
+    let state = match (exists, pdf_entry.original_mtime, modified_at) {
+        (false, _, _) => OriginalState::Missing,
+        (true, Some(recorded), Some(current)) if recorded != current => OriginalState::Modified,
+        (true, Some(_), Some(_)) => OriginalState::Unchanged,
+        (true, _, _) => OriginalState::Unknown,
+    };
 
-    // Save
-    fs::create_dir_all(app_data_dir).map_err(|e| e.to_string())?;
-    let serialized = serde_json::to_string_pretty(&strokes).map_err(|e| e.to_string())?;
-    fs::write(&strokes_path, serialized).map_err(|e| e.to_string())?;
+    Ok(OriginalStatus {
+        pdf_id,
+        original_path: pdf_entry.original_path,
+        exists,
+        modified_at,
+        recorded_mtime: pdf_entry.original_mtime,
+        state,
+    })
+}
 
-    Ok(true)
+#[derive(Debug, Serialize)]
+pub struct RelinkResult {
+    original_path: String,
+    hash_matched: bool,
 }
 
 #[tauri::command]
-pub fn load_pdf_strokes(app_handle: tauri::AppHandle, pdf_id: u32) -> Result<PdfStrokes, String> {
-    log::info!("Loading pdf strokes: {pdf_id}");
+pub fn relink_original(
+    app_handle: tauri::AppHandle,
+    pdf_id: u64,
+    new_path: String,
+) -> Result<RelinkResult, String> {
+    log::info!("Relinking original of pdf {pdf_id} to {new_path}");
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let pdf_entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+
+    let pdfium = app_handle.state::<AppState>().pdfium()?;
+    pdfium
+        .load_pdf_from_file(&new_path, None)
+        .map_err(|e| format!("Not a readable PDF: {e}"))?;
+
+    // entries registered before hashing are compared against their clone instead
+    let expected_hash = match pdf_entry.content_hash {
+        Some(hash) => hash,
+        None => hash_file(Path::new(&pdf_entry.clone_path))?,
+    };
+    let hash_matched = hash_file(Path::new(&new_path))? == expected_hash;
+
+    if !hash_matched {
+        log::warn!("Relinked original of pdf {pdf_id} doesn't match its registered content");
+    }
+
+    update_pdf_entry(&app_handle, pdf_id, |entry| {
+        entry.original_path = new_path.clone();
+        entry.original_mtime = file_mtime(Path::new(&new_path));
+    })?;
+
+    Ok(RelinkResult {
+        original_path: new_path,
+        hash_matched,
+    })
+}
+
+// Drops the page-derived sidecars of a pdf whose clone changed, then re-renders the cover,
+// dims and thumbnails in the background like registration does.
+pub(crate) fn rebuild_derived_data(
+    app_handle: &AppHandle,
+    pdf_entry: &PdfEntry,
+    folder_path: &Path,
+) -> Result<(), String> {
+    for dir in ["thumbnails", "annotated_thumbnails", "renders"] {
+        let thumbs_dir = folder_path.join(dir);
+        if thumbs_dir.exists() {
+            fs::remove_dir_all(&thumbs_dir).map_err(|e| e.to_string())?;
+        }
+    }
+    for sidecar in [
+        "thumbs.json",
+        "thumb_layouts.json",
+        "annotated_thumbs.json",
+        "dims.json",
+        "text.json",
+        "text_stats.json",
+    ] {
+        let sidecar_path = folder_path.join(sidecar);
+        if sidecar_path.exists() {
+            fs::remove_file(&sidecar_path).map_err(|e| e.to_string())?;
+        }
+    }
+
+    spawn_preview_rendering(app_handle, pdf_entry, folder_path, true)
+}
+
+// Re-renders the cover and thumbnails of a pdf whose clone is unchanged, e.g. after their
+// files went missing. Page sizes and the text cache are kept.
+pub(crate) fn regenerate_previews(
+    app_handle: &AppHandle,
+    pdf_entry: &PdfEntry,
+    folder_path: &Path,
+) -> Result<(), String> {
+    let thumbs_dir = folder_path.join("thumbnails");
+    if thumbs_dir.exists() {
+        fs::remove_dir_all(&thumbs_dir).map_err(|e| e.to_string())?;
+    }
+    for sidecar in ["thumbs.json", "thumb_layouts.json"] {
+        let sidecar_path = folder_path.join(sidecar);
+        if sidecar_path.exists() {
+            fs::remove_file(&sidecar_path).map_err(|e| e.to_string())?;
+        }
+    }
+
+    spawn_preview_rendering(app_handle, pdf_entry, folder_path, false)
+}
+
+// Renders the cover, thumbnails and (with `dims`) page sizes in the background
+fn spawn_preview_rendering(
+    app_handle: &AppHandle,
+    pdf_entry: &PdfEntry,
+    folder_path: &Path,
+    dims: bool,
+) -> Result<(), String> {
+    let state = app_handle.state::<AppState>();
+    let pdfium = state.pdfium()?;
+    let max_thumbnails = state.settings().max_thumbnails;
+    let app_handle = app_handle.clone();
+    let pdf_id = pdf_entry.id;
+    // a custom cover that went missing falls back to the first page
+    let custom_cover = pdf_entry.custom_cover && Path::new(&pdf_entry.cover_path).exists();
+    // so does a picked page the document no longer has
+    let cover_page = pdf_entry
+        .cover_page
+        .filter(|&page| page >= 1 && pdf_entry.page_count.is_none_or(|count| page <= count));
+    let clone_path = pdf_entry.clone_path.clone();
+    let folder_path = folder_path.to_path_buf();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app_handle.state::<AppState>();
+        let _slot = acquire_extraction_slot(&app_handle, &state, pdf_id);
+
+        // a cover the user supplied doesn't depend on the clone's pages
+        if !custom_cover {
+            let cover_dpi = state.settings().cover_dpi;
+            let page_index = cover_page.map_or(0, |page| (page - 1) as PdfPageIndex);
+            let cover = render_cover(
+                &pdfium,
+                &clone_path,
+                &folder_path,
+                pdf_id,
+                page_index,
+                cover_dpi,
+            )
+            .and_then(|cover_path| {
+                replace_cover(&app_handle, pdf_id, cover_path, false, cover_page)
+            });
+            if let Err(e) = cover {
+                log::error!("Failed to regenerate cover of pdf {pdf_id}: {e}");
+            }
+        }
+
+        if let Err(e) = extract_pdf_data(
+            &app_handle,
+            &pdfium,
+            &clone_path,
+            &folder_path,
+            ExtractOptions {
+                pdf_id,
+                thumbnail: true,
+                dims,
+                max_thumbnails,
+            },
+        ) {
+            log::error!("Failed to extract data of pdf {pdf_id}: {e}");
+        }
+    });
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn refresh_clone(
+    app_handle: tauri::AppHandle,
+    pdf_id: u64,
+    confirm_page_count_change: Option<bool>,
+) -> Result<u32, String> {
+    log::info!("Refreshing clone of pdf {pdf_id} from its original");
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let pdf_entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+    if !Path::new(&pdf_entry.original_path).exists() {
+        return Err(format!(
+            "Original file not found: {}",
+            pdf_entry.original_path
+        ));
+    }
+
+    let pdfium = app_handle.state::<AppState>().pdfium()?;
+    let old_page_count = pdfium
+        .load_pdf_from_file(&pdf_entry.clone_path, None)
+        .map(|document| document.pages().len() as u32)
+        .ok();
+    let new_page_count = pdfium
+        .load_pdf_from_file(&pdf_entry.original_path, None)
+        .map_err(|e| format!("Original is not a readable PDF: {e}"))?
+        .pages()
+        .len() as u32;
+
+    // bookmarks and strokes are keyed by page, a different page count may misalign them
+    if let Some(old_page_count) = old_page_count {
+        if old_page_count != new_page_count && !confirm_page_count_change.unwrap_or(false) {
+            return Err(format!(
+                "page_count_changed: {old_page_count} -> {new_page_count}, confirm to refresh anyway"
+            ));
+        }
+    }
+
+    let folder_path = app_data_dir.join(format!("pdf_{pdf_id}"));
+    // read before copying, an edit made during the copy shows up as a later change
+    let original_mtime = file_mtime(Path::new(&pdf_entry.original_path));
+    {
+        let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+        let _guard = lock.lock().unwrap();
+        fs::copy(&pdf_entry.original_path, &pdf_entry.clone_path).map_err(|e| e.to_string())?;
+    }
+
+    let content_hash = hash_file(Path::new(&pdf_entry.clone_path))?;
+    let pdf_entry = update_pdf_entry(&app_handle, pdf_id, |entry| {
+        entry.clone_hash = Some(content_hash.clone());
+        entry.content_hash = Some(content_hash);
+        entry.page_count = Some(new_page_count);
+        entry.original_mtime = original_mtime;
+    })?;
+
+    rebuild_derived_data(&app_handle, &pdf_entry, &folder_path)?;
+
+    Ok(new_page_count)
+}
+
+#[tauri::command]
+pub fn set_cover_from_page(
+    app_handle: tauri::AppHandle,
+    pdf_id: u64,
+    page_number: u32,
+) -> Result<String, String> {
+    log::info!("Setting cover of pdf {pdf_id} to page {page_number}");
+
+    let page_count = pdf_page_count(&app_handle, pdf_id)?;
+    if page_number == 0 || page_number > page_count {
+        return Err(format!(
+            "Page {page_number} is out of range (1-{page_count})"
+        ));
+    }
 
-    // This will handle platform specific app data directories
     let app_data_dir = app_handle
         .path()
         .app_data_dir()
         .map_err(|e| e.to_string())?;
 
-    let strokes_path = app_data_dir.join(format!("pdf_{pdf_id}/strokes.json"));
-
-    let strokes: PdfStrokes = if strokes_path.exists() {
-        let data = fs::read_to_string(&strokes_path).map_err(|e| e.to_string())?;
-        serde_json::from_str::<PdfStrokes>(&data).map_err(|e| e.to_string())?
-    } else {
-        PdfStrokes::new()
-    };
-
-    Ok(strokes)
+    let pdf_entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+    let folder_path = app_data_dir.join(format!("pdf_{pdf_id}"));
+    let state = app_handle.state::<AppState>();
+    let pdfium = state.pdfium()?;
+
+    let cover_path = render_cover(
+        &pdfium,
+        &pdf_entry.clone_path,
+        &folder_path,
+        pdf_id,
+        (page_number - 1) as PdfPageIndex,
+        state.settings().cover_dpi,
+    )?;
+
+    replace_cover(
+        &app_handle,
+        pdf_id,
+        cover_path.clone(),
+        false,
+        Some(page_number),
+    )?;
+    Ok(cover_path)
 }
 
+// Largest side of a custom cover, page covers are half the page size which is about this
+const CUSTOM_COVER_MAX_SIDE: u32 = 600;
+
 #[tauri::command]
-pub fn load_thumbnails(
+pub fn set_custom_cover(
     app_handle: tauri::AppHandle,
-    pdf_id: u32,
-) -> Result<PdfPagesThumbnails, String> {
-    log::info!("Loading pdf thumbnails: {pdf_id}");
+    pdf_id: u64,
+    image_path: String,
+) -> Result<String, String> {
+    log::info!("Setting custom cover of pdf {pdf_id} from {image_path}");
+
+    // decoding is the validation, anything image can't read isn't a usable cover
+    let image = image::open(&image_path).map_err(|e| format!("Not a readable image: {e}"))?;
 
-    // This will handle platform specific app data directories
     let app_data_dir = app_handle
         .path()
         .app_data_dir()
         .map_err(|e| e.to_string())?;
 
-    let thumbnails_path = app_data_dir.join(format!("pdf_{pdf_id}/thumbs.json"));
+    // fails early for unknown ids, before anything is written
+    find_pdf_entry(&app_data_dir, pdf_id)?;
+    let folder_path = app_data_dir.join(format!("pdf_{pdf_id}"));
 
-    let thumbnails: PdfPagesThumbnails = if thumbnails_path.exists() {
-        let data = fs::read_to_string(&thumbnails_path).map_err(|e| e.to_string())?;
-        serde_json::from_str::<PdfPagesThumbnails>(&data).map_err(|e| e.to_string())?
+    let image = if image.width() > CUSTOM_COVER_MAX_SIDE || image.height() > CUSTOM_COVER_MAX_SIDE {
+        image.resize(
+            CUSTOM_COVER_MAX_SIDE,
+            CUSTOM_COVER_MAX_SIDE,
+            image::imageops::FilterType::Triangle,
+        )
     } else {
-        PdfPagesThumbnails::new()
+        image
     };
 
-    Ok(thumbnails)
+    let timestamp = file_timestamp();
+    let cover_path = folder_path.join(format!("{pdf_id}_cover_{timestamp}.jpg"));
+
+    // jpeg has no alpha channel, transparent images are flattened
+    image::DynamicImage::ImageRgb8(image.to_rgb8())
+        .save(&cover_path)
+        .map_err(|e| e.to_string())?;
+
+    let cover_path = cover_path.to_str().unwrap().to_string();
+    if let Err(e) = replace_cover(&app_handle, pdf_id, cover_path.clone(), true, None) {
+        let _ = fs::remove_file(&cover_path);
+        return Err(e);
+    }
+
+    Ok(cover_path)
 }
 
 #[tauri::command]
-pub fn rename_pdf(app_handle: tauri::AppHandle, id: u64, name: String) -> Result<bool, String> {
-    log::info!("Loading pdf for renaming: {id}");
+pub fn open_pdf_externally(
+    app_handle: tauri::AppHandle,
+    pdf_id: u64,
+    use_original: bool,
+) -> Result<(), String> {
+    log::info!("Opening pdf {pdf_id} externally (original: {use_original})");
 
     let app_data_dir = app_handle
         .path()
         .app_data_dir()
         .map_err(|e| e.to_string())?;
 
-    let state_path = app_data_dir.join("pdfs.json");
-
-    let mut pdfs: Vec<PdfEntry> = if state_path.exists() {
-        let data = fs::read_to_string(&state_path).map_err(|e| e.to_string())?;
-        serde_json::from_str::<Vec<PdfEntry>>(&data).map_err(|e| e.to_string())?
+    let pdf_entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+    let path = if use_original {
+        pdf_entry.original_path
     } else {
-        Vec::new()
+        pdf_entry.clone_path
     };
 
-    match pdfs.binary_search_by(|pdf| pdf.id.cmp(&id)) {
-        Ok(index) => Ok(pdfs[index].file_name = name),
-        Err(_) => Err(format!("PDF with id {id} not found")),
-    }?;
-
-    fs::create_dir_all(app_data_dir).map_err(|e| e.to_string())?;
-    let serialized = serde_json::to_string_pretty(&pdfs).map_err(|e| e.to_string())?;
-    fs::write(&state_path, serialized).map_err(|e| e.to_string())?;
+    if !Path::new(&path).exists() {
+        return Err(format!("File not found: {path}"));
+    }
 
-    Ok(true)
+    open_path(Path::new(&path))
 }
 
 #[tauri::command]
@@ -642,9 +2535,12 @@ pub async fn save_editor_settings(
 
     let settings_path = app_data_dir.join(format!("pdf_{:?}/editor.json", props.id));
 
-    fs::create_dir_all(app_data_dir).map_err(|e| e.to_string())?;
-    let serialized = serde_json::to_string_pretty(&props).map_err(|e| e.to_string())?;
-    fs::write(&settings_path, serialized).map_err(|e| e.to_string())?;
+    write_json(
+        &app_handle,
+        &settings_path,
+        &props,
+        FileKind::EditorSettings,
+    )?;
 
     Ok(true)
 }
@@ -667,14 +2563,79 @@ pub fn load_editor_settings(
         let data = fs::read_to_string(&settings_path).map_err(|e| e.to_string())?;
         serde_json::from_str::<PdfEditorSyncProps>(&data).map_err(|e| e.to_string())?
     } else {
-        PdfEditorSyncProps::default()
+        default_editor_settings(&app_handle, id)
     };
 
     Ok(settings)
 }
 
+// Editor settings of a pdf without editor.json, with the pen colors from the app settings
+fn default_editor_settings(app_handle: &AppHandle, id: u64) -> PdfEditorSyncProps {
+    let defaults = app_handle.state::<AppState>().settings();
+    PdfEditorSyncProps {
+        id,
+        pen_color: defaults.default_pen_color,
+        highlighter_color: defaults.default_highlighter_color,
+        ..PdfEditorSyncProps::default()
+    }
+}
+
+// Drops a pdf's tool and view settings. `to_global` writes the current defaults into
+// editor.json, pinning them even if the app defaults change later; otherwise editor.json is
+// removed and the pdf follows the app defaults. Returns the settings the pdf now loads with.
+#[tauri::command]
+pub fn reset_editor_settings(
+    app_handle: tauri::AppHandle,
+    pdf_id: u64,
+    to_global: bool,
+) -> Result<PdfEditorSyncProps, String> {
+    log::info!("Resetting pdf editor settings: {pdf_id} (to global: {to_global})");
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let settings_path = app_data_dir.join(format!("pdf_{pdf_id}/editor.json"));
+    let settings = default_editor_settings(&app_handle, pdf_id);
+
+    if to_global {
+        write_json(
+            &app_handle,
+            &settings_path,
+            &settings,
+            FileKind::EditorSettings,
+        )?;
+    } else if settings_path.exists() {
+        fs::remove_file(&settings_path).map_err(|e| e.to_string())?;
+    }
+
+    Ok(settings)
+}
+
+pub(crate) fn pdf_page_count(app_handle: &AppHandle, pdf_id: u64) -> Result<u32, String> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let pdf_entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+    let pdfium = app_handle.state::<AppState>().pdfium()?;
+    let document = pdfium
+        .load_pdf_from_file(&pdf_entry.clone_path, None)
+        .map_err(|e| e.to_string())?;
+
+    Ok(document.pages().len() as u32)
+}
+
+#[tauri::command]
+pub fn get_page_count(app_handle: AppHandle, pdf_id: u64) -> Result<u32, String> {
+    log::info!("Counting pages of pdf {pdf_id}");
+    pdf_page_count(&app_handle, pdf_id)
+}
+
 // Bookmarks
-fn get_bookmarks_path(app_handle: &AppHandle, pdf_id: u64) -> Result<PathBuf, String> {
+pub(crate) fn get_bookmarks_path(app_handle: &AppHandle, pdf_id: u64) -> Result<PathBuf, String> {
     let app_data_dir = app_handle
         .path()
         .app_data_dir()
@@ -682,33 +2643,73 @@ fn get_bookmarks_path(app_handle: &AppHandle, pdf_id: u64) -> Result<PathBuf, St
     Ok(app_data_dir.join(format!("pdf_{pdf_id}/bookmarks.json")))
 }
 
-fn load_bookmarks_from_file(path: &PathBuf) -> Result<PdfBookmarks, String> {
-    if !path.exists() {
+pub(crate) fn load_bookmarks_from_file(
+    app_handle: &AppHandle,
+    path: &Path,
+) -> Result<PdfBookmarks, AppError> {
+    let Some(data) = read_data_file(app_handle, path)? else {
         return Ok(vec![]);
-    }
-
-    let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    };
     if data.trim().is_empty() {
         return Ok(vec![]);
     }
 
-    serde_json::from_str::<PdfBookmarks>(&data).map_err(|e| format!("Invalid JSON: {e}"))
+    serde_json::from_str::<PdfBookmarks>(&data)
+        .map_err(|e| AppError::Parse(format!("Invalid JSON: {e}")))
 }
 
-fn save_bookmarks_to_file(path: &PathBuf, bookmarks: &PdfBookmarks) -> Result<(), String> {
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+fn validate_end_page(
+    app_handle: &AppHandle,
+    pdf_id: u64,
+    page_number: u32,
+    end_page: Option<u32>,
+) -> Result<(), String> {
+    let Some(end_page) = end_page else {
+        return Ok(());
+    };
+
+    if end_page < page_number {
+        return Err(format!(
+            "End page {end_page} cannot be before start page {page_number}"
+        ));
+    }
+
+    let page_count = pdf_page_count(app_handle, pdf_id)?;
+    if end_page > page_count {
+        return Err(format!(
+            "End page {end_page} is out of range (document has {page_count} pages)"
+        ));
     }
-    let data = serde_json::to_string_pretty(bookmarks).map_err(|e| e.to_string())?;
-    fs::write(path, data).map_err(|e| e.to_string())
+
+    Ok(())
+}
+
+// page ascending, label breaking ties so the order is the same on every read
+pub(crate) fn sort_bookmarks(bookmarks: &mut PdfBookmarks) {
+    bookmarks.sort_by(|a, b| {
+        a.page_number
+            .cmp(&b.page_number)
+            .then_with(|| a.label.cmp(&b.label))
+    });
+}
+
+pub(crate) fn save_bookmarks_to_file(
+    app_handle: &AppHandle,
+    path: &PathBuf,
+    bookmarks: &PdfBookmarks,
+) -> Result<(), String> {
+    write_json(app_handle, path, bookmarks, FileKind::Bookmarks)
 }
 
 #[tauri::command]
-pub fn get_pdf_bookmarks(app_handle: AppHandle, pdf_id: u64) -> Result<PdfBookmarks, String> {
+pub fn get_pdf_bookmarks(app_handle: AppHandle, pdf_id: u64) -> Result<PdfBookmarks, AppError> {
     log::info!("Loading bookmarks for PDF {pdf_id}");
 
     let path = get_bookmarks_path(&app_handle, pdf_id)?;
-    load_bookmarks_from_file(&path)
+    let mut bookmarks = load_bookmarks_from_file(&app_handle, &path)?;
+    sort_bookmarks(&mut bookmarks);
+
+    Ok(bookmarks)
 }
 
 #[tauri::command]
@@ -717,6 +2718,7 @@ pub fn add_pdf_bookmark(
     pdf_id: u64,
     page_number: u32,
     label: String,
+    end_page: Option<u32>,
 ) -> Result<PdfBookmarks, String> {
     log::info!("Adding bookmark to PDF {pdf_id} - page {page_number}");
 
@@ -724,13 +2726,25 @@ pub fn add_pdf_bookmark(
         return Err("Label cannot be empty".to_string());
     }
 
+    // a range ending on its own start page is just a single page
+    let end_page = end_page.filter(|end| *end != page_number);
+    validate_end_page(&app_handle, pdf_id, page_number, end_page)?;
+
+    // under the pdf lock so a concurrent change to the bookmarks isn't overwritten
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
     let path = get_bookmarks_path(&app_handle, pdf_id)?;
-    let mut bookmarks = load_bookmarks_from_file(&path)?;
+    let mut bookmarks = load_bookmarks_from_file(&app_handle, &path)?;
 
-    let new_bookmark = PdfBookmark { page_number, label };
+    let new_bookmark = PdfBookmark {
+        page_number,
+        label,
+        end_page,
+    };
 
     bookmarks.push(new_bookmark);
-    save_bookmarks_to_file(&path, &bookmarks)?;
+    save_bookmarks_to_file(&app_handle, &path, &bookmarks)?;
 
     Ok(bookmarks)
 }
@@ -741,11 +2755,17 @@ pub fn update_pdf_bookmark(
     pdf_id: u64,
     label: Option<String>,
     page_number: u32,
+    end_page: Option<u32>,
 ) -> Result<PdfBookmarks, String> {
     log::info!("Updating bookmark {page_number} in PDF {pdf_id}");
 
+    validate_end_page(&app_handle, pdf_id, page_number, end_page)?;
+
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
     let path = get_bookmarks_path(&app_handle, pdf_id)?;
-    let mut bookmarks = load_bookmarks_from_file(&path)?;
+    let mut bookmarks = load_bookmarks_from_file(&app_handle, &path)?;
 
     if let Some(bm) = bookmarks.iter_mut().find(|b| b.page_number == page_number) {
         if let Some(lbl) = label {
@@ -754,11 +2774,15 @@ pub fn update_pdf_bookmark(
             }
             bm.label = lbl;
         }
+        // passing the start page as end page turns a range back into a single page
+        if let Some(end) = end_page {
+            bm.end_page = (end != page_number).then_some(end);
+        }
     } else {
         return Err(format!("Bookmark with id {page_number} not found"));
     }
 
-    save_bookmarks_to_file(&path, &bookmarks)?;
+    save_bookmarks_to_file(&app_handle, &path, &bookmarks)?;
     Ok(bookmarks)
 }
 
@@ -770,8 +2794,11 @@ pub fn delete_pdf_bookmark(
 ) -> Result<PdfBookmarks, String> {
     log::info!("Deleting bookmark {page_number} in PDF {pdf_id}");
 
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
     let path = get_bookmarks_path(&app_handle, pdf_id)?;
-    let mut bookmarks = load_bookmarks_from_file(&path)?;
+    let mut bookmarks = load_bookmarks_from_file(&app_handle, &path)?;
 
     let before_len = bookmarks.len();
     bookmarks.retain(|b| b.page_number != page_number);
@@ -780,6 +2807,563 @@ pub fn delete_pdf_bookmark(
         return Err(format!("Bookmark with id {page_number} not found"));
     }
 
-    save_bookmarks_to_file(&path, &bookmarks)?;
+    save_bookmarks_to_file(&app_handle, &path, &bookmarks)?;
+    Ok(bookmarks)
+}
+
+#[tauri::command]
+pub fn next_bookmark(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    from_page: u32,
+) -> Result<Option<PdfBookmark>, String> {
+    log::info!("Finding next bookmark after page {from_page} in PDF {pdf_id}");
+
+    let path = get_bookmarks_path(&app_handle, pdf_id)?;
+    let mut bookmarks = load_bookmarks_from_file(&app_handle, &path)?;
+    sort_bookmarks(&mut bookmarks);
+
+    Ok(bookmarks.into_iter().find(|b| b.page_number > from_page))
+}
+
+#[tauri::command]
+pub fn previous_bookmark(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    from_page: u32,
+) -> Result<Option<PdfBookmark>, String> {
+    log::info!("Finding previous bookmark before page {from_page} in PDF {pdf_id}");
+
+    let path = get_bookmarks_path(&app_handle, pdf_id)?;
+    let mut bookmarks = load_bookmarks_from_file(&app_handle, &path)?;
+    sort_bookmarks(&mut bookmarks);
+
+    // the closest page wins, and on a shared page the first one in sorted order
+    let closest_page = bookmarks
+        .iter()
+        .map(|b| b.page_number)
+        .filter(|page| *page < from_page)
+        .max();
+
+    Ok(closest_page.and_then(|page| bookmarks.into_iter().find(|b| b.page_number == page)))
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum BookmarkProblem {
+    // pages are numbered from 1
+    PageZero,
+    PageOutOfRange,
+    EndOutOfRange,
+    // a range ending before (or on) its own start
+    EndBeforeStart,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BookmarkIssue {
+    bookmark: PdfBookmark,
+    problem: BookmarkProblem,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BookmarkFixStrategy {
+    // move pages into range and drop broken ranges, keeping every bookmark
+    Clamp,
+    // delete every bookmark with a problem
+    Remove,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BookmarkFixReport {
+    // the bookmarks as they were before the fix
+    fixed: Vec<BookmarkIssue>,
+    removed: usize,
+    bookmarks: PdfBookmarks,
+}
+
+// The first problem of a bookmark in a document of `page_count` pages, the start page
+// is checked before the range
+fn bookmark_problem(bookmark: &PdfBookmark, page_count: u32) -> Option<BookmarkProblem> {
+    if bookmark.page_number == 0 {
+        return Some(BookmarkProblem::PageZero);
+    }
+    if bookmark.page_number > page_count {
+        return Some(BookmarkProblem::PageOutOfRange);
+    }
+    match bookmark.end_page {
+        Some(end) if end <= bookmark.page_number => Some(BookmarkProblem::EndBeforeStart),
+        Some(end) if end > page_count => Some(BookmarkProblem::EndOutOfRange),
+        _ => None,
+    }
+}
+
+fn bookmark_issues(bookmarks: &PdfBookmarks, page_count: u32) -> Vec<BookmarkIssue> {
+    bookmarks
+        .iter()
+        .filter_map(|bookmark| {
+            bookmark_problem(bookmark, page_count).map(|problem| BookmarkIssue {
+                bookmark: bookmark.clone(),
+                problem,
+            })
+        })
+        .collect()
+}
+
+// Bookmarks pointing outside the document, e.g. after pages were deleted. Read-only, see
+// fix_bookmarks.
+#[tauri::command]
+pub fn validate_bookmarks(
+    app_handle: AppHandle,
+    pdf_id: u64,
+) -> Result<Vec<BookmarkIssue>, String> {
+    log::info!("Validating bookmarks of PDF {pdf_id}");
+
+    let page_count = pdf_page_count(&app_handle, pdf_id)?;
+    let path = get_bookmarks_path(&app_handle, pdf_id)?;
+    let mut bookmarks = load_bookmarks_from_file(&app_handle, &path)?;
+    sort_bookmarks(&mut bookmarks);
+
+    Ok(bookmark_issues(&bookmarks, page_count))
+}
+
+// Resolves what validate_bookmarks reports with `strategy`. The file is only rewritten when
+// something changed.
+#[tauri::command]
+pub fn fix_bookmarks(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    strategy: BookmarkFixStrategy,
+) -> Result<BookmarkFixReport, String> {
+    log::info!("Fixing bookmarks of PDF {pdf_id} ({strategy:?})");
+
+    let page_count = pdf_page_count(&app_handle, pdf_id)?;
+    let path = get_bookmarks_path(&app_handle, pdf_id)?;
+
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    let mut bookmarks = load_bookmarks_from_file(&app_handle, &path)?;
+    sort_bookmarks(&mut bookmarks);
+
+    let fixed = bookmark_issues(&bookmarks, page_count);
+    if fixed.is_empty() {
+        return Ok(BookmarkFixReport {
+            fixed,
+            removed: 0,
+            bookmarks,
+        });
+    }
+
+    let before = bookmarks.len();
+    match strategy {
+        BookmarkFixStrategy::Remove => {
+            bookmarks.retain(|bookmark| bookmark_problem(bookmark, page_count).is_none());
+        }
+        BookmarkFixStrategy::Clamp => {
+            clamp_bookmark_pages(&mut bookmarks, page_count);
+        }
+    }
+
+    save_bookmarks_to_file(&app_handle, &path, &bookmarks)?;
+
+    Ok(BookmarkFixReport {
+        fixed,
+        removed: before - bookmarks.len(),
+        bookmarks,
+    })
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// end_page is left empty for single page bookmarks
+fn bookmarks_to_csv(bookmarks: &PdfBookmarks) -> String {
+    let mut csv = String::from("page_number,label,end_page\n");
+    for bm in bookmarks {
+        let end_page = bm.end_page.map(|end| end.to_string()).unwrap_or_default();
+        csv.push_str(&format!(
+            "{},{},{end_page}\n",
+            bm.page_number,
+            csv_field(&bm.label)
+        ));
+    }
+    csv
+}
+
+// Splits csv text into rows of fields, honouring quoted fields with embedded commas, quotes and newlines
+fn parse_csv_rows(data: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = data.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_quotes = true,
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    rows.retain(|r| !(r.len() == 1 && r[0].trim().is_empty()));
+    rows
+}
+
+// Reads what bookmarks_to_csv writes. end_page is optional, files exported before range
+// bookmarks have two columns.
+fn bookmarks_from_csv(data: &str) -> Result<PdfBookmarks, String> {
+    let mut rows = parse_csv_rows(data).into_iter().peekable();
+
+    if rows
+        .peek()
+        .is_some_and(|r| r.first().map(|f| f.trim()) == Some("page_number"))
+    {
+        rows.next();
+    }
+
+    rows.enumerate()
+        .map(|(i, row)| {
+            let (page, label, end_page) = match row.as_slice() {
+                [page, label] => (page, label, None),
+                [page, label, end_page] => (page, label, Some(end_page)),
+                _ => {
+                    return Err(format!(
+                        "Row {}: expected 2 or 3 columns (page_number,label,end_page), found {}",
+                        i + 1,
+                        row.len()
+                    ))
+                }
+            };
+
+            let page_number = page
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| format!("Row {}: invalid page number '{page}'", i + 1))?;
+            let end_page = match end_page.map(|end| end.trim()) {
+                None | Some("") => None,
+                Some(end) => Some(
+                    end.parse::<u32>()
+                        .map_err(|_| format!("Row {}: invalid end page '{end}'", i + 1))?,
+                ),
+            };
+
+            Ok(PdfBookmark {
+                page_number,
+                label: label.clone(),
+                end_page,
+            })
+        })
+        .collect()
+}
+
+#[tauri::command]
+pub fn export_bookmarks(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    output_path: String,
+    format: BookmarkFileFormat,
+) -> Result<(), String> {
+    log::info!("Exporting bookmarks of PDF {pdf_id} to {output_path}");
+
+    let path = get_bookmarks_path(&app_handle, pdf_id)?;
+    let mut bookmarks = load_bookmarks_from_file(&app_handle, &path)?;
+    sort_bookmarks(&mut bookmarks);
+
+    let contents = match format {
+        BookmarkFileFormat::Json => {
+            serde_json::to_string_pretty(&bookmarks).map_err(|e| e.to_string())?
+        }
+        BookmarkFileFormat::Csv => bookmarks_to_csv(&bookmarks),
+    };
+
+    fs::write(&output_path, contents).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn import_bookmarks(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    input_path: String,
+    mode: BookmarkImportMode,
+) -> Result<PdfBookmarks, String> {
+    log::info!("Importing bookmarks into PDF {pdf_id} from {input_path}");
+
+    let data = fs::read_to_string(&input_path).map_err(|e| e.to_string())?;
+
+    let is_json = Path::new(&input_path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    let imported = if is_json {
+        serde_json::from_str::<PdfBookmarks>(&data).map_err(|e| format!("Invalid JSON: {e}"))?
+    } else {
+        bookmarks_from_csv(&data)?
+    };
+
+    let page_count = pdf_page_count(&app_handle, pdf_id)?;
+    for bm in &imported {
+        if bm.label.trim().is_empty() {
+            return Err(format!(
+                "Bookmark on page {} has an empty label",
+                bm.page_number
+            ));
+        }
+        let last_page = bm.end_page.unwrap_or(bm.page_number);
+        if bm.page_number == 0 || last_page < bm.page_number || last_page > page_count {
+            return Err(format!(
+                "Bookmark '{}' is out of range (document has {page_count} pages)",
+                bm.label
+            ));
+        }
+    }
+
+    // a merge keeps bookmarks added meanwhile from another window
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    let path = get_bookmarks_path(&app_handle, pdf_id)?;
+    let mut bookmarks = match mode {
+        BookmarkImportMode::Replace => Vec::new(),
+        BookmarkImportMode::Merge => load_bookmarks_from_file(&app_handle, &path)?,
+    };
+
+    for bm in imported {
+        let duplicate = bookmarks
+            .iter()
+            .any(|b| b.page_number == bm.page_number && b.label == bm.label);
+        if !duplicate {
+            bookmarks.push(bm);
+        }
+    }
+
+    sort_bookmarks(&mut bookmarks);
+    save_bookmarks_to_file(&app_handle, &path, &bookmarks)?;
+
     Ok(bookmarks)
 }
+
+// Progress of a running thumbnail extraction, None when nothing is being extracted
+#[tauri::command]
+pub fn get_extraction_eta(
+    app_handle: AppHandle,
+    pdf_id: u64,
+) -> Result<Option<ExtractionProgress>, String> {
+    let state = app_handle.state::<AppState>();
+    let jobs = state.extraction_progress.lock().unwrap();
+    Ok(jobs.get(&pdf_id).cloned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+
+    fn test_stroke(id: String) -> Stroke {
+        Stroke {
+            id: Some(id),
+            tool: DrawingToolType::Pen,
+            color: "#000000".to_string(),
+            opacity: 1.0,
+            thickness: 2,
+            path: vec![StrokePath { x: 0.0, y: 0.0 }, StrokePath { x: 1.0, y: 1.0 }],
+            created_at: 0,
+        }
+    }
+
+    // Saves race to read-modify-write the same strokes.json, the pdf lock has to keep every
+    // one of them from being overwritten by another's stale read
+    #[test]
+    fn interleaved_saves_keep_every_stroke() {
+        let dir = std::env::temp_dir().join(format!("akda_saves_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let strokes_path = dir.join("strokes.json");
+        fs::write(&strokes_path, "{}").unwrap();
+
+        let state = Arc::new(AppState::new(PathBuf::new(), Err("no pdfium".to_string())));
+        let savers: Vec<_> = (0..8)
+            .map(|saver| {
+                let state = state.clone();
+                let strokes_path = strokes_path.clone();
+                thread::spawn(move || {
+                    for n in 0..10 {
+                        merge_saved_stroke(
+                            &state,
+                            1,
+                            &strokes_path,
+                            n % 3 + 1,
+                            test_stroke(format!("{saver}-{n}")),
+                            |path| {
+                                let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+                                // give another saver the chance to read the same stale file
+                                thread::yield_now();
+                                serde_json::from_str(&data).map_err(|e| e.to_string())
+                            },
+                            |path, strokes| {
+                                let data =
+                                    serde_json::to_string(strokes).map_err(|e| e.to_string())?;
+                                fs::write(path, data).map_err(|e| e.to_string())
+                            },
+                        )
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for saver in savers {
+            saver.join().unwrap();
+        }
+
+        let data = fs::read_to_string(&strokes_path).unwrap();
+        let strokes: PdfStrokes = serde_json::from_str(&data).unwrap();
+        assert_eq!(strokes.inner.values().map(Vec::len).sum::<usize>(), 80);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prepare_assigns_an_id_once() {
+        let mut stroke = test_stroke(String::new());
+        stroke.id = None;
+        stroke.prepare(DEFAULT_MAX_STROKE_THICKNESS).unwrap();
+        let id = stroke.id.clone().unwrap();
+        assert!(!id.is_empty());
+
+        stroke.prepare(DEFAULT_MAX_STROKE_THICKNESS).unwrap();
+        assert_eq!(stroke.id, Some(id));
+    }
+
+    #[test]
+    fn csv_bookmarks_keep_their_end_page() {
+        let bookmarks = vec![
+            PdfBookmark {
+                page_number: 2,
+                label: "Intro, part 1".to_string(),
+                end_page: Some(5),
+            },
+            PdfBookmark {
+                page_number: 7,
+                label: "Summary".to_string(),
+                end_page: None,
+            },
+        ];
+
+        let csv = bookmarks_to_csv(&bookmarks);
+        assert_eq!(
+            csv,
+            "page_number,label,end_page\n2,\"Intro, part 1\",5\n7,Summary,\n"
+        );
+        let parsed = bookmarks_from_csv(&csv).unwrap();
+        assert_eq!(parsed[0].end_page, Some(5));
+        assert_eq!(parsed[1].end_page, None);
+
+        // files exported before range bookmarks
+        let parsed = bookmarks_from_csv("page_number,label\n3,Old\n").unwrap();
+        assert_eq!((parsed[0].page_number, parsed[0].end_page), (3, None));
+    }
+
+    #[test]
+    fn bookmarks_sort_by_page_then_label() {
+        let bookmark = |page_number, label: &str| PdfBookmark {
+            page_number,
+            label: label.to_string(),
+            end_page: None,
+        };
+        let mut bookmarks = vec![
+            bookmark(12, "Summary"),
+            bookmark(3, "Proof"),
+            bookmark(12, "Exercises"),
+            bookmark(1, "Cover"),
+            bookmark(3, "Lemma"),
+        ];
+
+        sort_bookmarks(&mut bookmarks);
+
+        let order: Vec<_> = bookmarks
+            .iter()
+            .map(|b| (b.page_number, b.label.as_str()))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                (1, "Cover"),
+                (3, "Lemma"),
+                (3, "Proof"),
+                (12, "Exercises"),
+                (12, "Summary")
+            ]
+        );
+    }
+
+    #[test]
+    fn sanitize_clamps_styling_at_the_boundaries() {
+        let mut stroke = test_stroke("clamp".to_string());
+        stroke.opacity = 5.0;
+        stroke.thickness = 99_999;
+        stroke.sanitize(200).unwrap();
+        assert_eq!(stroke.opacity, 1.0);
+        assert_eq!(stroke.thickness, 200);
+
+        let mut stroke = test_stroke("low".to_string());
+        stroke.opacity = -0.5;
+        stroke.thickness = 1;
+        stroke.sanitize(200).unwrap();
+        assert_eq!(stroke.opacity, 0.0);
+        assert_eq!(stroke.thickness, 1);
+
+        // values right on the limits are kept as they are
+        let mut stroke = test_stroke("edge".to_string());
+        stroke.opacity = 1.0;
+        stroke.thickness = 200;
+        stroke.path = vec![StrokePath { x: 0.0, y: 0.0 }];
+        stroke.sanitize(200).unwrap();
+        assert_eq!((stroke.opacity, stroke.thickness), (1.0, 200));
+    }
+
+    #[test]
+    fn sanitize_rejects_strokes_that_cant_be_drawn() {
+        let invalid: [fn(&mut Stroke); 6] = [
+            |s: &mut Stroke| s.opacity = f64::NAN,
+            |s: &mut Stroke| s.thickness = 0,
+            |s: &mut Stroke| s.path.clear(),
+            |s: &mut Stroke| s.path[0].x = -1.0,
+            |s: &mut Stroke| s.path[1].y = f64::INFINITY,
+            |s: &mut Stroke| s.path[1].x = f64::NAN,
+        ];
+        for (case, make_invalid) in invalid.iter().enumerate() {
+            let mut stroke = test_stroke(format!("invalid-{case}"));
+            make_invalid(&mut stroke);
+            assert!(stroke.sanitize(200).is_err(), "case {case} was accepted");
+        }
+    }
+}
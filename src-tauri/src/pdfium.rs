@@ -0,0 +1,119 @@
+use std::path::{Path, PathBuf};
+
+use pdfium_render::prelude::{Pdfium, PdfiumLibraryBindings};
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::state::AppState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PdfiumBinding {
+    Bundled,
+    System,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PdfiumInfo {
+    // binding held by the app since startup, None when pdf features are disabled
+    active_binding: Option<PdfiumBinding>,
+    // why both the bundled and the system library failed to bind at startup
+    startup_error: Option<String>,
+    bundled_library_path: String,
+    bundled_library_exists: bool,
+    // pdfium's C API doesn't report its build version, so this is only known for the bundled copy
+    version: Option<String>,
+}
+
+fn bundled_library_path(pdfium_path: &Path) -> PathBuf {
+    Pdfium::pdfium_platform_library_name_at_path(pdfium_path)
+}
+
+// Prefers the bundled library, falling back to a system-wide pdfium
+pub(crate) fn bind_pdfium_bindings(
+    pdfium_path: &Path,
+) -> Result<(Box<dyn PdfiumLibraryBindings>, PdfiumBinding), String> {
+    match Pdfium::bind_to_library(bundled_library_path(pdfium_path)) {
+        Ok(bindings) => Ok((bindings, PdfiumBinding::Bundled)),
+        Err(bundled_error) => match Pdfium::bind_to_system_library() {
+            Ok(bindings) => {
+                log::warn!("Bundled pdfium unavailable ({bundled_error}), using system library");
+                Ok((bindings, PdfiumBinding::System))
+            }
+            Err(system_error) => Err(format!(
+                "Failed to bind pdfium (bundled: {bundled_error}, system: {system_error})"
+            )),
+        },
+    }
+}
+
+pub(crate) fn bind_pdfium(pdfium_path: &Path) -> Result<(Pdfium, PdfiumBinding), String> {
+    let (bindings, binding) = bind_pdfium_bindings(pdfium_path)?;
+    Ok((Pdfium::new(bindings), binding))
+}
+
+#[tauri::command]
+pub fn get_pdfium_info(app_handle: AppHandle) -> Result<PdfiumInfo, String> {
+    log::info!("Collecting pdfium diagnostics");
+
+    let state = app_handle.state::<AppState>();
+    let library_path = bundled_library_path(&state.lib_path);
+
+    // reports the binding made at startup, binding again here would load a second copy
+    let (active_binding, startup_error) = match &*state.pdfium.lock().unwrap() {
+        Ok((_, binding)) => (Some(*binding), None),
+        Err(e) => (None, Some(e.clone())),
+    };
+
+    let version = match active_binding {
+        Some(PdfiumBinding::Bundled) => library_version(&state.lib_path),
+        _ => None,
+    };
+
+    Ok(PdfiumInfo {
+        active_binding,
+        startup_error,
+        bundled_library_exists: library_path.exists(),
+        bundled_library_path: library_path.to_string_lossy().to_string(),
+        version,
+    })
+}
+
+// pdfium-binaries releases carry a VERSION file, read when it is kept next to the library
+fn library_version(pdfium_path: &Path) -> Option<String> {
+    std::fs::read_to_string(pdfium_path.join("VERSION"))
+        .ok()
+        .map(|version| version.trim().to_string())
+        .filter(|version| !version.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    // Binding is the per-command cost the shared instance saves, e.g. on every render_page
+    #[test]
+    #[ignore = "needs the pdfium library in pdfium-libs, run with --ignored"]
+    fn commands_share_the_startup_binding() {
+        let pdfium_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("pdfium-libs");
+        let lib_path = if cfg!(target_os = "windows") {
+            pdfium_dir.join("windows")
+        } else if cfg!(target_os = "macos") {
+            pdfium_dir.join("macos")
+        } else {
+            pdfium_dir.join("linux")
+        };
+
+        let pdfium = bind_pdfium(&lib_path);
+        if let Err(e) = &pdfium {
+            panic!("pdfium is unavailable: {e}");
+        }
+        let state = AppState::new(lib_path, pdfium);
+
+        let first = state.pdfium().unwrap();
+        let second = state.pdfium().unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+    }
+}
@@ -0,0 +1,386 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::encryption::{decrypt, encrypt, file_key, is_encrypted, is_sensitive};
+use crate::error::AppError;
+use crate::state::AppState;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonFormat {
+    #[default]
+    Pretty,
+    Compact,
+}
+
+// What a persisted file holds, deciding whether the configured format applies to it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileKind {
+    Registry,
+    Strokes,
+    Thumbnails,
+    Dimensions,
+    Text,
+    Bookmarks,
+    Starred,
+    Collections,
+    EditorSettings,
+    Settings,
+    Snapshot,
+    Encryption,
+    Note,
+    Profiles,
+    PageLabels,
+    StudySets,
+}
+
+impl FileKind {
+    // Generated bulk data can be minified, files people may open and edit stay readable
+    fn is_generated(&self) -> bool {
+        matches!(
+            self,
+            FileKind::Strokes | FileKind::Thumbnails | FileKind::Dimensions | FileKind::Text
+        )
+    }
+
+    // Files that can't be regenerated keep their previous version, see backup_path.
+    // Snapshots are written once and never overwritten.
+    fn keeps_backup(&self) -> bool {
+        !matches!(
+            self,
+            FileKind::Thumbnails | FileKind::Dimensions | FileKind::Text | FileKind::Snapshot
+        )
+    }
+}
+
+// Previous version of `path`, written before each overwrite so a corrupted file can be
+// restored by repair_library
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    PathBuf::from(backup)
+}
+
+pub fn to_json_string<T: Serialize + ?Sized>(
+    value: &T,
+    kind: FileKind,
+    format: JsonFormat,
+) -> Result<String, String> {
+    let serialized = if kind.is_generated() && format == JsonFormat::Compact {
+        serde_json::to_string(value)
+    } else {
+        serde_json::to_string_pretty(value)
+    };
+
+    serialized.map_err(|e| e.to_string())
+}
+
+pub fn write_json<T: Serialize + ?Sized>(
+    app_handle: &AppHandle,
+    path: &Path,
+    value: &T,
+    kind: FileKind,
+) -> Result<(), String> {
+    let format = app_handle
+        .state::<AppState>()
+        .settings
+        .lock()
+        .unwrap()
+        .data_file_format;
+
+    write_json_as(app_handle, path, value, kind, format)
+}
+
+// `write_json` in a given format instead of the configured one
+pub fn write_json_as<T: Serialize + ?Sized>(
+    app_handle: &AppHandle,
+    path: &Path,
+    value: &T,
+    kind: FileKind,
+    format: JsonFormat,
+) -> Result<(), String> {
+    write_json_with(app_handle, path, value, kind, format, kind.keeps_backup())
+}
+
+// `write_json` leaving the backup as it is, for writes as frequent as stroke autosave. Each
+// would otherwise replace the backup with a copy only seconds older than the file.
+pub fn write_json_keeping_backup<T: Serialize + ?Sized>(
+    app_handle: &AppHandle,
+    path: &Path,
+    value: &T,
+    kind: FileKind,
+) -> Result<(), String> {
+    let format = app_handle
+        .state::<AppState>()
+        .settings
+        .lock()
+        .unwrap()
+        .data_file_format;
+
+    write_json_with(app_handle, path, value, kind, format, false)
+}
+
+fn write_json_with<T: Serialize + ?Sized>(
+    app_handle: &AppHandle,
+    path: &Path,
+    value: &T,
+    kind: FileKind,
+    format: JsonFormat,
+    backup: bool,
+) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    let serialized = to_json_string(value, kind, format)?;
+    let key = if is_sensitive(path) {
+        file_key(app_handle, path)?
+    } else {
+        None
+    };
+    let contents = match key {
+        Some(key) => encrypt(&key, serialized.as_bytes())?,
+        None => serialized.into_bytes(),
+    };
+
+    if backup && path.exists() {
+        fs::copy(path, backup_path(path)).map_err(|e| e.to_string())?;
+    }
+
+    fs::write(path, contents).map_err(|e| e.to_string())
+}
+
+// Contents of a data file, decrypted when its pdf has encryption on. None when it doesn't
+// exist yet.
+pub fn read_data_file(app_handle: &AppHandle, path: &Path) -> Result<Option<String>, AppError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let data = fs::read(path)?;
+    let data = if is_encrypted(&data) {
+        let key = file_key(app_handle, path)?.ok_or_else(|| {
+            AppError::Locked(format!("{} is encrypted without a key", path.display()))
+        })?;
+        decrypt(&key, &data)?
+    } else {
+        data
+    };
+
+    String::from_utf8(data)
+        .map(Some)
+        .map_err(|e| AppError::Parse(e.to_string()))
+}
+
+// How often a file rewritten while it grows (e.g. thumbs.json during extraction) is persisted
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FlushPolicy {
+    // flush after this many updates, 1 writes on every update
+    pub every_updates: u32,
+    // flush when this long has passed since the last write
+    pub every_ms: u64,
+}
+
+impl Default for FlushPolicy {
+    fn default() -> Self {
+        Self {
+            every_updates: 25,
+            every_ms: 1000,
+        }
+    }
+}
+
+// Tracks updates since the last write, the caller still writes once more when done
+pub struct ThrottledFlush {
+    policy: FlushPolicy,
+    pending: u32,
+    last_flush: Instant,
+}
+
+impl ThrottledFlush {
+    pub fn new(policy: FlushPolicy) -> Self {
+        Self {
+            policy,
+            pending: 0,
+            last_flush: Instant::now(),
+        }
+    }
+
+    // Records an update, true when it's time to write
+    pub fn record(&mut self) -> bool {
+        self.record_at(Instant::now())
+    }
+
+    // `record` with the clock read by the caller
+    fn record_at(&mut self, now: Instant) -> bool {
+        self.pending += 1;
+
+        let due = self.pending >= self.policy.every_updates.max(1)
+            || now.saturating_duration_since(self.last_flush)
+                >= Duration::from_millis(self.policy.every_ms);
+        if due {
+            self.pending = 0;
+            self.last_flush = now;
+        }
+        due
+    }
+
+    // Records page `page_no` of an extraction. Page 1 is due right away without counting
+    // towards the next write, for files read while the rest is still being extracted.
+    pub fn record_page(&mut self, page_no: u32) -> bool {
+        page_no == 1 || self.record()
+    }
+
+    // Whether updates were recorded since the last write
+    pub fn is_dirty(&self) -> bool {
+        self.pending > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pdf::PdfStrokes;
+
+    use super::*;
+
+    // A page densely drawn over, 60 strokes of 300 points each
+    fn dense_page() -> PdfStrokes {
+        let strokes: Vec<_> = (0..60)
+            .map(|stroke| {
+                let path: Vec<_> = (0..300)
+                    .map(|point| {
+                        serde_json::json!({
+                            "x": 12.5 + point as f64 * 1.75,
+                            "y": 40.25 + stroke as f64 * 9.5 + (point % 7) as f64,
+                        })
+                    })
+                    .collect();
+                serde_json::json!({
+                    "id": format!("stroke-{stroke}"),
+                    "tool": "pen",
+                    "color": "#1f2937",
+                    "opacity": 1.0,
+                    "thickness": 3,
+                    "path": path,
+                    "created_at": 1_700_000_000_000i64 + stroke,
+                })
+            })
+            .collect();
+        serde_json::from_value(serde_json::json!({ "1": strokes })).unwrap()
+    }
+
+    #[test]
+    fn compact_format_shrinks_dense_strokes() {
+        let strokes = dense_page();
+        let pretty = to_json_string(&strokes, FileKind::Strokes, JsonFormat::Pretty).unwrap();
+        let compact = to_json_string(&strokes, FileKind::Strokes, JsonFormat::Compact).unwrap();
+
+        assert!(compact.len() * 2 < pretty.len());
+
+        // either format reads back the same
+        let from_pretty: PdfStrokes = serde_json::from_str(&pretty).unwrap();
+        let from_compact: PdfStrokes = serde_json::from_str(&compact).unwrap();
+        assert_eq!(
+            serde_json::to_string(&from_pretty).unwrap(),
+            serde_json::to_string(&from_compact).unwrap()
+        );
+    }
+
+    #[test]
+    fn files_people_edit_stay_pretty() {
+        let value = serde_json::json!({ "name": "Reading list", "pdfIds": [1, 2] });
+        let collections =
+            to_json_string(&value, FileKind::Collections, JsonFormat::Compact).unwrap();
+        let bookmarks = to_json_string(&value, FileKind::Bookmarks, JsonFormat::Compact).unwrap();
+
+        assert!(collections.contains('\n'));
+        assert!(bookmarks.contains('\n'));
+    }
+
+    #[test]
+    fn thumbnail_writes_are_throttled() {
+        // time never triggers a write in this test
+        let policy = FlushPolicy {
+            every_updates: 25,
+            every_ms: u64::MAX,
+        };
+        let mut flush = ThrottledFlush::new(policy);
+        let due: Vec<u32> = (1..=1010).filter(|_| flush.record()).collect();
+        assert_eq!(due, (1..=40).map(|n| n * 25).collect::<Vec<_>>());
+        // the last 10 are left for the final write
+        assert!(flush.is_dirty());
+
+        // a short document is only written at the end
+        let mut flush = ThrottledFlush::new(policy);
+        assert!(!(0..3).any(|_| flush.record()));
+        assert!(flush.is_dirty());
+
+        let per_page = FlushPolicy {
+            every_updates: 1,
+            every_ms: u64::MAX,
+        };
+        let mut flush = ThrottledFlush::new(per_page);
+        assert!((0..1000).all(|_| flush.record()));
+        assert!(!flush.is_dirty());
+    }
+
+    #[test]
+    fn dims_writes_are_throttled_after_the_first_page() {
+        let policy = FlushPolicy {
+            every_updates: 25,
+            every_ms: u64::MAX,
+        };
+        let mut flush = ThrottledFlush::new(policy);
+        let due: Vec<u32> = (1..=1000)
+            .filter(|&page_no| flush.record_page(page_no))
+            .collect();
+
+        // page 1 right away for load_pdf, then every 25 of the other 999 pages
+        assert_eq!(due[..3], [1, 26, 51]);
+        assert_eq!(due.len(), 40);
+        assert_eq!(due.last(), Some(&976));
+        assert!(flush.is_dirty());
+    }
+
+    #[test]
+    fn extraction_events_are_bounded_by_the_interval() {
+        // like the event throttles of extract_pdf_data, only time decides
+        let interval_ms = 100;
+        let policy = FlushPolicy {
+            every_updates: u32::MAX,
+            every_ms: interval_ms,
+        };
+        let pages = 2010;
+
+        let started = Instant::now();
+        let mut events = ThrottledFlush {
+            policy,
+            pending: 0,
+            last_flush: started,
+        };
+        let mut delta = Vec::new();
+        let mut emitted = Vec::new();
+        for page_no in 1..=pages {
+            // rendering a page takes 200µs
+            let now = started + Duration::from_micros(200) * page_no;
+            delta.push(page_no);
+            if events.record_at(now) {
+                emitted.push(std::mem::take(&mut delta));
+            }
+        }
+        if events.is_dirty() {
+            emitted.push(delta);
+        }
+
+        // one per 100ms of rendering, and the final one with what's left
+        let sizes: Vec<usize> = emitted.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [500, 500, 500, 500, 10]);
+        // each page is in exactly one delta
+        let delivered: Vec<u32> = emitted.into_iter().flatten().collect();
+        assert_eq!(delivered, (1..=pages).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::path::Path;
+
+use image::imageops::FilterType;
+use tauri::{AppHandle, Manager};
+
+use crate::error::AppError;
+use crate::pdf::{find_pdf_entry, read_pdf_entries, write_pdf_entries, CoverHash, PdfEntry};
+use crate::state::AppState;
+
+// pHash: the cover is shrunk to SAMPLE_SIZE x SAMPLE_SIZE greyscale, and the lowest
+// HASH_SIZE x HASH_SIZE frequencies of its DCT are compared against their median
+const SAMPLE_SIZE: usize = 32;
+const HASH_SIZE: usize = 8;
+// bits out of 64 two covers may differ by and still be grouped
+const MAX_THRESHOLD: u32 = 32;
+
+// 1D DCT-II of each row of a square matrix
+fn dct_rows(input: &[f64], size: usize) -> Vec<f64> {
+    let mut output = vec![0.0; size * size];
+    for row in 0..size {
+        for k in 0..size {
+            output[row * size + k] = (0..size)
+                .map(|n| {
+                    input[row * size + n] * (PI / size as f64 * (n as f64 + 0.5) * k as f64).cos()
+                })
+                .sum();
+        }
+    }
+    output
+}
+
+fn transpose(input: &[f64], size: usize) -> Vec<f64> {
+    let mut output = vec![0.0; size * size];
+    for row in 0..size {
+        for col in 0..size {
+            output[col * size + row] = input[row * size + col];
+        }
+    }
+    output
+}
+
+// 64-bit perceptual hash of an image file as 16 hex digits
+fn phash_file(path: &Path) -> Result<String, AppError> {
+    let image = image::open(path).map_err(|e| AppError::Parse(e.to_string()))?;
+    let pixels: Vec<f64> = image
+        .resize_exact(SAMPLE_SIZE as u32, SAMPLE_SIZE as u32, FilterType::Triangle)
+        .to_luma8()
+        .pixels()
+        .map(|pixel| pixel.0[0] as f64)
+        .collect();
+
+    // 2D DCT as row transforms before and after a transpose
+    let dct = transpose(
+        &dct_rows(
+            &transpose(&dct_rows(&pixels, SAMPLE_SIZE), SAMPLE_SIZE),
+            SAMPLE_SIZE,
+        ),
+        SAMPLE_SIZE,
+    );
+    let low: Vec<f64> = (0..HASH_SIZE)
+        .flat_map(|row| (0..HASH_SIZE).map(move |col| (row, col)))
+        .map(|(row, col)| dct[row * SAMPLE_SIZE + col])
+        .collect();
+
+    // the DC term is the overall brightness, left out of the median
+    let mut sorted = low[1..].to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let median = sorted[sorted.len() / 2];
+
+    let hash = low
+        .iter()
+        .enumerate()
+        .filter(|(_, &coefficient)| coefficient > median)
+        .fold(0u64, |hash, (i, _)| hash | 1 << (63 - i));
+    Ok(format!("{hash:016x}"))
+}
+
+// Union-find root of `i`, halving the path on the way
+fn find_root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+fn cover_file(pdf: &PdfEntry) -> Option<String> {
+    Path::new(&pdf.cover_path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(str::to_string)
+}
+
+// The cached hash while it still belongs to the current cover
+fn cached_hash(pdf: &PdfEntry) -> Option<String> {
+    let cached = pdf.cover_phash.as_ref()?;
+    (Some(&cached.cover_file) == cover_file(pdf).as_ref()).then(|| cached.hash.clone())
+}
+
+// Records freshly computed hashes in the registry in one write. Entries whose cover changed
+// in the meantime are left alone.
+fn store_hashes(
+    app_handle: &AppHandle,
+    computed: &HashMap<u64, CoverHash>,
+) -> Result<(), AppError> {
+    if computed.is_empty() {
+        return Ok(());
+    }
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    let state_path = app_data_dir.join("pdfs.json");
+
+    let state = app_handle.state::<AppState>();
+    let _guard = state.registry_lock.lock().unwrap();
+
+    let mut pdfs = read_pdf_entries(&state_path)?;
+    for pdf in &mut pdfs {
+        if let Some(hash) = computed.get(&pdf.id) {
+            if cover_file(pdf).as_ref() == Some(&hash.cover_file) {
+                pdf.cover_phash = Some(hash.clone());
+            }
+        }
+    }
+    write_pdf_entries(app_handle, &state_path, &pdfs)?;
+
+    Ok(())
+}
+
+// Hashes of every pdf with a readable cover, computing and caching the missing ones
+fn library_hashes(app_handle: &AppHandle) -> Result<Vec<(u64, u64)>, AppError> {
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    let pdfs = read_pdf_entries(&app_data_dir.join("pdfs.json"))?;
+
+    let mut hashes = Vec::with_capacity(pdfs.len());
+    let mut computed = HashMap::new();
+    for pdf in &pdfs {
+        let hash = match cached_hash(pdf) {
+            Some(hash) => hash,
+            None => {
+                let Some(cover_file) = cover_file(pdf) else {
+                    continue;
+                };
+                match phash_file(Path::new(&pdf.cover_path)) {
+                    Ok(hash) => {
+                        computed.insert(
+                            pdf.id,
+                            CoverHash {
+                                cover_file,
+                                hash: hash.clone(),
+                            },
+                        );
+                        hash
+                    }
+                    Err(e) => {
+                        log::warn!("Skipping cover of pdf {}: {e}", pdf.id);
+                        continue;
+                    }
+                }
+            }
+        };
+        if let Ok(bits) = u64::from_str_radix(&hash, 16) {
+            hashes.push((pdf.id, bits));
+        }
+    }
+
+    store_hashes(app_handle, &computed)?;
+    Ok(hashes)
+}
+
+// Perceptual hash of a pdf's cover as 16 hex digits, cached in the registry until the cover
+// changes. Covers that look alike have hashes a few bits apart.
+#[tauri::command]
+pub fn cover_phash(app_handle: AppHandle, pdf_id: u64) -> Result<String, AppError> {
+    log::info!("Computing cover hash of pdf {pdf_id}");
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    let pdf = find_pdf_entry(&app_data_dir, pdf_id)?;
+    if let Some(hash) = cached_hash(&pdf) {
+        return Ok(hash);
+    }
+
+    let cover_file = cover_file(&pdf)
+        .ok_or_else(|| AppError::NotFound(format!("PDF {pdf_id} has no cover yet")))?;
+    let hash = phash_file(Path::new(&pdf.cover_path))?;
+    store_hashes(
+        &app_handle,
+        &HashMap::from([(
+            pdf_id,
+            CoverHash {
+                cover_file,
+                hash: hash.clone(),
+            },
+        )]),
+    )?;
+
+    Ok(hash)
+}
+
+// Groups pdfs whose covers are at most `threshold` bits apart, e.g. other editions or scans
+// of the same book. Grouping is transitive. Only groups of two or more are returned, each
+// sorted by id.
+#[tauri::command]
+pub fn find_similar_covers(
+    app_handle: AppHandle,
+    threshold: u32,
+) -> Result<Vec<Vec<u64>>, AppError> {
+    log::info!("Finding similar covers within {threshold} bits");
+
+    if threshold > MAX_THRESHOLD {
+        return Err(AppError::InvalidInput(format!(
+            "Threshold must be at most {MAX_THRESHOLD} bits"
+        )));
+    }
+
+    let hashes = library_hashes(&app_handle)?;
+
+    // union-find over the pairs within the threshold
+    let mut parent: Vec<usize> = (0..hashes.len()).collect();
+    for (a, (_, hash_a)) in hashes.iter().enumerate() {
+        for (b, (_, hash_b)) in hashes.iter().enumerate().skip(a + 1) {
+            if (hash_a ^ hash_b).count_ones() <= threshold {
+                let (root_a, root_b) = (find_root(&mut parent, a), find_root(&mut parent, b));
+                parent[root_b] = root_a;
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<u64>> = HashMap::new();
+    for (i, (pdf_id, _)) in hashes.iter().enumerate() {
+        let group_root = find_root(&mut parent, i);
+        groups.entry(group_root).or_default().push(*pdf_id);
+    }
+
+    let mut groups: Vec<Vec<u64>> = groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|mut group| {
+            group.sort_unstable();
+            group
+        })
+        .collect();
+    groups.sort();
+
+    Ok(groups)
+}
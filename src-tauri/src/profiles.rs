@@ -0,0 +1,376 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::error::AppError;
+use crate::pdf::find_pdf_entry;
+use crate::persistence::{backup_path, write_json, FileKind};
+use crate::state::AppState;
+use crate::stroke_queue::flush_pending_strokes;
+
+// The annotations a pdf had before profiles existed
+pub const DEFAULT_PROFILE: &str = "default";
+const MAX_PROFILE_NAME_LEN: usize = 40;
+// stored bookmarks shared by profiles without their own, while one with its own is active
+const SHARED_BOOKMARKS: &str = "bookmarks_shared.json";
+
+// The active profile's annotations are always the live strokes.json and bookmarks.json, so
+// every other command works on the active profile without knowing about profiles. The others
+// are stored next to them as strokes_{name}.json and bookmarks_{name}.json.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnotationProfile {
+    name: String,
+    // false shares the bookmarks of every other profile without their own
+    separate_bookmarks: bool,
+    created_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnotationProfiles {
+    active: String,
+    profiles: Vec<AnnotationProfile>,
+}
+
+impl Default for AnnotationProfiles {
+    fn default() -> Self {
+        Self {
+            active: DEFAULT_PROFILE.to_string(),
+            profiles: vec![AnnotationProfile {
+                name: DEFAULT_PROFILE.to_string(),
+                separate_bookmarks: false,
+                created_at: None,
+            }],
+        }
+    }
+}
+
+impl AnnotationProfiles {
+    fn get(&self, name: &str) -> Option<&AnnotationProfile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+}
+
+fn profiles_path(folder: &Path) -> PathBuf {
+    folder.join("profiles.json")
+}
+
+fn stored_strokes(folder: &Path, name: &str) -> PathBuf {
+    folder.join(format!("strokes_{name}.json"))
+}
+
+fn stored_bookmarks(folder: &Path, profile: &AnnotationProfile) -> PathBuf {
+    if profile.separate_bookmarks {
+        folder.join(format!("bookmarks_{}.json", profile.name))
+    } else {
+        folder.join(SHARED_BOOKMARKS)
+    }
+}
+
+// Profile names end up in file names, so only letters, digits, '-' and '_' are allowed
+fn validate_profile_name(name: &str) -> Result<(), AppError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_PROFILE_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        // would collide with the stored shared bookmarks
+        && name != "shared";
+    if !valid {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid profile name: {name:?}, use up to {MAX_PROFILE_NAME_LEN} letters, digits, '-' or '_'"
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn load_profiles(folder: &Path) -> Result<AnnotationProfiles, AppError> {
+    let path = profiles_path(folder);
+    if !path.exists() {
+        return Ok(AnnotationProfiles::default());
+    }
+
+    let data = fs::read_to_string(path)?;
+    Ok(serde_json::from_str::<AnnotationProfiles>(&data)?)
+}
+
+// Annotation files of the profiles that aren't active, as (path, is_strokes). Page edits
+// remap them along with the live ones.
+pub(crate) fn stored_profile_files(folder: &Path) -> Result<Vec<(PathBuf, bool)>, AppError> {
+    let profiles = load_profiles(folder)?;
+    let Some(active) = profiles.get(&profiles.active) else {
+        return Ok(Vec::new());
+    };
+
+    let mut files = Vec::new();
+    for profile in &profiles.profiles {
+        if profile.name != active.name {
+            files.push((stored_strokes(folder, &profile.name), true));
+            if profile.separate_bookmarks {
+                files.push((stored_bookmarks(folder, profile), false));
+            }
+        }
+    }
+    if active.separate_bookmarks {
+        files.push((folder.join(SHARED_BOOKMARKS), false));
+    }
+
+    Ok(files
+        .into_iter()
+        .filter(|(path, _)| path.exists())
+        .collect())
+}
+
+// Moves done by a profile switch, undone in reverse when a later step fails. A file a move
+// would replace is set aside instead of removed until the switch is saved.
+#[derive(Default)]
+struct ProfileMoves {
+    done: Vec<(PathBuf, PathBuf)>,
+    set_aside: Vec<PathBuf>,
+}
+
+impl ProfileMoves {
+    fn rename(&mut self, from: &Path, to: &Path) -> Result<(), AppError> {
+        fs::rename(from, to)?;
+        self.done.push((from.to_path_buf(), to.to_path_buf()));
+        Ok(())
+    }
+
+    // Moves `from` to `to` along with its backup, so a restored backup is always of the
+    // file next to it. `to` goes away even when `from` doesn't exist, so a missing file keeps
+    // meaning "no annotations" after the move.
+    fn move_annotations(&mut self, from: &Path, to: &Path) -> Result<(), AppError> {
+        self.move_file(from, to)?;
+        self.move_file(&backup_path(from), &backup_path(to))
+    }
+
+    fn move_file(&mut self, from: &Path, to: &Path) -> Result<(), AppError> {
+        if to.exists() {
+            let mut aside = to.as_os_str().to_owned();
+            aside.push(".replaced");
+            let aside = PathBuf::from(aside);
+            self.rename(to, &aside)?;
+            self.set_aside.push(aside);
+        }
+        if from.exists() {
+            self.rename(from, to)?;
+        }
+        Ok(())
+    }
+
+    fn undo(self) {
+        for (from, to) in self.done.into_iter().rev() {
+            if let Err(e) = fs::rename(&to, &from) {
+                log::error!(
+                    "Failed to move {} back to {}: {e}",
+                    to.display(),
+                    from.display()
+                );
+            }
+        }
+    }
+
+    fn finish(self) {
+        for path in self.set_aside {
+            if let Err(e) = fs::remove_file(&path) {
+                log::warn!("Failed to remove {}: {e}", path.display());
+            }
+        }
+    }
+}
+
+// Adds an empty profile, `separate_bookmarks` gives it bookmarks of its own instead of the
+// shared ones. Doesn't switch to it. Returns every profile.
+#[tauri::command]
+pub fn create_annotation_profile(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    name: String,
+    separate_bookmarks: Option<bool>,
+) -> Result<AnnotationProfiles, AppError> {
+    log::info!("Creating annotation profile {name} of pdf {pdf_id}");
+
+    let name = name.trim().to_string();
+    validate_profile_name(&name)?;
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    find_pdf_entry(&app_data_dir, pdf_id)?;
+    let folder = app_data_dir.join(format!("pdf_{pdf_id}"));
+
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    let mut profiles = load_profiles(&folder)?;
+    if profiles.get(&name).is_some() {
+        return Err(AppError::InvalidInput(format!(
+            "Profile {name} already exists"
+        )));
+    }
+
+    profiles.profiles.push(AnnotationProfile {
+        name,
+        separate_bookmarks: separate_bookmarks.unwrap_or(false),
+        created_at: Some(Local::now().to_rfc3339()),
+    });
+    write_json(
+        &app_handle,
+        &profiles_path(&folder),
+        &profiles,
+        FileKind::Profiles,
+    )?;
+
+    Ok(profiles)
+}
+
+#[tauri::command]
+pub fn list_profiles(app_handle: AppHandle, pdf_id: u64) -> Result<AnnotationProfiles, AppError> {
+    log::info!("Listing annotation profiles of pdf {pdf_id}");
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    load_profiles(&app_data_dir.join(format!("pdf_{pdf_id}")))
+}
+
+// Stores the active profile's annotations and makes `name`'s the live ones. Strokes queued
+// for autosave are written to the profile they were drawn in first. Returns the active name.
+#[tauri::command]
+pub fn switch_profile(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    name: String,
+) -> Result<String, AppError> {
+    log::info!("Switching pdf {pdf_id} to annotation profile {name}");
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    find_pdf_entry(&app_data_dir, pdf_id)?;
+    let folder = app_data_dir.join(format!("pdf_{pdf_id}"));
+
+    flush_pending_strokes(&app_handle, pdf_id)?;
+
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    let mut profiles = load_profiles(&folder)?;
+    if profiles.active == name {
+        return Ok(name);
+    }
+    let target = profiles
+        .get(&name)
+        .cloned()
+        .ok_or_else(|| AppError::NotFound(format!("Profile {name} not found")))?;
+    // an active name missing from the list is treated like the default profile
+    let current = profiles
+        .get(&profiles.active)
+        .cloned()
+        .unwrap_or_else(|| AnnotationProfile {
+            name: profiles.active.clone(),
+            separate_bookmarks: false,
+            created_at: None,
+        });
+
+    let live_strokes = folder.join("strokes.json");
+    let live_bookmarks = folder.join("bookmarks.json");
+
+    let mut moves = ProfileMoves::default();
+    let switched = (|| -> Result<(), AppError> {
+        moves.move_annotations(&live_strokes, &stored_strokes(&folder, &current.name))?;
+        moves.move_annotations(&stored_strokes(&folder, &target.name), &live_strokes)?;
+
+        // both sharing means the live bookmarks stay where they are
+        if current.separate_bookmarks || target.separate_bookmarks {
+            moves.move_annotations(&live_bookmarks, &stored_bookmarks(&folder, &current))?;
+            moves.move_annotations(&stored_bookmarks(&folder, &target), &live_bookmarks)?;
+        }
+
+        profiles.active = target.name.clone();
+        write_json(
+            &app_handle,
+            &profiles_path(&folder),
+            &profiles,
+            FileKind::Profiles,
+        )?;
+        Ok(())
+    })();
+
+    match switched {
+        Ok(()) => {
+            moves.finish();
+            Ok(profiles.active)
+        }
+        Err(e) => {
+            log::error!("Failed to switch pdf {pdf_id} to profile {name}, undoing: {e}");
+            moves.undo();
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_folder(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("akda_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn undo_restores_moved_and_replaced_files() {
+        let dir = temp_folder("profile_undo");
+        let live = dir.join("strokes.json");
+        let stored = dir.join("strokes_exam.json");
+        fs::write(&live, "live").unwrap();
+        fs::write(backup_path(&live), "live backup").unwrap();
+        fs::write(&stored, "stale").unwrap();
+        fs::write(backup_path(&stored), "stale backup").unwrap();
+
+        let mut moves = ProfileMoves::default();
+        moves.move_annotations(&live, &stored).unwrap();
+        assert!(!live.exists());
+        assert!(!backup_path(&live).exists());
+        assert_eq!(fs::read_to_string(&stored).unwrap(), "live");
+        assert_eq!(
+            fs::read_to_string(backup_path(&stored)).unwrap(),
+            "live backup"
+        );
+
+        moves.undo();
+        assert_eq!(fs::read_to_string(&live).unwrap(), "live");
+        assert_eq!(
+            fs::read_to_string(backup_path(&live)).unwrap(),
+            "live backup"
+        );
+        assert_eq!(fs::read_to_string(&stored).unwrap(), "stale");
+        assert_eq!(
+            fs::read_to_string(backup_path(&stored)).unwrap(),
+            "stale backup"
+        );
+        assert!(!dir.join("strokes_exam.json.replaced").exists());
+        assert!(!dir.join("strokes_exam.json.bak.replaced").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn finish_drops_what_a_missing_file_replaced() {
+        let dir = temp_folder("profile_finish");
+        let live = dir.join("strokes.json");
+        fs::write(&live, "live").unwrap();
+        fs::write(backup_path(&live), "live backup").unwrap();
+
+        let mut moves = ProfileMoves::default();
+        moves
+            .move_annotations(&dir.join("strokes_exam.json"), &live)
+            .unwrap();
+        assert!(!live.exists());
+        assert!(!backup_path(&live).exists());
+
+        moves.finish();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,205 @@
+use std::fs;
+use std::io::Cursor;
+use std::time::Instant;
+
+use pdfium_render::prelude::*;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::error::AppError;
+use crate::pdf::find_pdf_entry;
+use crate::state::AppState;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RenderTiming {
+    page: u32,
+    render_ms: f64,
+    // png encoding in memory, what saving a render costs on top of the disk write
+    encode_ms: f64,
+    pixels: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RenderBenchmarkProgress {
+    pdf_id: u64,
+    page: u32,
+    total: u32,
+}
+
+pub const MIN_RENDER_DPI: f32 = 18.0;
+pub const MAX_RENDER_DPI: f32 = 600.0;
+// longest side of any render in pixels, large pages at a high DPI are scaled down to it
+const MAX_RENDER_SIDE: f32 = 10_000.0;
+
+pub(crate) fn validate_dpi(dpi: f32) -> Result<(), String> {
+    if !dpi.is_finite() || !(MIN_RENDER_DPI..=MAX_RENDER_DPI).contains(&dpi) {
+        return Err(format!(
+            "DPI must be between {MIN_RENDER_DPI} and {MAX_RENDER_DPI}, got {dpi}"
+        ));
+    }
+    Ok(())
+}
+
+// Scales are pixels per page point, the same range as the DPIs
+pub(crate) fn validate_scale(scale: f32) -> Result<(), String> {
+    let (min_scale, max_scale) = (MIN_RENDER_DPI / 72.0, MAX_RENDER_DPI / 72.0);
+    if !scale.is_finite() || !(min_scale..=max_scale).contains(&scale) {
+        return Err(format!(
+            "Scale must be between {min_scale:.2} and {max_scale:.2}, got {scale}"
+        ));
+    }
+    Ok(())
+}
+
+// Pixel size of a page of `width` x `height` points rendered at `dpi`, px = points / 72 * dpi
+pub(crate) fn pixels_at_dpi(width: f32, height: f32, dpi: f32) -> (i32, i32) {
+    let mut scale = dpi.clamp(MIN_RENDER_DPI, MAX_RENDER_DPI) / 72.0;
+    let longest = width.max(height) * scale;
+    if longest > MAX_RENDER_SIDE {
+        scale *= MAX_RENDER_SIDE / longest;
+    }
+
+    (
+        ((width * scale).round() as i32).max(1),
+        ((height * scale).round() as i32).max(1),
+    )
+}
+
+// Renders a page at `dpi` as a png under the pdf's renders/ folder and returns its path.
+// Renders are cached per page and DPI until the clone changes.
+#[tauri::command]
+pub fn render_page_dpi(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    page: u32,
+    dpi: f32,
+) -> Result<String, AppError> {
+    log::info!("Rendering page {page} of pdf {pdf_id} at {dpi} dpi");
+
+    validate_dpi(dpi).map_err(AppError::InvalidInput)?;
+    // whole DPIs, so close values share a cached render
+    let dpi = dpi.round();
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    let pdf_entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+    let renders_dir = app_data_dir.join(format!("pdf_{pdf_id}/renders"));
+    let render_path = renders_dir.join(format!("page_{page}_{dpi}dpi.png"));
+    if render_path.exists() {
+        return Ok(render_path.to_string_lossy().into_owned());
+    }
+
+    let pdfium = app_handle.state::<AppState>().pdfium()?;
+    let document = pdfium.load_pdf_from_file(&pdf_entry.clone_path, None)?;
+
+    let page_count = document.pages().len() as u32;
+    if page == 0 || page > page_count {
+        return Err(AppError::InvalidInput(format!(
+            "Page {page} is out of range (1-{page_count})"
+        )));
+    }
+    let pdf_page = document.pages().get((page - 1) as PdfPageIndex)?;
+
+    let size = pdf_page.page_size();
+    let (width, height) = pixels_at_dpi(size.width().value, size.height().value, dpi);
+    let bitmap = pdf_page.render(width, height, None)?;
+
+    fs::create_dir_all(&renders_dir)?;
+    bitmap
+        .as_image()
+        .save(&render_path)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(render_path.to_string_lossy().into_owned())
+}
+
+fn run_render_benchmark(
+    app_handle: &AppHandle,
+    pdf_id: u64,
+    pages: &[u32],
+    scale: f32,
+) -> Result<Vec<RenderTiming>, AppError> {
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    let pdf_entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+    let pdfium = app_handle.state::<AppState>().pdfium()?;
+    let document = pdfium.load_pdf_from_file(&pdf_entry.clone_path, None)?;
+
+    let page_count = document.pages().len() as u32;
+    if let Some(page) = pages.iter().find(|&&p| p == 0 || p > page_count) {
+        return Err(AppError::InvalidInput(format!(
+            "Page {page} is out of range (1-{page_count})"
+        )));
+    }
+
+    let mut timings = Vec::with_capacity(pages.len());
+    for (i, page_no) in pages.iter().enumerate() {
+        let page = document.pages().get((page_no - 1) as PdfPageIndex)?;
+        let size = page.page_size();
+        let (width, height) = pixels_at_dpi(size.width().value, size.height().value, scale * 72.0);
+
+        let started = Instant::now();
+        let image = page.render(width, height, None)?.as_image();
+        let render_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+        let started = Instant::now();
+        image
+            .write_to(&mut Cursor::new(Vec::new()), image::ImageFormat::Png)
+            .map_err(|e| AppError::Other(e.to_string()))?;
+        let encode_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+        timings.push(RenderTiming {
+            page: *page_no,
+            render_ms,
+            encode_ms,
+            pixels: width as u64 * height as u64,
+        });
+
+        app_handle
+            .emit(
+                "render-benchmark-progress",
+                RenderBenchmarkProgress {
+                    pdf_id,
+                    page: i as u32 + 1,
+                    total: pages.len() as u32,
+                },
+            )
+            .unwrap();
+    }
+
+    Ok(timings)
+}
+
+// Renders `pages` at `scale` and reports how long rendering and encoding each one took,
+// without keeping the output, to find pages that are slow to draw. Emits
+// `render-benchmark-progress` after each page.
+#[tauri::command]
+pub async fn benchmark_render(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    pages: Vec<u32>,
+    scale: f32,
+) -> Result<Vec<RenderTiming>, AppError> {
+    log::info!("Benchmarking render of pages {pages:?} of pdf {pdf_id} at scale {scale}");
+
+    if pages.is_empty() {
+        return Err(AppError::InvalidInput(
+            "Select at least one page to render".to_string(),
+        ));
+    }
+    validate_scale(scale).map_err(AppError::InvalidInput)?;
+
+    // cpu heavy
+    tauri::async_runtime::spawn_blocking(move || -> Result<Vec<RenderTiming>, AppError> {
+        let timings = run_render_benchmark(&app_handle, pdf_id, &pages, scale)?;
+        for timing in &timings {
+            log::info!(
+                "Page {} of pdf {pdf_id}: {:.1} ms render, {:.1} ms encode, {} px",
+                timing.page,
+                timing.render_ms,
+                timing.encode_ms,
+                timing.pixels
+            );
+        }
+        Ok(timings)
+    })
+    .await?
+}
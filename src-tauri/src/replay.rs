@@ -0,0 +1,111 @@
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::pdf::{load_strokes_from_file, DrawingToolType, Stroke, StrokePath};
+
+// Strokes only carry the time they were saved, so drawing speed is synthesized per point
+const MS_PER_POINT: u64 = 8;
+const MIN_STROKE_MS: u64 = 120;
+const MAX_STROKE_MS: u64 = 2000;
+// pause between strokes when they have no timestamps
+const SYNTHETIC_GAP_MS: u64 = 300;
+// real pauses are kept up to this, so a break of an hour doesn't stall the playback
+const MAX_GAP_MS: u64 = 2000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayEvent {
+    stroke_id: Option<String>,
+    tool: DrawingToolType,
+    color: String,
+    opacity: f64,
+    thickness: u64,
+    points: Vec<StrokePath>,
+    // offset from the start of the replay
+    start_ms: u64,
+    // the points are drawn evenly over this
+    duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplaySpec {
+    pdf_id: u64,
+    page: u32,
+    // false when some stroke predates timestamps and the order and gaps are synthetic
+    timed: bool,
+    total_ms: u64,
+    events: Vec<ReplayEvent>,
+}
+
+fn stroke_duration_ms(stroke: &Stroke) -> u64 {
+    (stroke.path.len() as u64 * MS_PER_POINT).clamp(MIN_STROKE_MS, MAX_STROKE_MS)
+}
+
+// Orders the strokes by save time, keeping the gaps between them up to MAX_GAP_MS. When any
+// stroke lacks a timestamp the file order is used with even gaps instead.
+fn build_replay(pdf_id: u64, page: u32, mut strokes: Vec<Stroke>) -> ReplaySpec {
+    let timed = strokes.iter().all(|stroke| stroke.created_at > 0);
+    if timed {
+        // stable, strokes saved in the same millisecond keep their file order
+        strokes.sort_by_key(|stroke| stroke.created_at);
+    }
+
+    let mut events = Vec::with_capacity(strokes.len());
+    let mut cursor = 0;
+    let mut previous_created_at = None;
+
+    for stroke in strokes {
+        let gap = match previous_created_at {
+            None => 0,
+            Some(_) if !timed => SYNTHETIC_GAP_MS,
+            Some(previous) => ((stroke.created_at - previous).max(0) as u64).min(MAX_GAP_MS),
+        };
+        previous_created_at = Some(stroke.created_at);
+
+        let duration_ms = stroke_duration_ms(&stroke);
+        let start_ms = cursor + gap;
+        cursor = start_ms + duration_ms;
+
+        events.push(ReplayEvent {
+            stroke_id: stroke.id,
+            tool: stroke.tool,
+            color: stroke.color,
+            opacity: stroke.opacity,
+            thickness: stroke.thickness,
+            points: stroke.path,
+            start_ms,
+            duration_ms,
+        });
+    }
+
+    ReplaySpec {
+        pdf_id,
+        page,
+        timed,
+        total_ms: cursor,
+        events,
+    }
+}
+
+// Draw events for a page in the order the strokes were made, for the frontend to animate.
+// A page without strokes gives an empty replay.
+#[tauri::command]
+pub fn export_stroke_replay(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    page: u32,
+) -> Result<ReplaySpec, String> {
+    log::info!("Building stroke replay of page {page} of pdf {pdf_id}");
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let mut strokes = load_strokes_from_file(
+        &app_handle,
+        &app_data_dir.join(format!("pdf_{pdf_id}/strokes.json")),
+    )?;
+    let page_strokes = strokes.inner.remove(&page).unwrap_or_default();
+
+    Ok(build_replay(pdf_id, page, page_strokes))
+}
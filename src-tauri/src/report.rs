@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::fs;
+
+use chrono::Local;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::collections::{collections_file_path, read_collections};
+use crate::pdf::{
+    load_bookmarks_from_file, load_editor_settings, load_strokes_from_file, pdf_page_count,
+    read_pdf_entries, PdfEntry, PdfStrokes,
+};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BookReport {
+    pdf_id: u64,
+    file_name: String,
+    // 0 when the document can't be opened
+    page_count: u32,
+    // furthest page the reader is on, from the editor settings
+    pages_read: u32,
+    progress: f64,
+    annotations: usize,
+    annotated_pages: usize,
+    bookmarks: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CollectionReport {
+    id: String,
+    name: String,
+    pdf_count: usize,
+    pages_read: u64,
+    annotations: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadingReport {
+    generated_at: String,
+    pdf_count: usize,
+    total_pages: u64,
+    pages_read: u64,
+    total_annotations: usize,
+    total_bookmarks: usize,
+    // None when nothing is annotated
+    most_annotated: Option<BookReport>,
+    collections: Vec<CollectionReport>,
+    books: Vec<BookReport>,
+}
+
+// Unreadable sidecars count as empty, the report covers whatever can be read
+fn book_report(app_handle: &AppHandle, pdf: &PdfEntry) -> BookReport {
+    let folder = app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join(format!("pdf_{}", pdf.id)))
+        .unwrap_or_default();
+
+    let page_count = pdf
+        .page_count
+        .or_else(|| pdf_page_count(app_handle, pdf.id).ok())
+        .unwrap_or(0);
+    let pages_read = load_editor_settings(app_handle.clone(), pdf.id)
+        .map(|settings| settings.current_page.min(u64::from(page_count)) as u32)
+        .unwrap_or(0);
+
+    let strokes =
+        load_strokes_from_file(app_handle, &folder.join("strokes.json")).unwrap_or_else(|e| {
+            log::warn!("Skipping strokes of pdf {} in the report: {e}", pdf.id);
+            PdfStrokes::default()
+        });
+    let bookmarks = load_bookmarks_from_file(app_handle, &folder.join("bookmarks.json"))
+        .unwrap_or_else(|e| {
+            log::warn!("Skipping bookmarks of pdf {} in the report: {e}", pdf.id);
+            Vec::new()
+        });
+
+    BookReport {
+        pdf_id: pdf.id,
+        file_name: pdf.file_name.clone(),
+        page_count,
+        pages_read,
+        progress: if page_count == 0 {
+            0.0
+        } else {
+            f64::from(pages_read) / f64::from(page_count)
+        },
+        annotations: strokes.inner.values().map(Vec::len).sum(),
+        annotated_pages: strokes
+            .inner
+            .values()
+            .filter(|page| !page.is_empty())
+            .count(),
+        bookmarks: bookmarks.len(),
+    }
+}
+
+fn build_reading_report(app_handle: &AppHandle) -> Result<ReadingReport, String> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let pdfs = read_pdf_entries(&app_data_dir.join("pdfs.json"))?;
+    let books: Vec<BookReport> = pdfs
+        .iter()
+        .map(|pdf| book_report(app_handle, pdf))
+        .collect();
+    let by_id: HashMap<String, &BookReport> = books
+        .iter()
+        .map(|book| (book.pdf_id.to_string(), book))
+        .collect();
+
+    let collections = read_collections(&collections_file_path(app_handle)?)?
+        .collections
+        .into_iter()
+        .map(|collection| {
+            let members: Vec<&BookReport> = collection
+                .pdf_ids
+                .keys()
+                .filter_map(|id| by_id.get(id).copied())
+                .collect();
+            CollectionReport {
+                id: collection.id,
+                name: collection.name,
+                pdf_count: members.len(),
+                pages_read: members.iter().map(|book| u64::from(book.pages_read)).sum(),
+                annotations: members.iter().map(|book| book.annotations).sum(),
+            }
+        })
+        .collect();
+
+    let most_annotated = books
+        .iter()
+        .filter(|book| book.annotations > 0)
+        .max_by_key(|book| book.annotations)
+        .cloned();
+
+    Ok(ReadingReport {
+        generated_at: Local::now().to_rfc3339(),
+        pdf_count: books.len(),
+        total_pages: books.iter().map(|book| u64::from(book.page_count)).sum(),
+        pages_read: books.iter().map(|book| u64::from(book.pages_read)).sum(),
+        total_annotations: books.iter().map(|book| book.annotations).sum(),
+        total_bookmarks: books.iter().map(|book| book.bookmarks).sum(),
+        most_annotated,
+        collections,
+        books,
+    })
+}
+
+#[tauri::command]
+pub fn export_reading_report(app_handle: AppHandle) -> Result<ReadingReport, String> {
+    log::info!("Building reading report");
+    build_reading_report(&app_handle)
+}
+
+#[tauri::command]
+pub fn export_reading_report_json(app_handle: AppHandle, path: String) -> Result<(), String> {
+    log::info!("Exporting reading report to {path}");
+
+    let report = build_reading_report(&app_handle)?;
+    let contents = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+    fs::write(&path, contents).map_err(|e| e.to_string())
+}
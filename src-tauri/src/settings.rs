@@ -0,0 +1,331 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Deserializer, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::color::validate_color;
+use crate::pdf::{DEFAULT_EXTRACTION_EVENT_INTERVAL_MS, DEFAULT_MAX_STROKE_THICKNESS};
+use crate::persistence::{write_json, write_json_as, FileKind, FlushPolicy, JsonFormat};
+use crate::render::validate_dpi;
+use crate::snapshots::DEFAULT_MAX_SNAPSHOTS;
+use crate::state::{AppState, DEFAULT_MAX_CONCURRENT_EXTRACTIONS};
+use crate::stroke_queue::DEFAULT_STROKE_AUTOSAVE;
+
+// Bumped when a field changes meaning, older files are upgraded on load
+pub const SETTINGS_VERSION: u32 = 1;
+
+// App-wide settings the backend acts on, persisted to settings.json. Fields missing from the
+// file take their default so new settings don't invalidate existing files.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    pub version: u32,
+    // thumbnails are rendered at page size divided by this
+    pub thumbnail_divisor: f32,
+    // when set, thumbnails are rendered at this DPI instead of by the divisor
+    pub thumbnail_dpi: Option<f32>,
+    // when set, thumbnails are instead fitted into this box so they all share its size
+    pub thumbnail_box: Option<ThumbnailBox>,
+    // DPI of rendered covers, None renders them at half the page size
+    pub cover_dpi: Option<f32>,
+    // pen and highlighter colors of documents without editor settings yet
+    pub default_pen_color: String,
+    pub default_highlighter_color: String,
+    // documents rendering covers and thumbnails at the same time
+    pub max_concurrent_extractions: usize,
+    // encoding of generated data files (strokes, thumbnails, dims)
+    pub data_file_format: JsonFormat,
+    // thumbnails generated per document, sampled evenly; None means every page
+    pub max_thumbnails: Option<u32>,
+    // strokes thicker than this are clamped when saved
+    pub max_stroke_thickness: u64,
+    // how often thumbnails and page sizes are persisted while a document is being extracted
+    pub thumbnail_flush: FlushPolicy,
+    // minimum gap between extraction progress events sent to the frontend
+    pub extraction_event_interval_ms: u64,
+    // annotation snapshots kept per document, the oldest are pruned past this
+    pub max_snapshots: usize,
+    // when strokes buffered by queue_stroke are written to strokes.json
+    pub stroke_autosave: FlushPolicy,
+    // remove_pdf archives annotations for a later re-import unless told otherwise
+    pub archive_removed_annotations: bool,
+    // new pdfs in this folder are registered automatically, see watch.rs
+    pub watch_folder: Option<String>,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            thumbnail_divisor: 3.0,
+            thumbnail_dpi: None,
+            thumbnail_box: None,
+            cover_dpi: None,
+            default_pen_color: "#ff0000".into(),
+            default_highlighter_color: "#ffff00".into(),
+            max_concurrent_extractions: DEFAULT_MAX_CONCURRENT_EXTRACTIONS,
+            data_file_format: JsonFormat::default(),
+            max_thumbnails: None,
+            max_stroke_thickness: DEFAULT_MAX_STROKE_THICKNESS,
+            thumbnail_flush: FlushPolicy::default(),
+            extraction_event_interval_ms: DEFAULT_EXTRACTION_EVENT_INTERVAL_MS,
+            max_snapshots: DEFAULT_MAX_SNAPSHOTS,
+            stroke_autosave: DEFAULT_STROKE_AUTOSAVE,
+            archive_removed_annotations: false,
+            watch_folder: None,
+        }
+    }
+}
+
+impl AppSettings {
+    fn validate(&self) -> Result<(), String> {
+        if !self.thumbnail_divisor.is_finite() || self.thumbnail_divisor < 1.0 {
+            return Err(format!(
+                "Invalid thumbnail divisor: {}",
+                self.thumbnail_divisor
+            ));
+        }
+        if let Some(dpi) = self.thumbnail_dpi {
+            validate_dpi(dpi)?;
+        }
+        if let Some(dpi) = self.cover_dpi {
+            validate_dpi(dpi)?;
+        }
+        if self
+            .thumbnail_box
+            .is_some_and(|size| size.width == 0 || size.height == 0)
+        {
+            return Err("Thumbnail box must be at least 1x1".to_string());
+        }
+        validate_color(&self.default_pen_color)?;
+        validate_color(&self.default_highlighter_color)?;
+        if self.max_concurrent_extractions == 0 {
+            return Err("At least one extraction must be allowed".to_string());
+        }
+        if self.max_thumbnails == Some(0) {
+            return Err("Thumbnail cap must be at least 1".to_string());
+        }
+        if self.max_stroke_thickness == 0 {
+            return Err("Maximum stroke thickness must be at least 1".to_string());
+        }
+        if self.max_snapshots == 0 {
+            return Err("At least one snapshot must be kept".to_string());
+        }
+        Ok(())
+    }
+}
+
+// Pixel size every thumbnail is rendered at, pages are fitted inside and letterboxed
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ThumbnailBox {
+    pub width: u32,
+    pub height: u32,
+}
+
+// Distinguishes a field set to null (Some(None)) from one left out (None)
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+// Partial update, only the provided fields change
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AppSettingsPatch {
+    pub(crate) thumbnail_divisor: Option<f32>,
+    #[serde(deserialize_with = "present")]
+    pub(crate) thumbnail_dpi: Option<Option<f32>>,
+    #[serde(deserialize_with = "present")]
+    pub(crate) thumbnail_box: Option<Option<ThumbnailBox>>,
+    #[serde(deserialize_with = "present")]
+    pub(crate) cover_dpi: Option<Option<f32>>,
+    pub(crate) default_pen_color: Option<String>,
+    pub(crate) default_highlighter_color: Option<String>,
+    pub(crate) max_concurrent_extractions: Option<usize>,
+    pub(crate) data_file_format: Option<JsonFormat>,
+    #[serde(deserialize_with = "present")]
+    pub(crate) max_thumbnails: Option<Option<u32>>,
+    pub(crate) max_stroke_thickness: Option<u64>,
+    pub(crate) thumbnail_flush: Option<FlushPolicy>,
+    pub(crate) extraction_event_interval_ms: Option<u64>,
+    pub(crate) max_snapshots: Option<usize>,
+    pub(crate) stroke_autosave: Option<FlushPolicy>,
+    pub(crate) archive_removed_annotations: Option<bool>,
+    // only set through set_watch_folder and clear_watch_folder, which also start and stop
+    // the watcher
+    #[serde(skip)]
+    pub(crate) watch_folder: Option<Option<String>>,
+}
+
+impl AppSettingsPatch {
+    fn apply(self, settings: &mut AppSettings) {
+        if let Some(divisor) = self.thumbnail_divisor {
+            settings.thumbnail_divisor = divisor;
+        }
+        if let Some(dpi) = self.thumbnail_dpi {
+            settings.thumbnail_dpi = dpi;
+        }
+        if let Some(thumbnail_box) = self.thumbnail_box {
+            settings.thumbnail_box = thumbnail_box;
+        }
+        if let Some(dpi) = self.cover_dpi {
+            settings.cover_dpi = dpi;
+        }
+        if let Some(color) = self.default_pen_color {
+            settings.default_pen_color = color;
+        }
+        if let Some(color) = self.default_highlighter_color {
+            settings.default_highlighter_color = color;
+        }
+        if let Some(max) = self.max_concurrent_extractions {
+            settings.max_concurrent_extractions = max;
+        }
+        if let Some(format) = self.data_file_format {
+            settings.data_file_format = format;
+        }
+        if let Some(max_thumbnails) = self.max_thumbnails {
+            settings.max_thumbnails = max_thumbnails;
+        }
+        if let Some(thickness) = self.max_stroke_thickness {
+            settings.max_stroke_thickness = thickness;
+        }
+        if let Some(policy) = self.thumbnail_flush {
+            settings.thumbnail_flush = policy;
+        }
+        if let Some(interval) = self.extraction_event_interval_ms {
+            settings.extraction_event_interval_ms = interval;
+        }
+        if let Some(max) = self.max_snapshots {
+            settings.max_snapshots = max;
+        }
+        if let Some(policy) = self.stroke_autosave {
+            settings.stroke_autosave = policy;
+        }
+        if let Some(archive) = self.archive_removed_annotations {
+            settings.archive_removed_annotations = archive;
+        }
+        if let Some(folder) = self.watch_folder {
+            settings.watch_folder = folder;
+        }
+    }
+}
+
+fn settings_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+    Ok(app_data_dir.join("settings.json"))
+}
+
+// The stored settings, and whether they were carried over from preferences.json
+fn read_settings(app_handle: &AppHandle) -> Result<(AppSettings, bool), String> {
+    let path = settings_path(app_handle)?;
+
+    // these settings used to live next to the theme in preferences.json, carry them over
+    let migrating = !path.exists();
+    let path = if migrating {
+        path.with_file_name("preferences.json")
+    } else {
+        path
+    };
+    if !path.exists() {
+        return Ok((AppSettings::default(), false));
+    }
+
+    let data = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let mut settings =
+        serde_json::from_str::<AppSettings>(&data).map_err(|e| format!("Invalid settings: {e}"))?;
+
+    if settings.version > SETTINGS_VERSION {
+        log::warn!(
+            "Settings were written by a newer version ({}), unknown fields are ignored",
+            settings.version
+        );
+    }
+    settings.version = SETTINGS_VERSION;
+
+    if let Err(e) = settings.validate() {
+        log::warn!("Ignoring invalid settings: {e}");
+        return Ok((AppSettings::default(), false));
+    }
+
+    Ok((settings, migrating))
+}
+
+// Puts the settings into effect, for the parts that aren't read on use
+fn apply_settings(state: &AppState, settings: AppSettings) {
+    state
+        .extraction_slots
+        .set_limit(settings.max_concurrent_extractions);
+    *state.settings.lock().unwrap() = settings;
+}
+
+// Loads settings.json into the shared state, called once at startup
+pub(crate) fn init_settings(app_handle: &AppHandle) {
+    let (settings, migrated) = read_settings(app_handle).unwrap_or_else(|e| {
+        log::warn!("Failed to load settings, using defaults: {e}");
+        (AppSettings::default(), false)
+    });
+
+    // once settings.json exists preferences.json isn't read again, so the migration runs once
+    if migrated {
+        let written = settings_path(app_handle)
+            .and_then(|path| write_json(app_handle, &path, &settings, FileKind::Settings));
+        match written {
+            Ok(()) => log::info!("Moved settings out of preferences.json"),
+            Err(e) => log::warn!("Failed to save settings carried over from preferences.json: {e}"),
+        }
+    }
+
+    apply_settings(&app_handle.state::<AppState>(), settings);
+}
+
+// Merges `patch` into the cached settings and persists the result
+pub(crate) fn patch_settings(
+    app_handle: &AppHandle,
+    patch: AppSettingsPatch,
+) -> Result<AppSettings, String> {
+    let state = app_handle.state::<AppState>();
+    let path = settings_path(app_handle)?;
+
+    // held from read to write, so concurrent patches can't drop each other's changes
+    let mut cached = state.settings.lock().unwrap();
+    let mut settings = cached.clone();
+    patch.apply(&mut settings);
+    settings.validate()?;
+
+    // write_json would take the settings lock again for the format
+    write_json_as(
+        app_handle,
+        &path,
+        &settings,
+        FileKind::Settings,
+        settings.data_file_format,
+    )?;
+    state
+        .extraction_slots
+        .set_limit(settings.max_concurrent_extractions);
+    *cached = settings.clone();
+
+    Ok(settings)
+}
+
+#[tauri::command]
+pub fn get_settings(app_handle: AppHandle) -> Result<AppSettings, String> {
+    log::info!("Loading app settings");
+    Ok(app_handle.state::<AppState>().settings())
+}
+
+#[tauri::command]
+pub fn update_settings(
+    app_handle: AppHandle,
+    settings: AppSettingsPatch,
+) -> Result<AppSettings, String> {
+    log::info!("Updating app settings: {settings:?}");
+    patch_settings(&app_handle, settings)
+}
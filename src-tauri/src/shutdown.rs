@@ -0,0 +1,64 @@
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tauri::{AppHandle, Manager};
+
+use crate::state::AppState;
+use crate::stroke_queue::flush_all_pending_strokes;
+
+// How long shutdown waits for background work before giving up on it
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+fn background_work_running(state: &AppState) -> bool {
+    state.extraction_slots.running() > 0
+        || !state.export_jobs.lock().unwrap().is_empty()
+        || !state.ocr_jobs.lock().unwrap().is_empty()
+}
+
+// Stops background work and waits for it to persist what it has. Extractions stop at the next
+// page and flush their thumbnails and dims, exports and OCR are cancelled like from the UI.
+// Strokes queued for autosave are written right away; settings and other annotations are
+// written as they change, so nothing else is pending.
+// Returns false when work was still running after the timeout.
+pub(crate) fn flush_and_close(app_handle: &AppHandle) -> bool {
+    let state = app_handle.state::<AppState>();
+    state.shutting_down.store(true, Ordering::Relaxed);
+    // no new registrations from the watch folder
+    *state.watcher.lock().unwrap() = None;
+
+    flush_all_pending_strokes(app_handle);
+
+    for cancelled in state.export_jobs.lock().unwrap().values() {
+        cancelled.store(true, Ordering::Relaxed);
+    }
+    for cancelled in state.ocr_jobs.lock().unwrap().values() {
+        cancelled.store(true, Ordering::Relaxed);
+    }
+
+    let started = Instant::now();
+    while background_work_running(&state) {
+        if started.elapsed() >= SHUTDOWN_TIMEOUT {
+            log::warn!("Background work still running after {SHUTDOWN_TIMEOUT:?}, closing anyway");
+            return false;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    // commands still holding the library keep it alive until they return
+    *state.pdfium.lock().unwrap() = Err("The app is shutting down".to_string());
+
+    true
+}
+
+// Called by the frontend before it closes the app, also run on exit. Returns whether all
+// background work finished in time.
+#[tauri::command]
+pub async fn shutdown(app_handle: AppHandle) -> Result<bool, String> {
+    log::info!("Shutting down");
+
+    tauri::async_runtime::spawn_blocking(move || flush_and_close(&app_handle))
+        .await
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,142 @@
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::eraser::distance_to_segment;
+use crate::error::AppError;
+use crate::pdf::{load_strokes_from_file, StrokePath};
+use crate::persistence::{write_json, FileKind};
+use crate::state::AppState;
+use crate::stroke_queue::flush_pending_strokes;
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SimplifyResult {
+    points_before: usize,
+    points_after: usize,
+}
+
+// Ramer-Douglas-Peucker: keeps the endpoints and every point further than `epsilon` from the
+// line through the points kept around it. Iterative so long strokes can't overflow the stack.
+pub(crate) fn simplify_path(path: &[StrokePath], epsilon: f64) -> Vec<StrokePath> {
+    if path.len() < 3 {
+        return path.to_vec();
+    }
+
+    let mut keep = vec![false; path.len()];
+    keep[0] = true;
+    keep[path.len() - 1] = true;
+
+    let mut ranges = vec![(0, path.len() - 1)];
+    while let Some((start, end)) = ranges.pop() {
+        let farthest = (start + 1..end)
+            .map(|i| (i, distance_to_segment(&path[i], &path[start], &path[end])))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        if let Some((index, distance)) = farthest {
+            if distance > epsilon {
+                keep[index] = true;
+                ranges.push((start, index));
+                ranges.push((index, end));
+            }
+        }
+    }
+
+    path.iter()
+        .zip(keep)
+        .filter(|(_, kept)| *kept)
+        .map(|(point, _)| point.clone())
+        .collect()
+}
+
+#[tauri::command]
+pub fn simplify_strokes(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    page_id: u32,
+    epsilon: f64,
+) -> Result<SimplifyResult, AppError> {
+    log::info!("Simplifying strokes on page {page_id} of pdf {pdf_id} (epsilon {epsilon})");
+
+    if !epsilon.is_finite() || epsilon < 0.0 {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid epsilon: {epsilon}"
+        )));
+    }
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+
+    let strokes_path = app_data_dir.join(format!("pdf_{pdf_id}/strokes.json"));
+
+    flush_pending_strokes(&app_handle, pdf_id)?;
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    let mut strokes = load_strokes_from_file(&app_handle, &strokes_path)?;
+    let Some(page_strokes) = strokes.inner.get_mut(&page_id) else {
+        return Ok(SimplifyResult::default());
+    };
+
+    let mut result = SimplifyResult::default();
+    for stroke in page_strokes.iter_mut() {
+        result.points_before += stroke.path.len();
+        stroke.path = simplify_path(&stroke.path, epsilon);
+        result.points_after += stroke.path.len();
+    }
+
+    if result.points_after < result.points_before {
+        write_json(&app_handle, &strokes_path, &strokes, FileKind::Strokes)?;
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f64, y: f64) -> StrokePath {
+        StrokePath { x, y }
+    }
+
+    // A stroke sampled every 0.05 units along an L with a little jitter, like a fast
+    // high-rate pen: 2001 points along x, then 2000 up to the end of the L
+    fn dense_l_stroke() -> Vec<StrokePath> {
+        let jitter = |i: usize| ((i * 7919) % 11) as f64 * 0.01;
+        let across = (0..=2000).map(|i| point(10.0 + i as f64 * 0.05, 10.0 + jitter(i)));
+        let up = (1..=2000).map(|i| point(110.0 + jitter(i), 10.0 + i as f64 * 0.05));
+        across.chain(up).collect()
+    }
+
+    #[test]
+    fn simplifies_a_dense_stroke_to_its_shape() {
+        let path = dense_l_stroke();
+        let simplified = simplify_path(&path, 0.5);
+
+        assert!(simplified.len() <= 5, "kept {} points", simplified.len());
+        // endpoints and the corner survive
+        let first = &simplified[0];
+        let last = &simplified[simplified.len() - 1];
+        assert_eq!((first.x, first.y), (path[0].x, path[0].y));
+        assert_eq!((last.x, last.y), (path[4000].x, path[4000].y));
+        assert!(simplified
+            .iter()
+            .any(|p| (p.x - 110.0).abs() < 0.2 && (p.y - 10.0).abs() < 0.2));
+
+        // every dropped point is within epsilon of what's left
+        for p in &path {
+            let closest = simplified
+                .windows(2)
+                .map(|segment| distance_to_segment(p, &segment[0], &segment[1]))
+                .fold(f64::INFINITY, f64::min);
+            assert!(closest <= 0.5);
+        }
+    }
+
+    #[test]
+    fn keeps_short_and_zero_epsilon_paths() {
+        let two = vec![point(0.0, 0.0), point(5.0, 5.0)];
+        assert_eq!(simplify_path(&two, 10.0).len(), 2);
+
+        let zigzag: Vec<_> = (0..50).map(|i| point(i as f64, (i % 2) as f64)).collect();
+        assert_eq!(simplify_path(&zigzag, 0.0).len(), 50);
+    }
+}
@@ -0,0 +1,215 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::error::AppError;
+use crate::pdf::{find_pdf_entry, load_bookmarks_from_file, load_strokes_from_file};
+use crate::persistence::{write_json, FileKind};
+use crate::state::AppState;
+use crate::stroke_queue::flush_pending_strokes;
+use crate::timestamp::file_timestamp_ms;
+
+pub const DEFAULT_MAX_SNAPSHOTS: usize = 20;
+
+// Describes a snapshot, stored as snapshot.json next to its copies of the annotations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    id: String,
+    label: String,
+    created_at: String,
+    stroke_count: usize,
+    bookmark_count: usize,
+}
+
+fn snapshots_dir(folder: &Path) -> PathBuf {
+    folder.join("snapshots")
+}
+
+// Snapshot ids are folder names, anything that could point outside snapshots/ is rejected
+fn snapshot_dir(folder: &Path, snapshot_id: &str) -> Result<PathBuf, AppError> {
+    let valid = !snapshot_id.is_empty()
+        && snapshot_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid snapshot id: {snapshot_id}"
+        )));
+    }
+    Ok(snapshots_dir(folder).join(snapshot_id))
+}
+
+// Snapshot folders sorted oldest first, the timestamped ids sort chronologically
+fn snapshot_ids(folder: &Path) -> Result<Vec<String>, AppError> {
+    let dir = snapshots_dir(folder);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut ids = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            ids.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    ids.sort();
+    Ok(ids)
+}
+
+// Copies the current strokes and bookmarks into a new snapshot, then prunes the oldest ones
+// past the configured maximum. The caller holds the pdf lock.
+fn create_snapshot(app_handle: &AppHandle, folder: &Path, label: &str) -> Result<String, AppError> {
+    let strokes = load_strokes_from_file(app_handle, &folder.join("strokes.json"))?;
+    let bookmarks = load_bookmarks_from_file(app_handle, &folder.join("bookmarks.json"))?;
+
+    let timestamp = file_timestamp_ms();
+    let mut snapshot_id = timestamp.clone();
+    let mut suffix = 1;
+    while snapshots_dir(folder).join(&snapshot_id).exists() {
+        snapshot_id = format!("{timestamp}-{suffix}");
+        suffix += 1;
+    }
+    let dir = snapshots_dir(folder).join(&snapshot_id);
+
+    let info = SnapshotInfo {
+        id: snapshot_id.clone(),
+        label: label.to_string(),
+        created_at: Local::now().to_rfc3339(),
+        stroke_count: strokes.inner.values().map(Vec::len).sum(),
+        bookmark_count: bookmarks.len(),
+    };
+
+    let written = write_json(
+        app_handle,
+        &dir.join("strokes.json"),
+        &strokes,
+        FileKind::Snapshot,
+    )
+    .and_then(|_| {
+        write_json(
+            app_handle,
+            &dir.join("bookmarks.json"),
+            &bookmarks,
+            FileKind::Snapshot,
+        )
+    })
+    // written last, a folder without it is an incomplete snapshot
+    .and_then(|_| {
+        write_json(
+            app_handle,
+            &dir.join("snapshot.json"),
+            &info,
+            FileKind::Snapshot,
+        )
+    });
+    if let Err(e) = written {
+        let _ = fs::remove_dir_all(&dir);
+        return Err(AppError::Io(e));
+    }
+
+    let max_snapshots = app_handle.state::<AppState>().settings().max_snapshots;
+    let ids = snapshot_ids(folder)?;
+    for old_id in ids.iter().take(ids.len().saturating_sub(max_snapshots)) {
+        if let Err(e) = fs::remove_dir_all(snapshots_dir(folder).join(old_id)) {
+            log::warn!("Failed to prune snapshot {old_id}: {e}");
+        }
+    }
+
+    Ok(snapshot_id)
+}
+
+#[tauri::command]
+pub fn snapshot_annotations(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    label: String,
+) -> Result<String, AppError> {
+    log::info!("Snapshotting annotations of pdf {pdf_id} ({label})");
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    find_pdf_entry(&app_data_dir, pdf_id)?;
+    let folder = app_data_dir.join(format!("pdf_{pdf_id}"));
+
+    flush_pending_strokes(&app_handle, pdf_id)?;
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    create_snapshot(&app_handle, &folder, label.trim())
+}
+
+// Newest first. Incomplete or unreadable snapshots are skipped.
+#[tauri::command]
+pub fn list_snapshots(app_handle: AppHandle, pdf_id: u64) -> Result<Vec<SnapshotInfo>, AppError> {
+    log::info!("Listing snapshots of pdf {pdf_id}");
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    let folder = app_data_dir.join(format!("pdf_{pdf_id}"));
+
+    let mut snapshots = Vec::new();
+    for id in snapshot_ids(&folder)?.into_iter().rev() {
+        let info_path = snapshots_dir(&folder).join(&id).join("snapshot.json");
+        let info = fs::read_to_string(&info_path)
+            .map_err(AppError::from)
+            .and_then(|data| Ok(serde_json::from_str::<SnapshotInfo>(&data)?));
+        match info {
+            Ok(info) => snapshots.push(info),
+            Err(e) => log::warn!("Skipping snapshot {id} of pdf {pdf_id}: {e}"),
+        }
+    }
+
+    Ok(snapshots)
+}
+
+// Replaces the strokes and bookmarks with the snapshot's. The current annotations are
+// snapshotted first, so a restore can itself be undone.
+#[tauri::command]
+pub fn restore_snapshot(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    snapshot_id: String,
+) -> Result<(), AppError> {
+    log::info!("Restoring snapshot {snapshot_id} of pdf {pdf_id}");
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    let folder = app_data_dir.join(format!("pdf_{pdf_id}"));
+    let dir = snapshot_dir(&folder, &snapshot_id)?;
+
+    if !dir.join("snapshot.json").exists() {
+        return Err(AppError::NotFound(format!(
+            "Snapshot {snapshot_id} not found"
+        )));
+    }
+
+    // queued strokes go into the "before" snapshot instead of on top of the restored ones
+    flush_pending_strokes(&app_handle, pdf_id)?;
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    let strokes = load_strokes_from_file(&app_handle, &dir.join("strokes.json"))?;
+    let bookmarks = load_bookmarks_from_file(&app_handle, &dir.join("bookmarks.json"))?;
+
+    create_snapshot(
+        &app_handle,
+        &folder,
+        &format!("Before restoring {snapshot_id}"),
+    )?;
+
+    write_json(
+        &app_handle,
+        &folder.join("strokes.json"),
+        &strokes,
+        FileKind::Strokes,
+    )?;
+    write_json(
+        &app_handle,
+        &folder.join("bookmarks.json"),
+        &bookmarks,
+        FileKind::Bookmarks,
+    )?;
+
+    Ok(())
+}
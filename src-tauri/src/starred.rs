@@ -0,0 +1,72 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tauri::{AppHandle, Manager};
+
+use crate::error::AppError;
+use crate::persistence::{write_json, FileKind};
+use crate::state::AppState;
+
+// Starred pages are a plain set of page numbers, kept sorted so the file reads naturally
+pub type StarredPages = BTreeSet<u32>;
+
+pub(crate) fn starred_path(app_handle: &AppHandle, pdf_id: u64) -> Result<PathBuf, String> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+    Ok(app_data_dir.join(format!("pdf_{pdf_id}/starred.json")))
+}
+
+pub(crate) fn load_starred(path: &Path) -> Result<StarredPages, String> {
+    if !path.exists() {
+        return Ok(StarredPages::new());
+    }
+
+    let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    if data.trim().is_empty() {
+        return Ok(StarredPages::new());
+    }
+
+    serde_json::from_str::<StarredPages>(&data).map_err(|e| format!("Invalid JSON: {e}"))
+}
+
+#[tauri::command]
+pub fn get_starred_pages(app_handle: AppHandle, pdf_id: u64) -> Result<Vec<u32>, AppError> {
+    log::info!("Loading starred pages of pdf {pdf_id}");
+
+    let path = starred_path(&app_handle, pdf_id)?;
+    Ok(load_starred(&path)?.into_iter().collect())
+}
+
+// Returns whether the page is starred after the toggle
+#[tauri::command]
+pub fn toggle_starred_page(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    page: u32,
+) -> Result<bool, AppError> {
+    log::info!("Toggling star on page {page} of pdf {pdf_id}");
+
+    if page == 0 {
+        return Err(AppError::InvalidInput(
+            "Page numbers start at 1".to_string(),
+        ));
+    }
+
+    let path = starred_path(&app_handle, pdf_id)?;
+
+    let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    let mut starred = load_starred(&path)?;
+    let is_starred = if starred.remove(&page) {
+        false
+    } else {
+        starred.insert(page)
+    };
+
+    write_json(&app_handle, &path, &starred, FileKind::Starred)?;
+    Ok(is_starred)
+}
@@ -1,6 +1,153 @@
 // src/state.rs
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Instant;
+
+use notify::RecommendedWatcher;
+use pdfium_render::prelude::Pdfium;
+
+use crate::pdf::{ExtractionProgress, Stroke};
+use crate::pdfium::PdfiumBinding;
+use crate::settings::AppSettings;
+use crate::storage::DiskUsage;
+
+pub const DEFAULT_MAX_CONCURRENT_EXTRACTIONS: usize = 2;
+
+// Counting semaphore whose limit can change while permits are held
+pub struct Semaphore {
+    counts: Mutex<(usize, usize)>, // (running, limit)
+    released: Condvar,
+}
+
+impl Semaphore {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            counts: Mutex::new((0, limit.max(1))),
+            released: Condvar::new(),
+        }
+    }
+
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        let mut counts = self.counts.lock().unwrap();
+        if counts.0 < counts.1 {
+            counts.0 += 1;
+            Some(SemaphorePermit { semaphore: self })
+        } else {
+            None
+        }
+    }
+
+    // Blocks until a permit is free, only call from blocking threads
+    pub fn acquire(&self) -> SemaphorePermit<'_> {
+        let mut counts = self
+            .released
+            .wait_while(self.counts.lock().unwrap(), |(running, limit)| {
+                *running >= *limit
+            })
+            .unwrap();
+        counts.0 += 1;
+        SemaphorePermit { semaphore: self }
+    }
+
+    // Permits currently held
+    pub fn running(&self) -> usize {
+        self.counts.lock().unwrap().0
+    }
+
+    pub fn set_limit(&self, limit: usize) {
+        self.counts.lock().unwrap().1 = limit.max(1);
+        self.released.notify_all();
+    }
+}
+
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        self.semaphore.counts.lock().unwrap().0 -= 1;
+        self.semaphore.released.notify_one();
+    }
+}
 
 pub struct AppState {
     pub lib_path: PathBuf,
+    // bound once at startup and shared by every command, Err keeps the startup failure.
+    // Replaced by an error on shutdown so the library is released with the last user.
+    pub pdfium: Mutex<Result<(Arc<Pdfium>, PdfiumBinding), String>>,
+    // cancellation flags of running exports, keyed by export id
+    pub export_jobs: Mutex<HashMap<String, Arc<AtomicBool>>>,
+    // cancellation flags of running OCR passes, keyed by pdf id
+    pub ocr_jobs: Mutex<HashMap<u64, Arc<AtomicBool>>>,
+    // progress of running thumbnail extractions, keyed by pdf id
+    pub extraction_progress: Mutex<HashMap<u64, ExtractionProgress>>,
+    // bounds how many documents render covers and thumbnails at the same time
+    pub extraction_slots: Semaphore,
+    // cached copy of settings.json, features read it on use
+    pub settings: Mutex<AppSettings>,
+    // annotation keys of encrypted pdfs unlocked this session, keyed by pdf id
+    pub unlocked_keys: Mutex<HashMap<u64, [u8; 32]>>,
+    // strokes queued by queue_stroke and not written yet, keyed by pdf id, with their page
+    pub pending_strokes: Mutex<HashMap<u64, Vec<(u32, Stroke)>>>,
+    // disk usage of pdf folders with when it was computed, keyed by pdf id
+    pub disk_usage: Mutex<HashMap<u64, (Instant, DiskUsage)>>,
+    // watcher of the folder new pdfs are registered from, dropping it stops watching
+    pub watcher: Mutex<Option<RecommendedWatcher>>,
+    // set by shutdown, background work stops at its next checkpoint
+    pub shutting_down: AtomicBool,
+    // serializes read-modify-write cycles on pdfs.json
+    pub registry_lock: Mutex<()>,
+    // serializes read-modify-write cycles on study_sets.json, taken after the registry lock
+    pub study_sets_lock: Mutex<()>,
+    // serializes read-modify-write cycles on a pdf's sidecar files
+    pdf_locks: Mutex<HashMap<u64, Arc<Mutex<()>>>>,
+}
+
+impl AppState {
+    pub fn new(lib_path: PathBuf, pdfium: Result<(Pdfium, PdfiumBinding), String>) -> Self {
+        Self {
+            lib_path,
+            pdfium: Mutex::new(pdfium.map(|(pdfium, binding)| (Arc::new(pdfium), binding))),
+            export_jobs: Mutex::new(HashMap::new()),
+            ocr_jobs: Mutex::new(HashMap::new()),
+            extraction_progress: Mutex::new(HashMap::new()),
+            extraction_slots: Semaphore::new(DEFAULT_MAX_CONCURRENT_EXTRACTIONS),
+            settings: Mutex::new(AppSettings::default()),
+            unlocked_keys: Mutex::new(HashMap::new()),
+            pending_strokes: Mutex::new(HashMap::new()),
+            disk_usage: Mutex::new(HashMap::new()),
+            watcher: Mutex::new(None),
+            shutting_down: AtomicBool::new(false),
+            registry_lock: Mutex::new(()),
+            study_sets_lock: Mutex::new(()),
+            pdf_locks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn pdf_lock(&self, pdf_id: u64) -> Arc<Mutex<()>> {
+        self.pdf_locks
+            .lock()
+            .unwrap()
+            .entry(pdf_id)
+            .or_default()
+            .clone()
+    }
+
+    pub fn settings(&self) -> AppSettings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    pub fn pdfium(&self) -> Result<Arc<Pdfium>, String> {
+        match &*self.pdfium.lock().unwrap() {
+            Ok((pdfium, _)) => Ok(pdfium.clone()),
+            Err(e) => Err(format!("PDF features are unavailable: {e}")),
+        }
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::Relaxed)
+    }
 }
@@ -0,0 +1,211 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::pdf::{read_pdf_entries, PdfEntry};
+use crate::state::AppState;
+
+// How long a computed disk usage is reused. The storage screen lists every pdf, walking all
+// their folders each time it refreshes is what's slow, and sizes that old are close enough.
+const DISK_USAGE_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct DiskUsage {
+    pdf: u64,
+    thumbnails: u64,
+    annotations: u64,
+    text: u64,
+    // previous versions kept by write_json, see backup_path
+    backups: u64,
+    other: u64,
+    total: u64,
+}
+
+impl DiskUsage {
+    fn add(&mut self, relative_path: &Path, size: u64) {
+        let file_name = relative_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        let in_thumbnails = relative_path.starts_with("thumbnails")
+            || relative_path.starts_with("annotated_thumbnails")
+            || relative_path.starts_with("renders");
+
+        let bucket = if file_name.ends_with(".bak") {
+            &mut self.backups
+        } else if file_name.ends_with(".pdf") {
+            &mut self.pdf
+        } else if in_thumbnails
+            || matches!(
+                file_name,
+                "thumbs.json" | "thumb_layouts.json" | "annotated_thumbs.json"
+            )
+            || file_name.contains("_cover_")
+        {
+            &mut self.thumbnails
+        } else if matches!(
+            file_name,
+            "strokes.json" | "bookmarks.json" | "starred.json" | "note.json" | "page_labels.json"
+        ) {
+            &mut self.annotations
+        } else if matches!(file_name, "text.json" | "text_stats.json") {
+            &mut self.text
+        } else {
+            &mut self.other
+        };
+
+        *bucket += size;
+        self.total += size;
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PdfWithUsage {
+    #[serde(flatten)]
+    entry: PdfEntry,
+    usage: DiskUsage,
+}
+
+fn walk_folder(root: &Path, dir: &Path, usage: &mut DiskUsage) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let path = entry.path();
+        let metadata = entry.metadata().map_err(|e| e.to_string())?;
+
+        if metadata.is_dir() {
+            walk_folder(root, &path, usage)?;
+        } else {
+            let relative_path = path.strip_prefix(root).unwrap_or(&path);
+            usage.add(relative_path, metadata.len());
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn pdf_disk_usage(app_data_dir: &Path, pdf_id: u64) -> Result<DiskUsage, String> {
+    let folder_path = app_data_dir.join(format!("pdf_{pdf_id}"));
+    let mut usage = DiskUsage::default();
+
+    // a missing folder simply takes no space
+    if folder_path.exists() {
+        walk_folder(&folder_path, &folder_path, &mut usage)?;
+    }
+
+    Ok(usage)
+}
+
+// `pdf_disk_usage`, reusing one computed less than DISK_USAGE_TTL ago unless `refresh` is set
+fn cached_disk_usage(
+    app_handle: &AppHandle,
+    app_data_dir: &Path,
+    pdf_id: u64,
+    refresh: bool,
+) -> Result<DiskUsage, String> {
+    let state = app_handle.state::<AppState>();
+    if !refresh {
+        if let Some((computed_at, usage)) = state.disk_usage.lock().unwrap().get(&pdf_id) {
+            if computed_at.elapsed() < DISK_USAGE_TTL {
+                return Ok(usage.clone());
+            }
+        }
+    }
+
+    let usage = pdf_disk_usage(app_data_dir, pdf_id)?;
+    state
+        .disk_usage
+        .lock()
+        .unwrap()
+        .insert(pdf_id, (Instant::now(), usage.clone()));
+    Ok(usage)
+}
+
+// Drops cached disk usage, of one pdf or with None of every pdf, e.g. when ids change
+pub(crate) fn forget_disk_usage(app_handle: &AppHandle, pdf_id: Option<u64>) {
+    let state = app_handle.state::<AppState>();
+    let mut cache = state.disk_usage.lock().unwrap();
+    match pdf_id {
+        Some(pdf_id) => {
+            cache.remove(&pdf_id);
+        }
+        None => cache.clear(),
+    }
+}
+
+// Sizes are cached for a short while, `refresh` walks the folder again regardless
+#[tauri::command]
+pub fn get_pdf_disk_usage(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    refresh: Option<bool>,
+) -> Result<DiskUsage, String> {
+    log::info!("Computing disk usage of pdf {pdf_id}");
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    cached_disk_usage(&app_handle, &app_data_dir, pdf_id, refresh.unwrap_or(false))
+}
+
+#[tauri::command]
+pub fn list_pdf_with_usage(
+    app_handle: AppHandle,
+    refresh: Option<bool>,
+) -> Result<Vec<PdfWithUsage>, String> {
+    log::info!("Listing pdfs with disk usage");
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    read_pdf_entries(&app_data_dir.join("pdfs.json"))?
+        .into_iter()
+        .map(|entry| {
+            let usage = cached_disk_usage(
+                &app_handle,
+                &app_data_dir,
+                entry.id,
+                refresh.unwrap_or(false),
+            )?;
+            Ok(PdfWithUsage { entry, usage })
+        })
+        .collect()
+}
+
+// Covers thumbnails, cover and sidecars on top of the clone itself
+const DERIVED_DATA_FACTOR: f64 = 0.5;
+const SAFETY_MARGIN: u64 = 16 * 1024 * 1024;
+
+pub(crate) fn free_disk_space(path: &Path) -> Result<u64, String> {
+    fs2::available_space(path).map_err(|e| e.to_string())
+}
+
+// Fails with "insufficient_disk_space" when importing a file of `source_size` bytes won't fit
+pub(crate) fn ensure_space_for_import(app_data_dir: &Path, source_size: u64) -> Result<(), String> {
+    fs::create_dir_all(app_data_dir).map_err(|e| e.to_string())?;
+
+    let required = source_size + (source_size as f64 * DERIVED_DATA_FACTOR) as u64 + SAFETY_MARGIN;
+    let available = free_disk_space(app_data_dir)?;
+    if available < required {
+        log::warn!("Not enough disk space to import: {available} bytes free, {required} needed");
+        return Err("insufficient_disk_space".to_string());
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_free_disk_space(app_handle: AppHandle) -> Result<u64, String> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    fs::create_dir_all(&app_data_dir).map_err(|e| e.to_string())?;
+    free_disk_space(&app_data_dir)
+}
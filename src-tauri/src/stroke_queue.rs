@@ -0,0 +1,140 @@
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::error::AppError;
+use crate::pdf::{load_strokes_from_file, Stroke};
+use crate::persistence::{write_json_keeping_backup, FileKind, FlushPolicy};
+use crate::state::AppState;
+
+pub const DEFAULT_STROKE_AUTOSAVE: FlushPolicy = FlushPolicy {
+    every_updates: 20,
+    every_ms: 2000,
+};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StrokeQueueAck {
+    pdf_id: u64,
+    // strokes of the pdf waiting to be written, 0 when this one triggered a flush
+    buffered: usize,
+}
+
+// Writes the queued strokes of a pdf to strokes.json in the order they were queued. Holding
+// the pdf lock while taking the queue keeps two flushes from writing batches out of order.
+// On failure the strokes go back to the front of the queue. Returns how many were written.
+pub(crate) fn flush_pending_strokes(
+    app_handle: &AppHandle,
+    pdf_id: u64,
+) -> Result<usize, AppError> {
+    let state = app_handle.state::<AppState>();
+    let lock = state.pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    let pending = state
+        .pending_strokes
+        .lock()
+        .unwrap()
+        .remove(&pdf_id)
+        .unwrap_or_default();
+    if pending.is_empty() {
+        return Ok(0);
+    }
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    let strokes_path = app_data_dir.join(format!("pdf_{pdf_id}/strokes.json"));
+
+    let written = load_strokes_from_file(app_handle, &strokes_path).and_then(|mut strokes| {
+        for (page, stroke) in pending.iter().cloned() {
+            strokes.merge(page, stroke);
+        }
+        // the backup from the last explicit save is worth more than one from seconds ago
+        write_json_keeping_backup(app_handle, &strokes_path, &strokes, FileKind::Strokes)
+            .map_err(AppError::from)
+    });
+
+    match written {
+        Ok(()) => Ok(pending.len()),
+        Err(e) => {
+            let mut queues = state.pending_strokes.lock().unwrap();
+            let queue = queues.entry(pdf_id).or_default();
+            queue.splice(0..0, pending);
+            Err(e)
+        }
+    }
+}
+
+// Flushes every pdf with queued strokes, used on shutdown
+pub(crate) fn flush_all_pending_strokes(app_handle: &AppHandle) {
+    let pdf_ids: Vec<u64> = app_handle
+        .state::<AppState>()
+        .pending_strokes
+        .lock()
+        .unwrap()
+        .keys()
+        .copied()
+        .collect();
+
+    for pdf_id in pdf_ids {
+        if let Err(e) = flush_pending_strokes(app_handle, pdf_id) {
+            log::error!("Failed to flush queued strokes of pdf {pdf_id}: {e}");
+        }
+    }
+}
+
+// Buffers a stroke instead of writing it right away, for rapid drawing. The queue is written
+// once it holds `stroke_autosave.every_updates` strokes, or `every_ms` after its first stroke,
+// whichever comes first. It's kept per pdf, so it outlives page switches, and is flushed on
+// shutdown and before strokes are loaded.
+#[tauri::command]
+pub async fn queue_stroke(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    page_id: u32,
+    mut stroke: Stroke,
+) -> Result<StrokeQueueAck, AppError> {
+    log::info!("Queueing stroke on page {page_id} of pdf {pdf_id}");
+
+    let state = app_handle.state::<AppState>();
+    let settings = state.settings();
+    let policy = settings.stroke_autosave;
+    stroke.prepare(settings.max_stroke_thickness)?;
+
+    let buffered = {
+        let mut queues = state.pending_strokes.lock().unwrap();
+        let queue = queues.entry(pdf_id).or_default();
+        queue.push((page_id, stroke));
+        queue.len()
+    };
+
+    if buffered >= policy.every_updates.max(1) as usize {
+        flush_pending_strokes(&app_handle, pdf_id)?;
+        return Ok(StrokeQueueAck {
+            pdf_id,
+            buffered: 0,
+        });
+    }
+
+    // the first stroke of a batch starts its timer, later ones ride along
+    if buffered == 1 {
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn_blocking(move || loop {
+            thread::sleep(Duration::from_millis(policy.every_ms));
+            match flush_pending_strokes(&app_handle, pdf_id) {
+                Ok(_) => break,
+                // the strokes are back in the queue, nothing else would write them
+                Err(e) => log::error!("Failed to autosave strokes of pdf {pdf_id}, retrying: {e}"),
+            }
+        });
+    }
+
+    Ok(StrokeQueueAck { pdf_id, buffered })
+}
+
+// Writes the queued strokes of a pdf now, e.g. before closing it. Returns how many were written.
+#[tauri::command]
+pub async fn flush_strokes(app_handle: AppHandle, pdf_id: u64) -> Result<usize, AppError> {
+    log::info!("Flushing queued strokes of pdf {pdf_id}");
+    flush_pending_strokes(&app_handle, pdf_id)
+}
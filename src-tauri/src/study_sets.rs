@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::error::AppError;
+use crate::pdf::{pdf_page_count, read_pdf_entries};
+use crate::persistence::{write_json, FileKind};
+use crate::state::AppState;
+use crate::timestamp::{id_timestamp, unique_id};
+
+const MAX_LABEL_LEN: usize = 200;
+
+// A page of some pdf in the library, kept in a study set
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StudySetItem {
+    pub id: String,
+    pub pdf_id: u64,
+    pub page: u32,
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+// Pages from any number of pdfs, e.g. everything to revise for one exam. Unlike collections,
+// which group whole pdfs, a set points at single pages and keeps them in the order added.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StudySet {
+    pub id: String,
+    pub name: String,
+    pub items: Vec<StudySetItem>,
+    // same format as collection times
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StudySetsFile {
+    pub study_sets: Vec<StudySet>,
+}
+
+// An item with what it points at looked up in the registry
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedStudySetItem {
+    id: String,
+    pdf_id: u64,
+    page: u32,
+    label: Option<String>,
+    // None when the pdf is no longer in the library
+    file_name: Option<String>,
+    // false when the pdf is gone or no longer has the page, e.g. after its file was refreshed
+    available: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedStudySet {
+    id: String,
+    name: String,
+    items: Vec<ResolvedStudySetItem>,
+}
+
+pub(crate) fn study_sets_path(app_handle: &AppHandle) -> Result<PathBuf, AppError> {
+    Ok(app_handle.path().app_data_dir()?.join("study_sets.json"))
+}
+
+pub(crate) fn read_study_sets(path: &Path) -> Result<StudySetsFile, AppError> {
+    if !path.exists() {
+        return Ok(StudySetsFile::default());
+    }
+
+    let data = fs::read_to_string(path)?;
+    if data.trim().is_empty() {
+        return Ok(StudySetsFile::default());
+    }
+
+    Ok(serde_json::from_str(&data)?)
+}
+
+pub(crate) fn write_study_sets(
+    app_handle: &AppHandle,
+    path: &Path,
+    data: &StudySetsFile,
+) -> Result<(), String> {
+    write_json(app_handle, path, data, FileKind::StudySets)
+}
+
+// Moves the items of `pdf_id` along with their pages after a page edit, `remap` giving the
+// new number of each old page. Items of deleted pages are dropped. Returns whether any set
+// changed, the caller writes `data` back under the study sets lock.
+pub(crate) fn remap_study_set_pages(
+    data: &mut StudySetsFile,
+    pdf_id: u64,
+    remap: impl Fn(u32) -> Option<u32>,
+) -> bool {
+    let mut changed = false;
+    for set in &mut data.study_sets {
+        let before = set.items.len();
+        let mut moved = false;
+        set.items.retain_mut(|item| {
+            if item.pdf_id != pdf_id {
+                return true;
+            }
+            match remap(item.page) {
+                Some(page) => {
+                    moved |= page != item.page;
+                    item.page = page;
+                    true
+                }
+                None => false,
+            }
+        });
+        if moved || set.items.len() != before {
+            set.updated_at = id_timestamp();
+            changed = true;
+        }
+    }
+    changed
+}
+
+fn find_set<'a>(data: &'a mut StudySetsFile, set_id: &str) -> Result<&'a mut StudySet, AppError> {
+    data.study_sets
+        .iter_mut()
+        .find(|set| set.id == set_id)
+        .ok_or_else(|| AppError::NotFound("Study set not found".to_string()))
+}
+
+// Study sets without their items resolved, in creation order
+#[tauri::command]
+pub fn get_study_sets(app_handle: AppHandle) -> Result<Vec<StudySet>, AppError> {
+    let path = study_sets_path(&app_handle)?;
+    Ok(read_study_sets(&path)?.study_sets)
+}
+
+#[tauri::command]
+pub fn create_study_set(app_handle: AppHandle, name: String) -> Result<StudySet, AppError> {
+    log::info!("Creating study set {name}");
+
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput(
+            "Study set name cannot be empty".to_string(),
+        ));
+    }
+
+    let path = study_sets_path(&app_handle)?;
+    let state = app_handle.state::<AppState>();
+    let _guard = state.study_sets_lock.lock().unwrap();
+    let mut data = read_study_sets(&path)?;
+    if data.study_sets.iter().any(|set| set.name == name) {
+        return Err(AppError::InvalidInput(format!(
+            "Study set with name '{name}' already exists"
+        )));
+    }
+
+    let now = id_timestamp();
+    let set = StudySet {
+        id: unique_id(),
+        name,
+        items: Vec::new(),
+        created_at: now.clone(),
+        updated_at: now,
+    };
+
+    data.study_sets.push(set.clone());
+    write_study_sets(&app_handle, &path, &data)?;
+    Ok(set)
+}
+
+#[tauri::command]
+pub fn delete_study_set(app_handle: AppHandle, set_id: String) -> Result<bool, AppError> {
+    log::info!("Deleting study set {set_id}");
+
+    let path = study_sets_path(&app_handle)?;
+    let state = app_handle.state::<AppState>();
+    let _guard = state.study_sets_lock.lock().unwrap();
+    let mut data = read_study_sets(&path)?;
+
+    let original_len = data.study_sets.len();
+    data.study_sets.retain(|set| set.id != set_id);
+    if data.study_sets.len() == original_len {
+        return Err(AppError::NotFound("Study set not found".to_string()));
+    }
+
+    write_study_sets(&app_handle, &path, &data)?;
+    Ok(true)
+}
+
+// Appends a page to a set. The same page can be added more than once, e.g. under different
+// labels. Returns the new item.
+#[tauri::command]
+pub fn add_to_study_set(
+    app_handle: AppHandle,
+    set_id: String,
+    pdf_id: u64,
+    page: u32,
+    label: Option<String>,
+) -> Result<StudySetItem, AppError> {
+    log::info!("Adding page {page} of pdf {pdf_id} to study set {set_id}");
+
+    let label = label
+        .map(|label| label.trim().to_string())
+        .filter(|label| !label.is_empty());
+    if label
+        .as_ref()
+        .is_some_and(|label| label.chars().count() > MAX_LABEL_LEN)
+    {
+        return Err(AppError::InvalidInput(format!(
+            "Labels can be at most {MAX_LABEL_LEN} characters"
+        )));
+    }
+
+    // also fails for pdfs that aren't in the library
+    let page_count = pdf_page_count(&app_handle, pdf_id)?;
+    if page == 0 || page > page_count {
+        return Err(AppError::InvalidInput(format!(
+            "Page {page} is out of range (1-{page_count})"
+        )));
+    }
+
+    let path = study_sets_path(&app_handle)?;
+    let state = app_handle.state::<AppState>();
+    let _guard = state.study_sets_lock.lock().unwrap();
+    let mut data = read_study_sets(&path)?;
+    let set = find_set(&mut data, &set_id)?;
+
+    let item = StudySetItem {
+        id: unique_id(),
+        pdf_id,
+        page,
+        label,
+    };
+    set.items.push(item.clone());
+    set.updated_at = id_timestamp();
+
+    write_study_sets(&app_handle, &path, &data)?;
+    Ok(item)
+}
+
+// Returns whether the item was in the set
+#[tauri::command]
+pub fn remove_from_study_set(
+    app_handle: AppHandle,
+    set_id: String,
+    item_id: String,
+) -> Result<bool, AppError> {
+    log::info!("Removing item {item_id} from study set {set_id}");
+
+    let path = study_sets_path(&app_handle)?;
+    let state = app_handle.state::<AppState>();
+    let _guard = state.study_sets_lock.lock().unwrap();
+    let mut data = read_study_sets(&path)?;
+    let set = find_set(&mut data, &set_id)?;
+
+    let original_len = set.items.len();
+    set.items.retain(|item| item.id != item_id);
+    if set.items.len() == original_len {
+        return Ok(false);
+    }
+    set.updated_at = id_timestamp();
+
+    write_study_sets(&app_handle, &path, &data)?;
+    Ok(true)
+}
+
+// A set with each item's pdf looked up. Items whose pdf was removed, or that point past its
+// last page, stay in the set but are marked unavailable.
+#[tauri::command]
+pub fn get_study_set(app_handle: AppHandle, set_id: String) -> Result<ResolvedStudySet, AppError> {
+    log::info!("Loading study set {set_id}");
+
+    let path = study_sets_path(&app_handle)?;
+    let mut data = read_study_sets(&path)?;
+    let set = find_set(&mut data, &set_id)?;
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    let pdfs: HashMap<u64, _> = read_pdf_entries(&app_data_dir.join("pdfs.json"))?
+        .into_iter()
+        .map(|pdf| (pdf.id, pdf))
+        .collect();
+
+    let items = set
+        .items
+        .iter()
+        .map(|item| {
+            let pdf = pdfs.get(&item.pdf_id);
+            // entries registered before page counts were kept can't be checked here
+            let has_page = pdf
+                .and_then(|pdf| pdf.page_count)
+                .is_none_or(|page_count| item.page <= page_count);
+            ResolvedStudySetItem {
+                id: item.id.clone(),
+                pdf_id: item.pdf_id,
+                page: item.page,
+                label: item.label.clone(),
+                file_name: pdf.map(|pdf| pdf.file_name.clone()),
+                available: pdf.is_some() && has_page,
+            }
+        })
+        .collect();
+
+    Ok(ResolvedStudySet {
+        id: set.id.clone(),
+        name: set.name.clone(),
+        items,
+    })
+}
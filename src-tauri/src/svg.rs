@@ -0,0 +1,187 @@
+use std::fs;
+use std::path::Path;
+
+use tauri::{AppHandle, Manager};
+
+use crate::color::Rgba;
+use crate::pdf::{load_dims_from_file, load_strokes_from_file, DrawingToolType, Stroke};
+use crate::state::AppState;
+use crate::stroke_queue::flush_pending_strokes;
+
+// Highlighters are drawn see-through even when stored fully opaque
+const MAX_HIGHLIGHTER_OPACITY: f64 = 0.5;
+// drawn instead of a color that doesn't parse (e.g. from a hand-edited strokes.json)
+const FALLBACK_COLOR: Rgba = Rgba {
+    r: 0,
+    g: 0,
+    b: 0,
+    a: 255,
+};
+
+// The color a stroke is drawn in, black with a warning when its color doesn't parse. Shared
+// with the flattened pdf export so one bad stroke doesn't fail a whole export.
+pub(crate) fn stroke_color(stroke: &Stroke) -> Rgba {
+    stroke.color.parse::<Rgba>().unwrap_or_else(|e| {
+        log::warn!("{e}, drawing the stroke in black");
+        FALLBACK_COLOR
+    })
+}
+
+fn stroke_element(stroke: &Stroke) -> Option<String> {
+    if matches!(stroke.tool, DrawingToolType::Eraser) || stroke.path.is_empty() {
+        return None;
+    }
+
+    let color = stroke_color(stroke);
+    let mut opacity = color.alpha() * stroke.opacity.clamp(0.0, 1.0);
+    if matches!(stroke.tool, DrawingToolType::Highlighter) {
+        opacity = opacity.min(MAX_HIGHLIGHTER_OPACITY);
+    }
+
+    let mut points: Vec<String> = stroke
+        .path
+        .iter()
+        .map(|p| format!("{:.2},{:.2}", p.x, p.y))
+        .collect();
+    // a single tap still draws a dot thanks to the round caps
+    if points.len() == 1 {
+        points.push(points[0].clone());
+    }
+
+    Some(format!(
+        r#"  <polyline points="{}" fill="none" stroke="rgb({},{},{})" stroke-opacity="{:.3}" stroke-width="{}" stroke-linecap="round" stroke-linejoin="round"/>"#,
+        points.join(" "),
+        color.r,
+        color.g,
+        color.b,
+        opacity,
+        stroke.thickness,
+    ))
+}
+
+// Stroke coordinates are page points with a top-left origin, which is SVG's own system,
+// so the viewBox is simply the page size.
+fn page_svg(width: f32, height: f32, strokes: &[Stroke]) -> String {
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {width} {height}" width="{width}" height="{height}">"#
+    );
+    svg.push('\n');
+
+    for stroke in strokes {
+        if let Some(element) = stroke_element(stroke) {
+            svg.push_str(&element);
+            svg.push('\n');
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[tauri::command]
+pub fn export_page_annotations_svg(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    page: u32,
+) -> Result<String, String> {
+    log::info!("Exporting annotations of page {page} of pdf {pdf_id} as svg");
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let folder_path = app_data_dir.join(format!("pdf_{pdf_id}"));
+    let dims = load_dims_from_file(&folder_path.join("dims.json"))?;
+    let size = dims
+        .inner
+        .get(&page)
+        .ok_or(format!("No dimensions recorded for page {page}"))?;
+
+    flush_pending_strokes(&app_handle, pdf_id)?;
+    let strokes = {
+        let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+        let _guard = lock.lock().unwrap();
+        load_strokes_from_file(&app_handle, &folder_path.join("strokes.json"))?
+    };
+    let page_strokes = strokes
+        .inner
+        .get(&page)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+
+    Ok(page_svg(size.width, size.height, page_strokes))
+}
+
+// Writes page_{n}.svg for every page with strokes, returns how many files were written
+#[tauri::command]
+pub fn export_all_annotations_svg(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    output_dir: String,
+) -> Result<u32, String> {
+    log::info!("Exporting annotations of pdf {pdf_id} as svg to {output_dir}");
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let folder_path = app_data_dir.join(format!("pdf_{pdf_id}"));
+    let dims = load_dims_from_file(&folder_path.join("dims.json"))?;
+
+    flush_pending_strokes(&app_handle, pdf_id)?;
+    let strokes = {
+        let lock = app_handle.state::<AppState>().pdf_lock(pdf_id);
+        let _guard = lock.lock().unwrap();
+        load_strokes_from_file(&app_handle, &folder_path.join("strokes.json"))?
+    };
+
+    let output_dir = Path::new(&output_dir);
+    fs::create_dir_all(output_dir).map_err(|e| e.to_string())?;
+
+    let mut pages: Vec<_> = strokes
+        .inner
+        .iter()
+        .filter(|(_, page_strokes)| !page_strokes.is_empty())
+        .collect();
+    pages.sort_by_key(|(page, _)| **page);
+
+    let mut written = 0;
+    for (page, page_strokes) in pages {
+        let Some(size) = dims.inner.get(page) else {
+            log::warn!("Skipping page {page} of pdf {pdf_id}, no dimensions recorded");
+            continue;
+        };
+
+        let svg = page_svg(size.width, size.height, page_strokes);
+        fs::write(output_dir.join(format!("page_{page}.svg")), svg).map_err(|e| e.to_string())?;
+        written += 1;
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pdf::StrokePath;
+
+    use super::*;
+
+    #[test]
+    fn draws_unparseable_colors_in_the_fallback() {
+        let stroke = Stroke {
+            id: None,
+            tool: DrawingToolType::Pen,
+            color: "not a color".to_string(),
+            opacity: 1.0,
+            thickness: 2,
+            path: vec![StrokePath { x: 1.0, y: 2.0 }],
+            created_at: 0,
+        };
+
+        let element = stroke_element(&stroke).unwrap();
+        assert!(element.contains(r#"stroke="rgb(0,0,0)""#));
+        assert!(element.contains(r#"points="1.00,2.00 1.00,2.00""#));
+    }
+}
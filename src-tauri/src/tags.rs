@@ -0,0 +1,230 @@
+use std::collections::HashSet;
+
+use tauri::{AppHandle, Manager};
+
+use crate::collections::{collections_file_path, read_collections};
+use crate::error::AppError;
+use crate::pdf::{read_pdf_entries, write_pdf_entries, PdfEntry};
+use crate::state::AppState;
+
+const MAX_TAG_LEN: usize = 40;
+
+fn normalize_tag(tag: &str) -> Result<String, AppError> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Err(AppError::InvalidInput("Tag cannot be empty".to_string()));
+    }
+    if tag.chars().count() > MAX_TAG_LEN {
+        return Err(AppError::InvalidInput(format!(
+            "Tags can be at most {MAX_TAG_LEN} characters"
+        )));
+    }
+    Ok(tag.to_string())
+}
+
+// Tags compare case-insensitively, the first spelling used is kept
+fn has_tag(entry: &PdfEntry, tag: &str) -> bool {
+    entry
+        .tags
+        .iter()
+        .any(|existing| existing.to_lowercase() == tag.to_lowercase())
+}
+
+// The entries retag_pdfs changes
+enum TagTargets<'a> {
+    // every id has to exist
+    Pdfs(HashSet<u64>),
+    // the members of a collection that are still in the registry
+    Collection(&'a str),
+}
+
+// Members of a collection that are in `pdfs`
+fn collection_pdf_ids(
+    app_handle: &AppHandle,
+    collection_id: &str,
+    pdfs: &[PdfEntry],
+) -> Result<HashSet<u64>, AppError> {
+    let data = read_collections(&collections_file_path(app_handle)?)?;
+    let collection = data
+        .collections
+        .iter()
+        .find(|collection| collection.id == collection_id)
+        .ok_or_else(|| AppError::NotFound("Collection not found".to_string()))?;
+
+    Ok(collection
+        .pdf_ids
+        .keys()
+        .filter_map(|id| id.parse::<u64>().ok())
+        .filter(|id| pdfs.iter().any(|pdf| pdf.id == *id))
+        .collect())
+}
+
+// Adds or removes `tag` on the entries in `pdf_ids`, returns how many changed
+fn apply_tag(pdfs: &mut [PdfEntry], pdf_ids: &HashSet<u64>, tag: &str, add: bool) -> usize {
+    let mut changed = 0;
+    for pdf in pdfs.iter_mut().filter(|pdf| pdf_ids.contains(&pdf.id)) {
+        if add && !has_tag(pdf, tag) {
+            pdf.tags.push(tag.to_string());
+            changed += 1;
+        } else if !add && has_tag(pdf, tag) {
+            pdf.tags
+                .retain(|existing| existing.to_lowercase() != tag.to_lowercase());
+            changed += 1;
+        }
+    }
+    changed
+}
+
+// Adds or removes `tag` on `targets` with a single registry write, resolved under the registry
+// lock so a pdf removed meanwhile isn't written back. Nothing is changed when a listed id
+// doesn't exist. Returns how many entries actually changed.
+fn retag_pdfs(
+    app_handle: &AppHandle,
+    targets: TagTargets,
+    tag: &str,
+    add: bool,
+) -> Result<usize, AppError> {
+    let tag = normalize_tag(tag)?;
+
+    let app_data_dir = app_handle.path().app_data_dir()?;
+    let state_path = app_data_dir.join("pdfs.json");
+
+    let state = app_handle.state::<AppState>();
+    let _guard = state.registry_lock.lock().unwrap();
+
+    let mut pdfs = read_pdf_entries(&state_path)?;
+    let pdf_ids = match targets {
+        TagTargets::Pdfs(pdf_ids) => {
+            if let Some(missing) = pdf_ids
+                .iter()
+                .find(|id| !pdfs.iter().any(|pdf| pdf.id == **id))
+            {
+                return Err(AppError::NotFound(format!(
+                    "PDF with id {missing} not found"
+                )));
+            }
+            pdf_ids
+        }
+        TagTargets::Collection(collection_id) => {
+            collection_pdf_ids(app_handle, collection_id, &pdfs)?
+        }
+    };
+
+    let changed = apply_tag(&mut pdfs, &pdf_ids, &tag, add);
+    if changed > 0 {
+        write_pdf_entries(app_handle, &state_path, &pdfs)?;
+    }
+    Ok(changed)
+}
+
+// Returns false when the pdf already had the tag
+#[tauri::command]
+pub fn tag_pdf(app_handle: AppHandle, pdf_id: u64, tag: String) -> Result<bool, AppError> {
+    log::info!("Tagging pdf {pdf_id} with {tag}");
+    let targets = TagTargets::Pdfs(HashSet::from([pdf_id]));
+    Ok(retag_pdfs(&app_handle, targets, &tag, true)? > 0)
+}
+
+// Returns false when the pdf didn't have the tag
+#[tauri::command]
+pub fn untag_pdf(app_handle: AppHandle, pdf_id: u64, tag: String) -> Result<bool, AppError> {
+    log::info!("Untagging pdf {pdf_id} from {tag}");
+    let targets = TagTargets::Pdfs(HashSet::from([pdf_id]));
+    Ok(retag_pdfs(&app_handle, targets, &tag, false)? > 0)
+}
+
+// Tags every listed pdf at once, entries that already have the tag are left as they are.
+// Returns how many were newly tagged.
+#[tauri::command]
+pub fn tag_pdfs(app_handle: AppHandle, pdf_ids: Vec<u64>, tag: String) -> Result<usize, AppError> {
+    log::info!("Tagging {} pdf(s) with {tag}", pdf_ids.len());
+    retag_pdfs(
+        &app_handle,
+        TagTargets::Pdfs(pdf_ids.into_iter().collect()),
+        &tag,
+        true,
+    )
+}
+
+// Returns how many of the listed pdfs had the tag
+#[tauri::command]
+pub fn untag_pdfs(
+    app_handle: AppHandle,
+    pdf_ids: Vec<u64>,
+    tag: String,
+) -> Result<usize, AppError> {
+    log::info!("Untagging {} pdf(s) from {tag}", pdf_ids.len());
+    retag_pdfs(
+        &app_handle,
+        TagTargets::Pdfs(pdf_ids.into_iter().collect()),
+        &tag,
+        false,
+    )
+}
+
+// Tags every member of a collection, returns how many were newly tagged
+#[tauri::command]
+pub fn tag_collection(
+    app_handle: AppHandle,
+    collection_id: String,
+    tag: String,
+) -> Result<usize, AppError> {
+    log::info!("Tagging members of collection {collection_id} with {tag}");
+
+    retag_pdfs(
+        &app_handle,
+        TagTargets::Collection(&collection_id),
+        &tag,
+        true,
+    )
+}
+
+// Returns how many members of the collection had the tag
+#[tauri::command]
+pub fn untag_collection(
+    app_handle: AppHandle,
+    collection_id: String,
+    tag: String,
+) -> Result<usize, AppError> {
+    log::info!("Untagging members of collection {collection_id} from {tag}");
+
+    retag_pdfs(
+        &app_handle,
+        TagTargets::Collection(&collection_id),
+        &tag,
+        false,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: u64, tags: &[&str]) -> PdfEntry {
+        let mut entry = PdfEntry::new(
+            id,
+            String::new(),
+            String::new(),
+            String::new(),
+            format!("{id}.pdf"),
+            None,
+            None,
+        );
+        entry.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        entry
+    }
+
+    #[test]
+    fn tags_compare_case_insensitively() {
+        let mut pdfs = vec![entry(1, &["Exam"]), entry(2, &[]), entry(3, &[])];
+        let ids = HashSet::from([1, 2]);
+
+        assert_eq!(apply_tag(&mut pdfs, &ids, "exam", true), 1);
+        assert_eq!(pdfs[0].tags, vec!["Exam"]);
+        assert_eq!(pdfs[1].tags, vec!["exam"]);
+        assert!(pdfs[2].tags.is_empty());
+
+        assert_eq!(apply_tag(&mut pdfs, &ids, "EXAM", false), 2);
+        assert!(pdfs.iter().all(|pdf| pdf.tags.is_empty()));
+    }
+}
@@ -0,0 +1,183 @@
+use std::fmt;
+
+use pdfium_render::prelude::*;
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::pdf::register_generated_pdf;
+
+// Spacing between ruled lines, grid lines and dots, in points
+const LINE_SPACING: f32 = 24.0;
+const GRID_SPACING: f32 = 20.0;
+const DOT_SPACING: f32 = 20.0;
+const MARGIN: f32 = 36.0;
+
+// PDF viewers reject pages larger than 14400pt (200in) on either side
+const MAX_PAGE_SIDE: f32 = 14400.0;
+const MAX_BLANK_PAGES: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageTemplate {
+    Blank,
+    Lined,
+    Grid,
+    Dots,
+}
+
+impl fmt::Display for PageTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PageTemplate::Blank => "Blank",
+            PageTemplate::Lined => "Lined",
+            PageTemplate::Grid => "Grid",
+            PageTemplate::Dots => "Dotted",
+        };
+        f.write_str(name)
+    }
+}
+
+fn guide_color() -> PdfColor {
+    PdfColor::new(190, 200, 215, 255)
+}
+
+// Draws the template background procedurally, in pdf coordinates (bottom-left origin)
+fn draw_template(page: &mut PdfPage, template: PageTemplate) -> Result<(), String> {
+    let width = page.width().value;
+    let height = page.height().value;
+    let stroke_width = PdfPoints::new(0.5);
+    let objects = page.objects_mut();
+
+    match template {
+        PageTemplate::Blank => {}
+        PageTemplate::Lined => {
+            // leave a header band at the top, like a paper notebook
+            let mut y = height - MARGIN - LINE_SPACING;
+            while y > MARGIN {
+                objects
+                    .create_path_object_line(
+                        PdfPoints::new(MARGIN),
+                        PdfPoints::new(y),
+                        PdfPoints::new(width - MARGIN),
+                        PdfPoints::new(y),
+                        guide_color(),
+                        stroke_width,
+                    )
+                    .map_err(|e| e.to_string())?;
+                y -= LINE_SPACING;
+            }
+        }
+        PageTemplate::Grid => {
+            let mut x = GRID_SPACING;
+            while x < width {
+                objects
+                    .create_path_object_line(
+                        PdfPoints::new(x),
+                        PdfPoints::ZERO,
+                        PdfPoints::new(x),
+                        PdfPoints::new(height),
+                        guide_color(),
+                        stroke_width,
+                    )
+                    .map_err(|e| e.to_string())?;
+                x += GRID_SPACING;
+            }
+
+            let mut y = GRID_SPACING;
+            while y < height {
+                objects
+                    .create_path_object_line(
+                        PdfPoints::ZERO,
+                        PdfPoints::new(y),
+                        PdfPoints::new(width),
+                        PdfPoints::new(y),
+                        guide_color(),
+                        stroke_width,
+                    )
+                    .map_err(|e| e.to_string())?;
+                y += GRID_SPACING;
+            }
+        }
+        PageTemplate::Dots => {
+            let mut y = DOT_SPACING;
+            while y < height {
+                let mut x = DOT_SPACING;
+                while x < width {
+                    objects
+                        .create_path_object_circle_at(
+                            PdfPoints::new(x),
+                            PdfPoints::new(y),
+                            PdfPoints::new(0.8),
+                            None,
+                            None,
+                            Some(guide_color()),
+                        )
+                        .map_err(|e| e.to_string())?;
+                    x += DOT_SPACING;
+                }
+                y += DOT_SPACING;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Inserts a template page of the given size at `index` (0-based)
+pub(crate) fn insert_template_page(
+    document: &mut PdfDocument,
+    index: PdfPageIndex,
+    width: f32,
+    height: f32,
+    template: PageTemplate,
+) -> Result<(), String> {
+    let size = PdfPagePaperSize::Custom(PdfPoints::new(width), PdfPoints::new(height));
+    let mut page = document
+        .pages_mut()
+        .create_page_at_index(size, index)
+        .map_err(|e| e.to_string())?;
+
+    draw_template(&mut page, template)
+}
+
+#[tauri::command]
+pub fn create_blank_pdf(
+    app_handle: AppHandle,
+    width: f32,
+    height: f32,
+    page_count: u32,
+    template: PageTemplate,
+) -> Result<u64, String> {
+    log::info!("Creating {page_count} page {template} pdf ({width}x{height})");
+
+    if !(1.0..=MAX_PAGE_SIDE).contains(&width) || !(1.0..=MAX_PAGE_SIDE).contains(&height) {
+        return Err(format!(
+            "Page size must be between 1 and {MAX_PAGE_SIDE} points per side"
+        ));
+    }
+    if page_count == 0 || page_count > MAX_BLANK_PAGES {
+        return Err(format!(
+            "Page count must be between 1 and {MAX_BLANK_PAGES}"
+        ));
+    }
+
+    let entry = register_generated_pdf(
+        &app_handle,
+        format!("{template} notebook.pdf"),
+        |pdfium, clone_path| {
+            let mut document = pdfium.create_new_pdf().map_err(|e| e.to_string())?;
+            for index in 0..page_count {
+                insert_template_page(
+                    &mut document,
+                    index as PdfPageIndex,
+                    width,
+                    height,
+                    template,
+                )?;
+            }
+            document.save_to_file(clone_path).map_err(|e| e.to_string())
+        },
+    )?;
+
+    Ok(entry.id)
+}
@@ -0,0 +1,472 @@
+use std::collections::HashMap;
+use std::fs;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+
+use pdfium_render::prelude::*;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::pdf::{find_pdf_entry, string_key_to_u32};
+use crate::persistence::{write_json, FileKind};
+use crate::state::AppState;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextSource {
+    #[default]
+    Native,
+    Ocr,
+}
+
+// Word position in page points, top-left origin like strokes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WordBox {
+    pub(crate) text: String,
+    pub(crate) x: f32,
+    pub(crate) y: f32,
+    pub(crate) width: f32,
+    pub(crate) height: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageText {
+    pub(crate) text: String,
+    // non-whitespace characters, 0 means the page has no text layer
+    pub(crate) char_count: usize,
+    #[serde(default)]
+    pub(crate) source: TextSource,
+    #[serde(default)]
+    pub(crate) words: Vec<WordBox>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PdfPagesText {
+    #[serde(flatten)]
+    #[serde(deserialize_with = "string_key_to_u32")]
+    pub(crate) inner: HashMap<u32, PageText>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextStats {
+    word_count: usize,
+    // non-whitespace characters, like PageText::char_count
+    char_count: usize,
+    // indexed by page number - 1, pages without a text layer count 0
+    page_word_counts: Vec<usize>,
+}
+
+// Pages read so far by a command that needs the text of every page
+#[derive(Debug, Clone, Serialize)]
+pub struct TextProgress {
+    pdf_id: u64,
+    page: u32,
+    total: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TextHit {
+    page: u32,
+    // in characters from the start of the page's text
+    char_offset: usize,
+    snippet: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextExportFormat {
+    Plain,
+    Markdown,
+}
+
+pub(crate) fn text_cache_path(app_data_dir: &Path, pdf_id: u64) -> PathBuf {
+    app_data_dir.join(format!("pdf_{pdf_id}/text.json"))
+}
+
+// Derived from text.json, removed whenever that changes
+pub(crate) fn text_stats_path(app_data_dir: &Path, pdf_id: u64) -> PathBuf {
+    app_data_dir.join(format!("pdf_{pdf_id}/text_stats.json"))
+}
+
+pub(crate) fn load_text_cache(path: &Path) -> Result<PdfPagesText, String> {
+    if !path.exists() {
+        return Ok(PdfPagesText::default());
+    }
+
+    let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    if data.trim().is_empty() {
+        return Ok(PdfPagesText::default());
+    }
+
+    serde_json::from_str::<PdfPagesText>(&data).map_err(|e| e.to_string())
+}
+
+const MAX_SEARCH_RESULTS: u32 = 500;
+// characters of context on each side of a hit
+const SNIPPET_CONTEXT: usize = 40;
+
+fn read_page_text(page: &PdfPage) -> Result<PageText, String> {
+    let text = page.text().map_err(|e| e.to_string())?.all();
+    let char_count = text.chars().filter(|c| !c.is_whitespace()).count();
+    Ok(PageText {
+        text,
+        char_count,
+        source: TextSource::Native,
+        words: Vec::new(),
+    })
+}
+
+// Returns the text of the requested pages (all pages when None), extracting and caching
+// whatever text.json doesn't hold yet.
+pub(crate) fn cached_pages_text(
+    app_handle: &AppHandle,
+    pdf_id: u64,
+    pages: Option<&[u32]>,
+) -> Result<PdfPagesText, String> {
+    let pages = match pages {
+        Some(pages) => PageSelection::Pages(pages),
+        None => PageSelection::All,
+    };
+    cached_pages_text_with_progress(app_handle, pdf_id, pages, |_, _, _| {
+        ControlFlow::Continue(())
+    })
+}
+
+// Pages cached_pages_text_with_progress reads
+#[derive(Clone, Copy)]
+enum PageSelection<'a> {
+    All,
+    Pages(&'a [u32]),
+    // from a page to the end of the document
+    From(u32),
+}
+
+// `cached_pages_text`, calling `on_page(page, text, total)` after each requested page. Breaking
+// out of `on_page` stops before the next page; the pages read until then are still cached and
+// returned.
+fn cached_pages_text_with_progress(
+    app_handle: &AppHandle,
+    pdf_id: u64,
+    pages: PageSelection,
+    mut on_page: impl FnMut(u32, &PageText, u32) -> ControlFlow<()>,
+) -> Result<PdfPagesText, String> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let pdf_entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+    let cache_path = text_cache_path(&app_data_dir, pdf_id);
+
+    let state = app_handle.state::<AppState>();
+    let pdfium = state.pdfium()?;
+    let lock = state.pdf_lock(pdf_id);
+    let _guard = lock.lock().unwrap();
+
+    let mut cache = load_text_cache(&cache_path)?;
+
+    let document = pdfium
+        .load_pdf_from_file(&pdf_entry.clone_path, None)
+        .map_err(|e| e.to_string())?;
+    let page_count = document.pages().len() as u32;
+
+    let mut wanted: Vec<u32> = match pages {
+        PageSelection::Pages(pages) => pages.to_vec(),
+        PageSelection::All => (1..=page_count).collect(),
+        PageSelection::From(start_page) => (start_page..=page_count).collect(),
+    };
+    let out_of_range = match pages {
+        PageSelection::From(start_page) => Some(start_page).filter(|p| *p == 0 || *p > page_count),
+        _ => wanted.iter().copied().find(|p| *p == 0 || *p > page_count),
+    };
+    if let Some(page) = out_of_range {
+        return Err(format!(
+            "Page {page} is out of range (document has {page_count} pages)"
+        ));
+    }
+
+    let total = wanted.len() as u32;
+    let mut read = wanted.len();
+    let mut changed = false;
+    for (index, page_no) in wanted.iter().enumerate() {
+        if !cache.inner.contains_key(page_no) {
+            let page = document
+                .pages()
+                .get((page_no - 1) as u16)
+                .map_err(|e| e.to_string())?;
+            cache.inner.insert(*page_no, read_page_text(&page)?);
+            changed = true;
+        }
+        if on_page(*page_no, &cache.inner[page_no], total).is_break() {
+            read = index + 1;
+            break;
+        }
+    }
+    wanted.truncate(read);
+
+    if changed {
+        write_json(app_handle, &cache_path, &cache, FileKind::Text)?;
+    }
+
+    cache.inner.retain(|page_no, _| wanted.contains(page_no));
+    Ok(cache)
+}
+
+#[tauri::command]
+pub fn page_has_text(app_handle: AppHandle, pdf_id: u64, page_number: u32) -> Result<bool, String> {
+    log::info!("Checking text layer of pdf {pdf_id} page {page_number}");
+
+    let text = cached_pages_text(&app_handle, pdf_id, Some(&[page_number]))?;
+    Ok(text
+        .inner
+        .get(&page_number)
+        .is_some_and(|page| page.char_count > 0))
+}
+
+#[tauri::command]
+pub fn pdf_text_coverage(app_handle: AppHandle, pdf_id: u64) -> Result<f32, String> {
+    log::info!("Computing text coverage of pdf {pdf_id}");
+
+    let text = cached_pages_text(&app_handle, pdf_id, None)?;
+    if text.inner.is_empty() {
+        return Ok(0.0);
+    }
+
+    let with_text = text
+        .inner
+        .values()
+        .filter(|page| page.char_count > 0)
+        .count();
+    Ok(with_text as f32 / text.inner.len() as f32)
+}
+
+// Words are whitespace separated runs holding at least one letter or digit, so stray
+// punctuation isn't counted
+fn word_count(text: &str) -> usize {
+    text.split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .count()
+}
+
+// Word and character counts of the whole document, from the same text layer as the other text
+// commands, OCR'd text included. Reading every page of a long document takes a while, so
+// `text-stats-progress` is emitted per page. The result is kept in text_stats.json.
+#[tauri::command]
+pub async fn document_text_stats(app_handle: AppHandle, pdf_id: u64) -> Result<TextStats, String> {
+    log::info!("Computing text stats of pdf {pdf_id}");
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+    let stats_path = text_stats_path(&app_data_dir, pdf_id);
+    if stats_path.exists() {
+        let data = fs::read_to_string(&stats_path).map_err(|e| e.to_string())?;
+        match serde_json::from_str::<TextStats>(&data) {
+            Ok(stats) => return Ok(stats),
+            Err(e) => log::warn!("Recomputing unreadable text stats of pdf {pdf_id}: {e}"),
+        }
+    }
+
+    let text = cached_pages_text_with_progress(
+        &app_handle,
+        pdf_id,
+        PageSelection::All,
+        |page, _, total| {
+            app_handle
+                .emit(
+                    "text-stats-progress",
+                    TextProgress {
+                        pdf_id,
+                        page,
+                        total,
+                    },
+                )
+                .unwrap();
+            ControlFlow::Continue(())
+        },
+    )?;
+
+    let mut page_word_counts = vec![0; text.inner.len()];
+    let mut char_count = 0;
+    for (page_no, page) in &text.inner {
+        page_word_counts[(page_no - 1) as usize] = word_count(&page.text);
+        char_count += page.char_count;
+    }
+
+    let stats = TextStats {
+        word_count: page_word_counts.iter().sum(),
+        char_count,
+        page_word_counts,
+    };
+    write_json(&app_handle, &stats_path, &stats, FileKind::Text)?;
+
+    Ok(stats)
+}
+
+// Case-insensitive matches of `query` in `text`. Chars are lowercased one to one so offsets
+// into the lowercased text are offsets into `text`.
+fn find_hits(page_no: u32, text: &str, query: &[char]) -> Vec<TextHit> {
+    let chars: Vec<char> = text.chars().collect();
+    let lowered: Vec<char> = chars.iter().map(|c| lowercase_char(*c)).collect();
+
+    let mut hits = Vec::new();
+    let mut start = 0;
+    while start + query.len() <= lowered.len() {
+        if lowered[start..start + query.len()] != *query {
+            start += 1;
+            continue;
+        }
+
+        let from = start.saturating_sub(SNIPPET_CONTEXT);
+        let to = (start + query.len() + SNIPPET_CONTEXT).min(chars.len());
+        let snippet: String = chars[from..to].iter().collect();
+        hits.push(TextHit {
+            page: page_no,
+            char_offset: start,
+            snippet: snippet.split_whitespace().collect::<Vec<_>>().join(" "),
+        });
+        start += query.len();
+    }
+    hits
+}
+
+fn lowercase_char(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+// Searches from `start_page` on and stops at the end of the first page that brings the hits
+// to `max_results`. Only the pages searched are extracted into text.json.
+fn search_pages(
+    app_handle: &AppHandle,
+    pdf_id: u64,
+    query: &[char],
+    max_results: usize,
+    start_page: u32,
+) -> Result<Vec<TextHit>, String> {
+    let mut hits = Vec::new();
+    cached_pages_text_with_progress(
+        app_handle,
+        pdf_id,
+        PageSelection::From(start_page),
+        |page_no, page_text, _| {
+            if page_text.char_count > 0 {
+                hits.extend(find_hits(page_no, &page_text.text, query));
+            }
+            if hits.len() >= max_results {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        },
+    )?;
+
+    Ok(hits)
+}
+
+// Search for typing into a search box: stops once `max_results` hits are found instead of
+// scanning the whole document. A page's hits are never split, so the results can run past
+// `max_results` by the rest of the last page, and "more results" continue with `start_page`
+// set to the page after the last hit.
+#[tauri::command]
+pub fn search_pdf_text_limited(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    query: String,
+    max_results: u32,
+    start_page: Option<u32>,
+) -> Result<Vec<TextHit>, String> {
+    log::info!("Searching pdf {pdf_id} for {query:?} (max {max_results})");
+
+    let query: Vec<char> = query.trim().chars().map(lowercase_char).collect();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    if max_results == 0 || max_results > MAX_SEARCH_RESULTS {
+        return Err(format!(
+            "Max results must be between 1 and {MAX_SEARCH_RESULTS}, got {max_results}"
+        ));
+    }
+
+    search_pages(
+        &app_handle,
+        pdf_id,
+        &query,
+        max_results as usize,
+        start_page.unwrap_or(1),
+    )
+}
+
+// The text of every page in order. Plain text separates pages with a marker line, markdown
+// gives each page a heading under one with the document name.
+fn document_text(file_name: &str, text: &PdfPagesText, format: TextExportFormat) -> String {
+    let mut pages: Vec<(&u32, &PageText)> = text.inner.iter().collect();
+    pages.sort_by_key(|(page_no, _)| **page_no);
+
+    let mut output = String::new();
+    if matches!(format, TextExportFormat::Markdown) {
+        let title = file_name.strip_suffix(".pdf").unwrap_or(file_name);
+        output.push_str(&format!("# {title}\n\n"));
+    }
+
+    for (page_no, page) in pages {
+        match format {
+            TextExportFormat::Plain => output.push_str(&format!("----- Page {page_no} -----\n\n")),
+            TextExportFormat::Markdown => output.push_str(&format!("## Page {page_no}\n\n")),
+        }
+        // pages without a text layer, e.g. scans that weren't OCR'd
+        if page.char_count == 0 {
+            output.push_str("[no text]");
+        } else {
+            output.push_str(page.text.trim());
+        }
+        output.push_str("\n\n");
+    }
+
+    output
+}
+
+// Writes the text of the whole document to `output_path`, from text.json where it's already
+// extracted. Reading the remaining pages emits `text-export-progress`. Returns the path.
+#[tauri::command]
+pub async fn export_document_text(
+    app_handle: AppHandle,
+    pdf_id: u64,
+    output_path: String,
+    format: TextExportFormat,
+) -> Result<String, String> {
+    log::info!("Exporting text of pdf {pdf_id} to {output_path}");
+
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+    let pdf_entry = find_pdf_entry(&app_data_dir, pdf_id)?;
+
+    let text = cached_pages_text_with_progress(
+        &app_handle,
+        pdf_id,
+        PageSelection::All,
+        |page, _, total| {
+            app_handle
+                .emit(
+                    "text-export-progress",
+                    TextProgress {
+                        pdf_id,
+                        page,
+                        total,
+                    },
+                )
+                .unwrap();
+            ControlFlow::Continue(())
+        },
+    )?;
+
+    fs::write(
+        &output_path,
+        document_text(&pdf_entry.file_name, &text, format),
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(output_path)
+}
@@ -0,0 +1,56 @@
+use std::sync::Mutex;
+
+use chrono::{DateTime, Local, Utc};
+
+// Local time in generated file names (thumbnails, covers, ...), sorts chronologically
+const FILE_FORMAT: &str = "%Y%m%d_%H%M%S";
+// for names that can be generated several times a second
+const FILE_FORMAT_MS: &str = "%Y%m%d_%H%M%S%3f";
+// UTC time in collection ids and their created/updated times
+const ID_FORMAT: &str = "%Y%m%d%H%M%S%3f";
+
+// millisecond of the last id handed out by unique_id
+static LAST_ID_MS: Mutex<i64> = Mutex::new(0);
+
+pub(crate) fn file_timestamp() -> String {
+    Local::now().format(FILE_FORMAT).to_string()
+}
+
+pub(crate) fn file_timestamp_ms() -> String {
+    Local::now().format(FILE_FORMAT_MS).to_string()
+}
+
+pub(crate) fn id_timestamp() -> String {
+    Utc::now().format(ID_FORMAT).to_string()
+}
+
+// An id_timestamp that is strictly greater than every id handed out before in this process.
+// Ids asked for within the same millisecond take the following milliseconds, so they stay
+// unique and in creation order without changing format.
+pub(crate) fn unique_id() -> String {
+    let mut last = LAST_ID_MS.lock().unwrap();
+    let ms = Utc::now().timestamp_millis().max(*last + 1);
+    *last = ms;
+
+    DateTime::<Utc>::from_timestamp_millis(ms)
+        .unwrap_or_default()
+        .format(ID_FORMAT)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn unique_ids_never_repeat_within_a_millisecond() {
+        let ids: Vec<String> = (0..1000).map(|_| unique_id()).collect();
+
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), ids.len());
+        // same length, so string order is creation order
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(ids.iter().all(|id| id.len() == ids[0].len()));
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::error::AppError;
+use crate::pdf::register_pdf_file;
+use crate::settings::{patch_settings, AppSettingsPatch};
+use crate::state::AppState;
+
+// A file is registered once it has had no events for this long, copies in progress keep
+// producing them
+const DEBOUNCE: Duration = Duration::from_millis(1500);
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PdfAutoRegistered {
+    pdf_id: u64,
+    path: String,
+    archived_annotations: bool,
+}
+
+fn is_pdf(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("pdf"))
+}
+
+// Registers a settled file unless the library already has the same content
+fn register_dropped_pdf(app_handle: &AppHandle, path: &Path) -> Result<(), AppError> {
+    if !path.is_file() {
+        // moved away or deleted again before it settled
+        return Ok(());
+    }
+
+    let path = path.to_string_lossy().into_owned();
+    let Some(registered) = register_pdf_file(app_handle.clone(), path.clone(), true)? else {
+        log::info!("Skipping {path}, it is already in the library");
+        return Ok(());
+    };
+    app_handle.emit(
+        "pdf-auto-registered",
+        PdfAutoRegistered {
+            pdf_id: registered.pdf_id,
+            path,
+            archived_annotations: registered.archived_annotations,
+        },
+    )?;
+    Ok(())
+}
+
+// Collects pdf paths from the watcher and registers each once it settles. Ends when the
+// watcher, and with it the sender, is dropped.
+fn debounce_events(app_handle: AppHandle, events: Receiver<PathBuf>) {
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+
+    loop {
+        // a steady stream of events for one file mustn't hold back the others, so settled
+        // paths are checked after every event, not only when the channel goes quiet
+        match events.recv_timeout(POLL_INTERVAL) {
+            Ok(path) => {
+                pending.insert(path, Instant::now());
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        if app_handle.state::<AppState>().is_shutting_down() {
+            break;
+        }
+
+        let settled: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, last_event)| last_event.elapsed() >= DEBOUNCE)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in settled {
+            pending.remove(&path);
+            if let Err(e) = register_dropped_pdf(&app_handle, &path) {
+                log::error!("Failed to register {path:?} from the watch folder: {e}");
+            }
+        }
+    }
+}
+
+fn start_watcher(app_handle: &AppHandle, folder: &Path) -> Result<RecommendedWatcher, AppError> {
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                for path in event.paths.into_iter().filter(|path| is_pdf(path)) {
+                    let _ = sender.send(path);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("Watch folder error: {e}"),
+        })
+        .map_err(|e| AppError::Other(e.to_string()))?;
+    watcher
+        .watch(folder, RecursiveMode::NonRecursive)
+        .map_err(|e| AppError::Other(e.to_string()))?;
+
+    let app_handle = app_handle.clone();
+    thread::spawn(move || debounce_events(app_handle, receiver));
+
+    Ok(watcher)
+}
+
+// Starts watching the folder saved in the settings, called once at startup
+pub(crate) fn init_watch_folder(app_handle: &AppHandle) {
+    let state = app_handle.state::<AppState>();
+    let Some(folder) = state.settings().watch_folder else {
+        return;
+    };
+
+    match start_watcher(app_handle, Path::new(&folder)) {
+        Ok(watcher) => *state.watcher.lock().unwrap() = Some(watcher),
+        Err(e) => log::warn!("Failed to watch {folder}: {e}"),
+    }
+}
+
+// Registers pdfs dropped into `path` from now on, replacing any folder watched before. Files
+// already in the folder are left alone, as are ones whose content is already in the library.
+// Each registration emits `pdf-auto-registered`. Persisted, so watching resumes on the next
+// start.
+#[tauri::command]
+pub fn set_watch_folder(app_handle: AppHandle, path: String) -> Result<(), AppError> {
+    log::info!("Watching {path} for new pdfs");
+
+    if !Path::new(&path).is_dir() {
+        return Err(AppError::NotFound(format!("Folder not found: {path}")));
+    }
+
+    let watcher = start_watcher(&app_handle, Path::new(&path))?;
+    patch_settings(
+        &app_handle,
+        AppSettingsPatch {
+            watch_folder: Some(Some(path)),
+            ..AppSettingsPatch::default()
+        },
+    )?;
+    // the previous watcher is dropped here, ending its debounce thread
+    *app_handle.state::<AppState>().watcher.lock().unwrap() = Some(watcher);
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn clear_watch_folder(app_handle: AppHandle) -> Result<(), AppError> {
+    log::info!("Clearing the watch folder");
+
+    *app_handle.state::<AppState>().watcher.lock().unwrap() = None;
+    patch_settings(
+        &app_handle,
+        AppSettingsPatch {
+            watch_folder: Some(None),
+            ..AppSettingsPatch::default()
+        },
+    )?;
+
+    Ok(())
+}
@@ -125,8 +125,14 @@ export function useSavePdfStrokes() {
     }) => {
       try {
         logger.debug('Saving strokes to backend', { pdfId, pageId, stroke })
-        await invoke('save_pdf_strokes', { pdfId, pageId, stroke })
+        // the backend assigns an id when the stroke has none
+        const strokeId = await invoke<string>('save_pdf_strokes', {
+          pdfId,
+          pageId,
+          stroke,
+        })
         logger.info('Preferences saved successfully')
+        return strokeId
       } catch (error) {
         const message =
           error instanceof Error ? error.message : 'Unknown error occurred'